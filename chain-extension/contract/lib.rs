@@ -5,7 +5,10 @@ use ink::prelude::vec::Vec;
 
 #[ink::contract(env = crate::ReceiptRegistryDefaultEnvironment)]
 mod dog_owner {
-    use ink::prelude::string::String;
+    use ink::prelude::{string::String, vec::Vec};
+
+    /// Most logs requested from the chain extension at once.
+    const LOGS_PAGE_SIZE: u32 = 16;
 
     #[ink(storage)]
     pub struct Dog {
@@ -26,11 +29,27 @@ mod dog_owner {
 
         #[ink(message)]
         pub fn process(&mut self, chain_id: u32, block_number: u64, receipt_hash: types::H256) {
-            let logs = self
-                .env()
-                .extension()
-                .logs_for_receipt(chain_id, block_number, receipt_hash.0, self.contract)
-                .expect("failed to retrieve logs");
+            let mut logs = Vec::new();
+            // Page through the logs, a single page of all of them may not fit the output buffer
+            loop {
+                let (total, page) = self
+                    .env()
+                    .extension()
+                    .logs_for_receipt(
+                        chain_id,
+                        block_number,
+                        receipt_hash.0,
+                        self.contract,
+                        Some(logs.len() as u32),
+                        Some(LOGS_PAGE_SIZE),
+                    )
+                    .expect("failed to retrieve logs");
+                let done = page.is_empty();
+                logs.extend(page);
+                if done || logs.len() as u32 >= total {
+                    break;
+                }
+            }
 
             for (topics, _) in logs {
                 for topic in topics {
@@ -57,6 +76,8 @@ pub trait ReceiptRegistryExtension {
 
     // 0004 stands for the registered ID on chain of the chain extension
     // 0001 stands for method that called using this interface.
+    // Returns the total number of logs of the contract in the receipt and the page of them after
+    // `offset`, at most `limit` logs. `None` reads from the first log and all of them.
     #[ink(extension = 0x00040001)]
     #[ink(handle_status = false)]
     fn logs_for_receipt(
@@ -64,7 +85,9 @@ pub trait ReceiptRegistryExtension {
         block_number: u64,
         receipt_hash: [u8; 32],
         contract_address: [u8; 20],
-    ) -> Result<Vec<Log>, Error>;
+        offset: Option<u32>,
+        limit: Option<u32>,
+    ) -> Result<(u32, Vec<Log>), Error>;
 }
 
 /// chain extension errors.
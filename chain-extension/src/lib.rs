@@ -6,6 +6,12 @@ use frame_support::{
     dispatch::Encode, inherent::Vec, sp_runtime::DispatchError, sp_std::marker::PhantomData,
 };
 use pallet_contracts::chain_extension::{ChainExtension, Environment, Ext, InitState, RetVal};
+use parity_scale_codec::{Decode, DecodeAll};
+
+/// Encoded length of the arguments sent by contracts built before paging.
+pub const LEGACY_ARGUMENTS_LEN: u32 = 4 + 8 + 32 + 20;
+/// Encoded length of a `None` and a `Some` paging field.
+const PAGING_FIELD_LENS: [u32; 2] = [1, 1 + 4];
 
 #[derive(parity_scale_codec::Encode, parity_scale_codec::Decode, Debug, Clone, PartialEq)]
 pub struct Arguments {
@@ -13,6 +19,72 @@ pub struct Arguments {
     pub block_number: u64,
     pub receipt_hash: [u8; 32],
     pub contract_address: [u8; 20],
+    /// Matching logs to skip, `None` to start at the first one.
+    pub offset: Option<u32>,
+    /// Most logs to return, `None` for all of them.
+    pub limit: Option<u32>,
+}
+
+impl Arguments {
+    /// Checks the length of the encoded arguments before they are read, only the legacy layout
+    /// and the paged one with each of its fields either set or not are accepted.
+    pub fn check_len(len: u32) -> Result<(), DispatchError> {
+        if len < LEGACY_ARGUMENTS_LEN {
+            return Err(DispatchError::Other(
+                "Receipt registry arguments are shorter than the 64 bytes of the legacy layout",
+            ));
+        }
+        let paging_len = len - LEGACY_ARGUMENTS_LEN;
+        let valid = paging_len == 0
+            || PAGING_FIELD_LENS.iter().any(|offset| {
+                PAGING_FIELD_LENS
+                    .iter()
+                    .any(|limit| offset + limit == paging_len)
+            });
+        if !valid {
+            return Err(DispatchError::Other(
+                "Receipt registry arguments have a length of neither the legacy nor the paged layout",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Decodes the arguments of a call. Contracts built before paging send only the first four
+    /// fields, so their call is read as a request for all logs. Returns whether the paging fields
+    /// were sent, as only such callers expect the total count in the output.
+    pub fn decode_compat(mut input: &[u8]) -> Result<(Self, bool), parity_scale_codec::Error> {
+        let (chain_id, block_number, receipt_hash, contract_address) =
+            <(u32, u64, [u8; 32], [u8; 20])>::decode(&mut input)?;
+        let paged = !input.is_empty();
+        let (offset, limit) = if paged {
+            <(Option<u32>, Option<u32>)>::decode_all(&mut input)?
+        } else {
+            (None, None)
+        };
+
+        Ok((
+            Arguments {
+                chain_id,
+                block_number,
+                receipt_hash,
+                contract_address,
+                offset,
+                limit,
+            },
+            paged,
+        ))
+    }
+}
+
+/// Returns the total number of `items` and the page of them selected by `offset` and `limit`.
+fn page<T>(items: Vec<T>, offset: Option<u32>, limit: Option<u32>) -> (u32, Vec<T>) {
+    let total = items.len() as u32;
+    let page = items
+        .into_iter()
+        .skip(offset.unwrap_or_default() as usize)
+        .take(limit.map_or(usize::MAX, |limit| limit as usize))
+        .collect();
+    (total, page)
 }
 
 enum ReceiptRegistryFuncId {
@@ -60,20 +132,33 @@ where
             ReceiptRegistryFuncId::LogsForReceipt => {
                 // TODO: proper weight calculation
 
-                let Arguments {
-                    chain_id,
-                    block_number,
-                    receipt_hash,
-                    contract_address,
-                } = env.read_as_unbounded(env.in_len())?;
+                let in_len = env.in_len();
+                Arguments::check_len(in_len)?;
+                let input = env.read(in_len)?;
+                let (
+                    Arguments {
+                        chain_id,
+                        block_number,
+                        receipt_hash,
+                        contract_address,
+                        offset,
+                        limit,
+                    },
+                    paged,
+                ) = Arguments::decode_compat(&input)
+                    .map_err(|_| DispatchError::Other("Invalid receipt registry arguments"))?;
 
                 log::debug!(
                     target: TARGET,
                     "logs_for_receipt with receipt hash: {receipt_hash:?} and contract address: {contract_address:?}",
                 );
 
+                // Contracts only query EVM chains so far
                 let (chain_id, receipt_hash, contract_address) = (
-                    webb_proposals::TypedChainId::Evm(chain_id),
+                    pallet_receipt_registry::typed_chain_id(
+                        pallet_receipt_registry::ChainKind::Evm,
+                        chain_id,
+                    ),
                     types::H256(receipt_hash),
                     types::H160(contract_address),
                 );
@@ -91,7 +176,7 @@ where
 
                 let logs: Vec<_> = data
                     .into_iter()
-                    .filter(|log| log.address == contract_address)
+                    .filter(|log| log.address.ct_eq(&contract_address))
                     .map(|log| {
                         let topics: Vec<_> = log
                             .topics
@@ -102,7 +187,14 @@ where
                     })
                     .collect();
 
-                let logs = logs.encode();
+                // A paged call gets the total count along with the page, so the contract knows
+                // when to stop
+                let (total, logs) = page(logs, offset, limit);
+                let logs = if paged {
+                    (total, logs).encode()
+                } else {
+                    logs.encode()
+                };
                 env.write(&logs, false, None)?;
 
                 Ok(RetVal::Converging(1))
@@ -110,3 +202,104 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use parity_scale_codec::Encode;
+
+    use frame_support::sp_runtime::DispatchError;
+
+    use super::{page, Arguments, LEGACY_ARGUMENTS_LEN};
+
+    fn arguments(offset: Option<u32>, limit: Option<u32>) -> Arguments {
+        Arguments {
+            chain_id: 5,
+            block_number: 8652100,
+            receipt_hash: [0x11; 32],
+            contract_address: [0x22; 20],
+            offset,
+            limit,
+        }
+    }
+
+    #[test]
+    fn arguments_without_paging_request_all_logs() {
+        let args = arguments(None, None);
+        let legacy = (
+            args.chain_id,
+            args.block_number,
+            args.receipt_hash,
+            args.contract_address,
+        )
+            .encode();
+        assert_eq!(Arguments::decode_compat(&legacy), Ok((args.clone(), false)));
+
+        assert_eq!(
+            Arguments::decode_compat(&args.encode()),
+            Ok((args.clone(), true))
+        );
+        let paged = arguments(Some(100), Some(100));
+        assert_eq!(
+            Arguments::decode_compat(&paged.encode()),
+            Ok((paged.clone(), true))
+        );
+
+        let mut trailing = paged.encode();
+        trailing.push(0);
+        assert!(Arguments::decode_compat(&trailing).is_err());
+    }
+
+    #[test]
+    fn arguments_length_is_checked() {
+        let legacy = (5u32, 8652100u64, [0x11u8; 32], [0x22u8; 20]).encode();
+        assert_eq!(legacy.len() as u32, LEGACY_ARGUMENTS_LEN);
+        assert_eq!(Arguments::check_len(LEGACY_ARGUMENTS_LEN), Ok(()));
+        for (offset, limit) in [
+            (None, None),
+            (Some(1), None),
+            (None, Some(1)),
+            (Some(1), Some(1)),
+        ] {
+            let encoded = arguments(offset, limit).encode();
+            assert_eq!(Arguments::check_len(encoded.len() as u32), Ok(()));
+        }
+
+        // A truncated buffer is rejected before it is read and fails to decode without a panic
+        let short = &legacy[..10];
+        assert_eq!(
+            Arguments::check_len(short.len() as u32),
+            Err(DispatchError::Other(
+                "Receipt registry arguments are shorter than the 64 bytes of the legacy layout"
+            ))
+        );
+        assert!(Arguments::decode_compat(short).is_err());
+        assert!(Arguments::check_len(0).is_err());
+
+        // Between the layouts, or past the longest one
+        for len in [65, 67, 73, 75, 1024] {
+            assert_eq!(
+                Arguments::check_len(len),
+                Err(DispatchError::Other(
+                    "Receipt registry arguments have a length of neither the legacy nor the paged layout"
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn large_log_set_is_read_in_two_pages() {
+        let logs: Vec<u32> = (0..300).collect();
+
+        let (total, first) = page(logs.clone(), None, Some(200));
+        assert_eq!(total, 300);
+        assert_eq!(first, (0..200).collect::<Vec<_>>());
+
+        let (total, second) = page(logs.clone(), Some(first.len() as u32), Some(200));
+        assert_eq!(total, 300);
+        assert_eq!(second, (200..300).collect::<Vec<_>>());
+
+        // Past the end, the contract stops once it has read `total` logs
+        assert_eq!(page(logs.clone(), Some(300), Some(200)), (300, vec![]));
+        assert_eq!(page(logs.clone(), None, None), (300, logs));
+    }
+}
@@ -96,11 +96,11 @@
           cargoCheckCommand = "true";
         };
 
-        # Check that the pallets built in WASM modes
+        # Check that the pallets and the no_std proof generator built in WASM modes
         commonWasmArgs = commonArgs // {
           pname = "workspace-wasm";
           cargoExtraArgs =
-                "-p pallet-receipt-registry -p pallet-chain-extension-receipt-registry -p pallet-evm-eth-receipt-provider --target wasm32-unknown-unknown --no-default-features";
+                "-p pallet-receipt-registry -p pallet-chain-extension-receipt-registry -p pallet-evm-eth-receipt-provider -p types --target wasm32-unknown-unknown --no-default-features --features types/merkle-proof";
         };
 
         craneLibLLvmTools = craneLib.overrideToolchain
@@ -124,12 +124,26 @@
         wasmWorkspace = craneLib.buildPackage (commonWasmArgs // {
           cargoArtifacts = wasmCargoArtifacts;
         });
+
+        # Check that the `types` library builds without warnings in WASM for a feature set,
+        # its tests need `std` and are covered by the workspace checks.
+        typesFeatureCheck = name: features: craneLib.cargoClippy (commonWasmArgs // {
+          pname = "types-${name}";
+          cargoArtifacts = wasmCargoArtifacts;
+          cargoExtraArgs = "-p types --target wasm32-unknown-unknown --no-default-features ${features}";
+          cargoClippyExtraArgs = "-- --deny warnings";
+        });
       in
       {
         checks = {
           # Build the crate as part of `nix flake check` for convenience
           inherit workspace wasmWorkspace;
 
+          # The primitives only, proof verification as used by the pallet, and proof generation
+          types-primitives = typesFeatureCheck "primitives" "";
+          types-proofs = typesFeatureCheck "proofs" "--features types/serde,types/proofs";
+          types-merkle-proof = typesFeatureCheck "merkle-proof" "--features types/merkle-proof";
+
           # Run clippy (and deny all warnings) on the crate source,
           # again, resuing the dependency artifacts from above.
           #
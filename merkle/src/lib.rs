@@ -1,5 +1 @@
-pub mod node;
-
-mod trie;
-
-pub use trie::{IterativeTrie, PatriciaTrie};
+pub use types::merkle::{node, IterativeTrie, PatriciaTrie};
@@ -30,6 +30,10 @@ pub fn load_block(test_suit: &str) -> (H256, BlockHeader) {
         blob_gas_used: execution_block.blob_gas_used.map(|a| a.as_u64()),
         excess_blob_gas: execution_block.excess_blob_gas.map(|a| a.as_u64()),
         parent_beacon_block_root: execution_block.parent_beacon_block_root.map(|a| H256(a.0)),
+        requests_hash: execution_block
+            .other
+            .get_deserialized::<ethers::types::H256>("requestsHash")
+            .map(|hash| H256(hash.unwrap().0)),
     };
 
     let hash = H256(execution_block.hash.unwrap().0);
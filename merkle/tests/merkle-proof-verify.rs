@@ -1,17 +1,17 @@
 use merkle_generator::IterativeTrie;
-use types::{EventProof, H256};
+use types::{EventProof, MerkleProof, ValidationError, H256};
 
 mod common;
 
 fn merkle_proof_test(test_block: &str, test_block_receipts: &str) {
     let (hash, block_header) = common::load_block(test_block);
-    let block_hash = H256::hash(&block_header);
+    let block_hash = block_header.hash();
     assert_eq!(hash, block_hash);
 
     let receipts = common::load_receipts(test_block_receipts);
     let mut trie = merkle_generator::PatriciaTrie::new();
     receipts.iter().enumerate().for_each(|(i, receipt)| {
-        trie.insert(alloy_rlp::encode(i), alloy_rlp::encode(receipt));
+        trie.insert(alloy_rlp::encode(i), receipt.encode_to_vec());
     });
 
     for (i, receipt) in receipts.iter().enumerate() {
@@ -26,8 +26,21 @@ fn merkle_proof_test(test_block: &str, test_block_receipts: &str) {
             merkle_proof_of_receipt: proof,
         };
 
-        proof.validate().unwrap()
+        proof.validate().unwrap();
+
+        let built = EventProof::build(block_header.clone(), block_hash, &receipts, i).unwrap();
+        assert_eq!(built.merkle_proof_of_receipt, proof.merkle_proof_of_receipt);
+        assert_eq!(built.transaction_receipt, proof.transaction_receipt);
+        assert_eq!(
+            built.transaction_receipt_hash,
+            proof.transaction_receipt_hash
+        );
     }
+
+    assert!(matches!(
+        EventProof::build(block_header, block_hash, &receipts, receipts.len()),
+        Err(ValidationError::TransactionIndexOutOfBounds { index, len }) if index == len
+    ));
 }
 
 #[test]
@@ -50,3 +63,61 @@ fn merkle_proof_8652100() {
     let block_receipts = include_str!("../tests/suits/block_8652100_receipts.json");
     merkle_proof_test(test_block, block_receipts)
 }
+
+#[test]
+fn merkle_proof_key_does_not_match_index() {
+    let (_, block_header) = common::load_block(include_str!("../tests/suits/block_8652100.json"));
+    let receipts =
+        common::load_receipts(include_str!("../tests/suits/block_8652100_receipts.json"));
+    let mut trie = merkle_generator::PatriciaTrie::new();
+    receipts.iter().enumerate().for_each(|(i, receipt)| {
+        trie.insert(alloy_rlp::encode(i), receipt.encode_to_vec());
+    });
+
+    // Proof is built for the receipt 1, but claims to be for the index 2.
+    let mut merkle_proof = trie.merkle_proof(alloy_rlp::encode(1usize));
+    merkle_proof.key = alloy_rlp::encode(2usize);
+
+    let proof = EventProof {
+        block_hash: block_header.hash(),
+        block_header,
+        transaction_receipt: receipts[1].clone(),
+        transaction_receipt_hash: H256::hash(&receipts[1]),
+        merkle_proof_of_receipt: merkle_proof,
+    };
+
+    assert!(matches!(
+        proof.validate(),
+        Err(ValidationError::ProofPathMismatch {
+            transaction_index: 2
+        })
+    ));
+}
+
+#[test]
+fn proof_generators_agree_on_receipts_root() {
+    let (_, block_header) = common::load_block(include_str!("../tests/suits/block_17819525.json"));
+    let receipts =
+        common::load_receipts(include_str!("../tests/suits/block_17819525_receipts.json"));
+    let mut trie = merkle_generator::PatriciaTrie::new();
+    receipts.iter().enumerate().for_each(|(i, receipt)| {
+        trie.insert(alloy_rlp::encode(i), receipt.encode_to_vec());
+    });
+    assert_eq!(trie.root_hash(), block_header.receipts_root);
+
+    for (i, receipt) in receipts.iter().enumerate() {
+        let generated = trie.merkle_proof(alloy_rlp::encode(i));
+        let built = MerkleProof::from_transactions(&receipts, i).unwrap();
+
+        assert_eq!(
+            generated.merkle_root(receipt),
+            block_header.receipts_root,
+            "proof of the merkle crate for receipt {i}"
+        );
+        assert_eq!(
+            built.merkle_root(receipt),
+            block_header.receipts_root,
+            "proof of the types crate for receipt {i}"
+        );
+    }
+}
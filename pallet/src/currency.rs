@@ -0,0 +1,78 @@
+use frame_support::{
+    pallet_prelude::{DispatchResult, MaxEncodedLen},
+    sp_runtime::traits::Saturating,
+    sp_std::marker::PhantomData,
+    traits::{
+        tokens::{fungibles, Preservation},
+        Currency, ExistenceRequirement, Get,
+    },
+    Parameter,
+};
+
+/// Moves proof rewards and deposits between the treasury and relayers.
+///
+/// [`NativeCurrency`] pays in a native [`Currency`], e.g. the balances pallet of the runtime.
+/// [`FungibleAsset`] pays in a single asset of a `fungibles` implementation instead, e.g.
+/// `pallet-assets`.
+pub trait ProofCurrency<AccountId> {
+    type Balance: Parameter + MaxEncodedLen + Copy + Default + Saturating;
+
+    fn transfer(source: &AccountId, dest: &AccountId, amount: Self::Balance) -> DispatchResult;
+
+    /// Like [`ProofCurrency::transfer`], but fails instead of leaving `source` below the
+    /// existential deposit.
+    fn transfer_keep_alive(
+        source: &AccountId,
+        dest: &AccountId,
+        amount: Self::Balance,
+    ) -> DispatchResult;
+}
+
+/// Pays rewards and collects deposits in the native currency `C`.
+pub struct NativeCurrency<C>(PhantomData<C>);
+
+impl<AccountId, C: Currency<AccountId>> ProofCurrency<AccountId> for NativeCurrency<C> {
+    type Balance = C::Balance;
+
+    fn transfer(source: &AccountId, dest: &AccountId, amount: Self::Balance) -> DispatchResult {
+        <C as Currency<AccountId>>::transfer(source, dest, amount, ExistenceRequirement::AllowDeath)
+    }
+
+    fn transfer_keep_alive(
+        source: &AccountId,
+        dest: &AccountId,
+        amount: Self::Balance,
+    ) -> DispatchResult {
+        <C as Currency<AccountId>>::transfer(source, dest, amount, ExistenceRequirement::KeepAlive)
+    }
+}
+
+/// Pays rewards and collects deposits in the `AssetId` asset of `Assets`.
+pub struct FungibleAsset<Assets, AssetId>(PhantomData<(Assets, AssetId)>);
+
+impl<AccountId, Assets, AssetId> ProofCurrency<AccountId> for FungibleAsset<Assets, AssetId>
+where
+    Assets: fungibles::Mutate<AccountId>,
+    AssetId: Get<Assets::AssetId>,
+{
+    type Balance = Assets::Balance;
+
+    fn transfer(source: &AccountId, dest: &AccountId, amount: Self::Balance) -> DispatchResult {
+        Assets::transfer(
+            AssetId::get(),
+            source,
+            dest,
+            amount,
+            Preservation::Expendable,
+        )
+        .map(|_| ())
+    }
+
+    fn transfer_keep_alive(
+        source: &AccountId,
+        dest: &AccountId,
+        amount: Self::Balance,
+    ) -> DispatchResult {
+        Assets::transfer(AssetId::get(), source, dest, amount, Preservation::Preserve).map(|_| ())
+    }
+}
@@ -2,17 +2,52 @@
 #![feature(slice_pattern)]
 
 use frame_support::sp_std::{convert::TryInto, prelude::*};
-use frame_support::traits::ExistenceRequirement::AllowDeath;
 use frame_support::{pallet_prelude::ensure, traits::Get, PalletId};
 pub use pallet::*;
-use types::{EventProof, TransactionReceipt};
-use types::{H160, H256};
+use types::{EventProof, TransactionReceipt, ValidationError};
+use types::{Log, H160, H256};
 use webb_proposals::TypedChainId;
 
-use frame_support::{sp_runtime::traits::AccountIdConversion, traits::Currency};
+use frame_support::sp_runtime::traits::AccountIdConversion;
+
+mod currency;
+pub use currency::{FungibleAsset, NativeCurrency, ProofCurrency};
+
+mod runtime_api;
+pub use runtime_api::ReceiptRegistryApi;
+
+pub mod weights;
+
+/// Kind of chain a `u32` chain id refers to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChainKind {
+    Evm,
+    Substrate,
+    Cosmos,
+}
+
+/// The storage key of the `kind` chain `chain_id`. Contracts and the relayer identify chains by a
+/// bare `u32`, and this is the only place it is turned into a [`TypedChainId`], so watching a new
+/// kind of chain only needs a [`ChainKind`] variant here.
+pub fn typed_chain_id(kind: ChainKind, chain_id: u32) -> TypedChainId {
+    match kind {
+        ChainKind::Evm => TypedChainId::Evm(chain_id),
+        ChainKind::Substrate => TypedChainId::Substrate(chain_id),
+        ChainKind::Cosmos => TypedChainId::Cosmos(chain_id),
+    }
+}
+
+/// Most chains returned by one [`Pallet::all_watched_contracts`] call.
+pub const MAX_WATCHED_CONTRACTS_PAGE: u32 = 100;
+
+/// Most blocks read by one [`Pallet::processed_receipts_in_range`] call.
+pub const MAX_PROCESSED_RECEIPTS_RANGE: u64 = 1000;
+
+/// Most receipts returned by one [`Pallet::processed_receipts_page`] call.
+pub const MAX_PROCESSED_RECEIPTS_PAGE: u32 = 100;
 
 type BalanceOf<T> =
-    <<T as Config>::Currency as Currency<<T as frame_system::Config>::AccountId>>::Balance;
+    <<T as Config>::Currency as ProofCurrency<<T as frame_system::Config>::AccountId>>::Balance;
 
 type CurrencyOf<T> = <T as Config>::Currency;
 
@@ -41,9 +76,29 @@ pub mod pallet {
         #[pallet::constant]
         type PalletId: Get<PalletId>;
 
-        type Currency: Currency<<Self as frame_system::Config>::AccountId>;
+        /// Currency proof rewards are paid and deposits are collected in, e.g.
+        /// `NativeCurrency<Balances>` or `FungibleAsset<Assets, RewardAssetId>`.
+        type Currency: ProofCurrency<<Self as frame_system::Config>::AccountId>;
 
         type PrivilegedOrigin: EnsureOrigin<<Self as frame_system::Config>::RuntimeOrigin>;
+
+        /// How many blocks behind the latest finalized block a proven block may be.
+        #[pallet::constant]
+        type MaxProofAge: Get<u64>;
+
+        /// Most logs the receipt of a proof may have.
+        #[pallet::constant]
+        type MaxLogsPerReceipt: Get<u32>;
+
+        /// Most data bytes a log of a proven receipt may have.
+        #[pallet::constant]
+        type MaxLogDataLen: Get<u32>;
+
+        /// Whether proofs may be submitted by unsigned `submit_proof_unsigned` calls, for a
+        /// trusted relayer that is neither charged deposits nor paid rewards. Should be `false`
+        /// unless the relayer is part of the deployment.
+        #[pallet::constant]
+        type AllowUnsignedProofs: Get<bool>;
     }
 
     /// ProcessedReceipts
@@ -98,6 +153,39 @@ pub mod pallet {
     pub(crate) type ProofReward<T: Config> =
         StorageMap<_, Blake2_128Concat, TypedChainId, BalanceOf<T>, ValueQuery>;
 
+    /// whether proof submission is halted
+    #[pallet::storage]
+    #[pallet::getter(fn paused)]
+    pub(crate) type Paused<T: Config> = StorageValue<_, bool, ValueQuery>;
+
+    /// account paying rewards and collecting deposits instead of the pallet account
+    #[pallet::storage]
+    #[pallet::getter(fn reward_account)]
+    pub(crate) type RewardAccount<T: Config> =
+        StorageValue<_, <T as frame_system::Config>::AccountId, OptionQuery>;
+
+    /// total rewards paid to each relayer
+    #[pallet::storage]
+    #[pallet::getter(fn relayer_rewards)]
+    pub(crate) type RelayerRewards<T: Config> = StorageMap<
+        _,
+        Blake2_128Concat,
+        <T as frame_system::Config>::AccountId,
+        BalanceOf<T>,
+        ValueQuery,
+    >;
+
+    /// whether only allowed relayers may submit proofs
+    #[pallet::storage]
+    #[pallet::getter(fn restrict_relayers)]
+    pub(crate) type RestrictRelayers<T: Config> = StorageValue<_, bool, ValueQuery>;
+
+    /// relayers allowed to submit proofs when submission is restricted
+    #[pallet::storage]
+    #[pallet::getter(fn allowed_relayers)]
+    pub(crate) type AllowedRelayers<T: Config> =
+        StorageMap<_, Blake2_128Concat, <T as frame_system::Config>::AccountId, (), OptionQuery>;
+
     /************* STORAGE ************ */
 
     #[pallet::event]
@@ -121,6 +209,61 @@ pub mod pallet {
             proof_deposit: BalanceOf<T>,
             proof_reward: BalanceOf<T>,
         },
+        /// The proof is valid, but the receipt has no logs of watched contracts
+        ProofAcceptedNoMatch {
+            typed_chain_id: TypedChainId,
+            receipt_hash: H256,
+        },
+        Paused,
+        Unpaused,
+        UpdateRewardAccount {
+            account: Option<<T as frame_system::Config>::AccountId>,
+        },
+        /// The relayer was paid `reward` for a proof, `total` is all it has earned so far
+        RelayerRewarded {
+            relayer: <T as frame_system::Config>::AccountId,
+            reward: BalanceOf<T>,
+            total: BalanceOf<T>,
+        },
+        /// The watched contracts of a chain were replaced by a list of `count` addresses
+        WatchedContractsReplaced {
+            typed_chain_id: TypedChainId,
+            count: u32,
+        },
+        AddedAllowedRelayer {
+            relayer: <T as frame_system::Config>::AccountId,
+        },
+        RemovedAllowedRelayer {
+            relayer: <T as frame_system::Config>::AccountId,
+        },
+        UpdateRestrictRelayers {
+            restricted: bool,
+        },
+        /// The receipt was marked as processed by the privileged origin, without a proof
+        ForceMarkedProcessed {
+            typed_chain_id: TypedChainId,
+            block_number: u64,
+            receipt_hash: H256,
+        },
+        /// `amount` of the deposits collected in the pallet account was moved to `dest`
+        DepositsSwept {
+            dest: <T as frame_system::Config>::AccountId,
+            amount: BalanceOf<T>,
+        },
+        /// `removed` storage entries of the chain were removed by a `clear_chain` call
+        ChainStorageRemoved {
+            typed_chain_id: TypedChainId,
+            removed: u32,
+        },
+        /// The chain has no storage entries left
+        ChainCleared {
+            typed_chain_id: TypedChainId,
+        },
+        /// The address was not added, the chain already watches it
+        ContractAddressAlreadyWatched {
+            typed_chain_id: TypedChainId,
+            address: H160,
+        },
     }
 
     #[pallet::error]
@@ -135,6 +278,22 @@ pub mod pallet {
         NoMonitoredAddressesForChain,
         /// Too many watched contracts
         TooManyAddresses,
+        /// Proof submission is paused
+        Paused,
+        /// The block is too far behind the latest finalized block
+        ProofTooOld,
+        /// The relayer is not allowed to submit proofs
+        RelayerNotAllowed,
+        /// The receipt has too many logs or a log with too much data
+        ReceiptTooLarge,
+        /// Unsigned proofs are not allowed by the runtime
+        UnsignedProofsDisabled,
+        /// A proof of the receipt has already been accepted
+        ReceiptAlreadyProcessed,
+        /// The bloom of the receipt doesn't match its logs
+        BloomMismatch,
+        /// The receipt has no logs of watched contracts
+        NoWatchedLogs,
     }
 
     #[pallet::hooks]
@@ -143,7 +302,17 @@ pub mod pallet {
     #[pallet::call]
     impl<T: Config> Pallet<T> {
         /// submitting proof that a receipt has been included in a block
-        #[pallet::weight({6})]
+        ///
+        /// the block is trusted by its hash only: the proof is accepted if the hash the light
+        /// client stores for the claimed block number is the hash of the submitted header.
+        /// `pallet-eth2-light-client` only stores hashes of finalized execution blocks, so a
+        /// header of another fork, or claiming the number of another block, doesn't hash to the
+        /// stored hash and is rejected.
+        #[pallet::weight(weights::submit_proof::<T>(
+            event_proof.len() as u32,
+            weights::MAX_WATCHED_CONTRACTS,
+            false,
+        ))]
         #[pallet::call_index(6)]
         pub fn submit_proof(
             origin: OriginFor<T>,
@@ -151,70 +320,33 @@ pub mod pallet {
             event_proof: Vec<u8>,
         ) -> DispatchResultWithPostInfo {
             let validator = ensure_signed(origin)?;
-
-            // Create a str slice from the body.
-            let event_proof_str = frame_support::sp_std::str::from_utf8(&event_proof)
-                .map_err(|_| Error::<T>::ConvertToStringFailed)?;
-
-            let event_proof: EventProof =
-                serde_json::from_str(event_proof_str).map_err(|_| Error::<T>::DeserializeFail)?;
-
-            let finalized_execution_header_hash =
-                pallet_eth2_light_client::Pallet::<T>::finalized_execution_blocks(
-                    typed_chain_id,
-                    event_proof.block_header.number,
-                )
-                .ok_or(Error::<T>::HeaderHashDoesNotExist)?;
-
-            let block_hash = event_proof.block_hash;
-
+            ensure!(!Self::paused(), Error::<T>::Paused);
             ensure!(
-                block_hash.0 == finalized_execution_header_hash.0 .0,
-                Error::<T>::BlockHashesDoNotMatch,
+                Self::is_relayer_allowed(&validator),
+                Error::<T>::RelayerNotAllowed
             );
 
-            // 1 verifying its cryptographic integrity
-            ensure!(event_proof.validate().is_ok(), Error::<T>::VerifyProofFail);
+            let proof_len = event_proof.len() as u32;
+            let event_proof = Self::decode_proof(typed_chain_id, &event_proof)?;
 
-            let treasury = Self::account_id();
+            let treasury = Self::treasury_account();
             let transaction_receipt_hash: H256 = event_proof.transaction_receipt_hash;
 
-            // If the receipt proof has already been processed
-            let rewarded = if !<ProcessedReceiptsHash<T>>::contains_key(
-                typed_chain_id,
-                transaction_receipt_hash,
-            ) {
-                //2 checking the receipt includes a LOG emitted by a contract address we are watching.
-
-                let block_number = event_proof.block_header.number;
-                let mut rewarded = false;
-
-                let addresses = Self::watched_contracts(typed_chain_id);
-                ensure!(
-                    addresses.is_some(),
-                    Error::<T>::NoMonitoredAddressesForChain
-                );
+            // A duplicate is neither stored nor rewarded, it only pays the deposit, so the proof
+            // isn't verified again. `decode_proof` has still checked `block_hash` against the
+            // finalized block, only binding the header and receipt to it is skipped
+            let duplicate = Self::is_receipt_processed(typed_chain_id, transaction_receipt_hash);
+            let mut scanned_addresses = 0;
 
-                for address in addresses.expect("checked above") {
-                    if Self::is_contract_address_in_log(&event_proof.transaction_receipt, address) {
-                        ProcessedReceipts::<T>::insert(
-                            (typed_chain_id, block_number, transaction_receipt_hash),
-                            event_proof.transaction_receipt.receipt.logs.clone(),
-                        );
-                        ProcessedReceiptsHash::<T>::insert(
-                            typed_chain_id,
-                            transaction_receipt_hash,
-                            (),
-                        );
+            // 1 verifying its cryptographic integrity
+            if !duplicate {
+                Self::verify_proof(&event_proof)?;
+            }
 
-                        Self::deposit_event(Event::SubmitProcessedReceipts {
-                            typed_chain_id,
-                            block_number,
-                            receipt_hash: transaction_receipt_hash,
-                        });
-                        rewarded = true;
-                    }
-                }
+            // If the receipt proof has already been processed
+            let rewarded = if !duplicate {
+                let (rewarded, scanned) = Self::process_receipt(typed_chain_id, &event_proof)?;
+                scanned_addresses = scanned;
                 rewarded
             } else {
                 false
@@ -222,28 +354,40 @@ pub mod pallet {
 
             let _success = if rewarded {
                 // Rewarding relayer for submitting a proof of inclusion of a receipt
-                CurrencyOf::<T>::transfer(
-                    &treasury,
-                    &validator,
-                    Self::proof_reward(typed_chain_id),
-                    AllowDeath,
-                )
+                let reward = Self::proof_reward(typed_chain_id);
+                let success = CurrencyOf::<T>::transfer(&treasury, &validator, reward);
+                if success.is_ok() {
+                    let total = RelayerRewards::<T>::mutate(&validator, |total| {
+                        *total = total.saturating_add(reward);
+                        *total
+                    });
+                    Self::deposit_event(Event::RelayerRewarded {
+                        relayer: validator,
+                        reward,
+                        total,
+                    });
+                }
+                success
             } else {
                 // Validator
                 CurrencyOf::<T>::transfer(
                     &validator,
                     &treasury,
                     Self::proof_deposit(typed_chain_id),
-                    AllowDeath,
                 )
             };
 
             debug_assert!(_success.is_ok());
 
-            Ok(().into())
+            Ok(Some(weights::submit_proof::<T>(
+                proof_len,
+                scanned_addresses,
+                duplicate,
+            ))
+            .into())
         }
 
-        /// update watching address
+        /// update watching address, adding an already watched address changes nothing
         #[pallet::weight({7})]
         #[pallet::call_index(7)]
         pub fn update_watching_address(
@@ -254,8 +398,13 @@ pub mod pallet {
         ) -> DispatchResultWithPostInfo {
             T::PrivilegedOrigin::ensure_origin(origin)?;
 
+            let mut already_watched = false;
             let result =
                 WatchedContracts::<T>::mutate(typed_chain_id, |addresses| match (addresses, add) {
+                    (Some(ref mut addresses), true) if addresses.contains(&address) => {
+                        already_watched = true;
+                        Ok(())
+                    }
                     (Some(ref mut addresses), true) => addresses.try_push(address),
                     (Some(ref mut addresses), false) => {
                         addresses.retain(|&x| x != address);
@@ -275,7 +424,12 @@ pub mod pallet {
                 return Err(Error::<T>::TooManyAddresses.into());
             }
 
-            if add {
+            if already_watched {
+                Self::deposit_event(Event::ContractAddressAlreadyWatched {
+                    typed_chain_id,
+                    address,
+                });
+            } else if add {
                 Self::deposit_event(Event::AddedContractAddress {
                     typed_chain_id,
                     address,
@@ -312,6 +466,245 @@ pub mod pallet {
 
             Ok(().into())
         }
+
+        /// pause or resume proof submission
+        #[pallet::weight({9})]
+        #[pallet::call_index(9)]
+        pub fn set_paused(origin: OriginFor<T>, paused: bool) -> DispatchResultWithPostInfo {
+            T::PrivilegedOrigin::ensure_origin(origin)?;
+
+            Paused::<T>::put(paused);
+
+            if paused {
+                Self::deposit_event(Event::Paused);
+            } else {
+                Self::deposit_event(Event::Unpaused);
+            }
+
+            Ok(().into())
+        }
+
+        /// set the account paying rewards and collecting deposits, `None` to use the pallet account
+        #[pallet::weight({10})]
+        #[pallet::call_index(10)]
+        pub fn set_reward_account(
+            origin: OriginFor<T>,
+            account: Option<<T as frame_system::Config>::AccountId>,
+        ) -> DispatchResultWithPostInfo {
+            T::PrivilegedOrigin::ensure_origin(origin)?;
+
+            match &account {
+                Some(account) => RewardAccount::<T>::put(account),
+                None => RewardAccount::<T>::kill(),
+            }
+
+            Self::deposit_event(Event::UpdateRewardAccount { account });
+
+            Ok(().into())
+        }
+
+        /// replace all watched contracts of the chain at once, an empty list stops watching it.
+        /// duplicate addresses are only kept once
+        #[pallet::weight({11})]
+        #[pallet::call_index(11)]
+        pub fn set_watched_contracts(
+            origin: OriginFor<T>,
+            typed_chain_id: TypedChainId,
+            addresses: BoundedVec<H160, ConstU32<100>>,
+        ) -> DispatchResultWithPostInfo {
+            T::PrivilegedOrigin::ensure_origin(origin)?;
+
+            // Keep the first occurrence of every address, in the given order
+            let mut unique = Vec::with_capacity(addresses.len());
+            for address in addresses.into_inner() {
+                if !unique.contains(&address) {
+                    unique.push(address);
+                }
+            }
+            let addresses: BoundedVec<H160, ConstU32<100>> =
+                unique.try_into().expect("no longer than the given list");
+
+            let count = addresses.len() as u32;
+            if addresses.is_empty() {
+                WatchedContracts::<T>::remove(typed_chain_id);
+            } else {
+                WatchedContracts::<T>::insert(typed_chain_id, addresses);
+            }
+
+            Self::deposit_event(Event::WatchedContractsReplaced {
+                typed_chain_id,
+                count,
+            });
+
+            Ok(().into())
+        }
+
+        /// add or remove a relayer allowed to submit proofs when submission is restricted
+        #[pallet::weight({12})]
+        #[pallet::call_index(12)]
+        pub fn update_allowed_relayer(
+            origin: OriginFor<T>,
+            relayer: <T as frame_system::Config>::AccountId,
+            allowed: bool,
+        ) -> DispatchResultWithPostInfo {
+            T::PrivilegedOrigin::ensure_origin(origin)?;
+
+            if allowed {
+                AllowedRelayers::<T>::insert(&relayer, ());
+                Self::deposit_event(Event::AddedAllowedRelayer { relayer });
+            } else {
+                AllowedRelayers::<T>::remove(&relayer);
+                Self::deposit_event(Event::RemovedAllowedRelayer { relayer });
+            }
+
+            Ok(().into())
+        }
+
+        /// restrict proof submission to allowed relayers, or open it to any signed account
+        #[pallet::weight({13})]
+        #[pallet::call_index(13)]
+        pub fn set_restrict_relayers(
+            origin: OriginFor<T>,
+            restricted: bool,
+        ) -> DispatchResultWithPostInfo {
+            T::PrivilegedOrigin::ensure_origin(origin)?;
+
+            RestrictRelayers::<T>::put(restricted);
+
+            Self::deposit_event(Event::UpdateRestrictRelayers { restricted });
+
+            Ok(().into())
+        }
+
+        /// mark a receipt as processed without a proof, for migrations and recovery where the
+        /// off-chain data is authoritative. no reward is paid and no deposit taken
+        #[pallet::weight({14})]
+        #[pallet::call_index(14)]
+        pub fn force_mark_processed(
+            origin: OriginFor<T>,
+            typed_chain_id: TypedChainId,
+            block_number: u64,
+            receipt_hash: H256,
+            logs: Vec<Log>,
+        ) -> DispatchResultWithPostInfo {
+            T::PrivilegedOrigin::ensure_origin(origin)?;
+            ensure!(Self::logs_within_bounds(&logs), Error::<T>::ReceiptTooLarge);
+
+            ProcessedReceipts::<T>::insert((typed_chain_id, block_number, receipt_hash), logs);
+            ProcessedReceiptsHash::<T>::insert(typed_chain_id, receipt_hash, ());
+
+            Self::deposit_event(Event::ForceMarkedProcessed {
+                typed_chain_id,
+                block_number,
+                receipt_hash,
+            });
+
+            Ok(().into())
+        }
+
+        /// move `amount` of the deposits collected in the treasury account to `dest`. fails if the
+        /// treasury account would drop below the existential deposit
+        #[pallet::weight({15})]
+        #[pallet::call_index(15)]
+        pub fn sweep_deposits(
+            origin: OriginFor<T>,
+            dest: <T as frame_system::Config>::AccountId,
+            amount: BalanceOf<T>,
+        ) -> DispatchResultWithPostInfo {
+            T::PrivilegedOrigin::ensure_origin(origin)?;
+
+            CurrencyOf::<T>::transfer_keep_alive(&Self::treasury_account(), &dest, amount)?;
+
+            Self::deposit_event(Event::DepositsSwept { dest, amount });
+
+            Ok(().into())
+        }
+
+        /// remove at most `max_removals` storage entries of a decommissioned chain: processed
+        /// receipts, watched contracts and proof fees. call it again until `ChainCleared` is
+        /// emitted
+        #[pallet::weight(weights::clear_chain::<T>(*max_removals))]
+        #[pallet::call_index(16)]
+        pub fn clear_chain(
+            origin: OriginFor<T>,
+            typed_chain_id: TypedChainId,
+            max_removals: u32,
+        ) -> DispatchResultWithPostInfo {
+            T::PrivilegedOrigin::ensure_origin(origin)?;
+
+            let removed = Self::remove_chain_entries(typed_chain_id, max_removals);
+            Self::deposit_event(Event::ChainStorageRemoved {
+                typed_chain_id,
+                removed,
+            });
+            if !Self::has_chain_entries(typed_chain_id) {
+                Self::deposit_event(Event::ChainCleared { typed_chain_id });
+            }
+
+            Ok(Some(weights::clear_chain::<T>(removed)).into())
+        }
+
+        /// submitting proof of a receipt without a signed origin, for deployments where the
+        /// relayer is trusted infrastructure rather than paid per proof
+        ///
+        /// only accepted while `AllowUnsignedProofs` is set. No deposit is taken and no reward is
+        /// paid, the verification of the proof in `validate_unsigned` is what keeps invalid and
+        /// duplicate proofs out of the pool, as well as receipts without logs of watched contracts.
+        #[pallet::weight(weights::submit_proof_unsigned::<T>(
+            event_proof.len() as u32,
+            weights::MAX_WATCHED_CONTRACTS,
+        ))]
+        #[pallet::call_index(17)]
+        pub fn submit_proof_unsigned(
+            origin: OriginFor<T>,
+            typed_chain_id: TypedChainId,
+            event_proof: Vec<u8>,
+        ) -> DispatchResultWithPostInfo {
+            ensure_none(origin)?;
+
+            let proof_len = event_proof.len() as u32;
+            let event_proof = Self::check_unsigned_proof(typed_chain_id, &event_proof)?;
+            let (_, scanned_addresses) = Self::process_receipt(typed_chain_id, &event_proof)?;
+
+            Ok(Some(weights::submit_proof_unsigned::<T>(
+                proof_len,
+                scanned_addresses,
+            ))
+            .into())
+        }
+    }
+
+    #[pallet::validate_unsigned]
+    impl<T: Config> ValidateUnsigned for Pallet<T> {
+        type Call = Call<T>;
+
+        fn validate_unsigned(_source: TransactionSource, call: &Self::Call) -> TransactionValidity {
+            let Call::submit_proof_unsigned {
+                typed_chain_id,
+                event_proof,
+            } = call
+            else {
+                return InvalidTransaction::Call.into();
+            };
+
+            let event_proof = Self::check_unsigned_proof(*typed_chain_id, event_proof).map_err(
+                |err| match err {
+                    Error::<T>::UnsignedProofsDisabled
+                    | Error::<T>::Paused
+                    | Error::<T>::NoMonitoredAddressesForChain
+                    | Error::<T>::NoWatchedLogs => InvalidTransaction::Call,
+                    Error::<T>::ProofTooOld | Error::<T>::ReceiptAlreadyProcessed => {
+                        InvalidTransaction::Stale
+                    }
+                    _ => InvalidTransaction::BadProof,
+                },
+            )?;
+
+            ValidTransaction::with_tag_prefix("ReceiptRegistry")
+                .and_provides((*typed_chain_id, event_proof.transaction_receipt_hash.0))
+                .propagate(true)
+                .build()
+        }
     }
 }
 
@@ -320,16 +713,269 @@ impl<T: Config> Pallet<T> {
         <T as Config>::PalletId::get().into_account_truncating()
     }
 
+    /// The account rewards are paid from and deposits are paid to.
+    pub fn treasury_account() -> <T as frame_system::Config>::AccountId {
+        Self::reward_account().unwrap_or_else(Self::account_id)
+    }
+
+    /// Whether the logs of a receipt fit the `MaxLogsPerReceipt` and `MaxLogDataLen` bounds.
+    fn logs_within_bounds(logs: &[Log]) -> bool {
+        logs.len() <= T::MaxLogsPerReceipt::get() as usize
+            && logs
+                .iter()
+                .all(|log| log.data.len() <= T::MaxLogDataLen::get() as usize)
+    }
+
+    /// Decodes a submitted proof and checks it is of a receipt of a block the light client
+    /// finalized, recent enough and within the log bounds. The proof itself is not verified.
+    fn decode_proof(
+        typed_chain_id: TypedChainId,
+        event_proof: &[u8],
+    ) -> Result<EventProof, Error<T>> {
+        // Create a str slice from the body.
+        let event_proof_str = frame_support::sp_std::str::from_utf8(event_proof)
+            .map_err(|_| Error::<T>::ConvertToStringFailed)?;
+
+        let event_proof: EventProof =
+            serde_json::from_str(event_proof_str).map_err(|_| Error::<T>::DeserializeFail)?;
+
+        ensure!(
+            Self::logs_within_bounds(&event_proof.transaction_receipt.receipt.logs),
+            Error::<T>::ReceiptTooLarge
+        );
+
+        let latest_finalized_block =
+            pallet_eth2_light_client::Pallet::<T>::last_block_number(typed_chain_id);
+        ensure!(
+            latest_finalized_block.saturating_sub(event_proof.block_header.number)
+                <= T::MaxProofAge::get(),
+            Error::<T>::ProofTooOld
+        );
+
+        let finalized_execution_header_hash =
+            pallet_eth2_light_client::Pallet::<T>::finalized_execution_blocks(
+                typed_chain_id,
+                event_proof.block_header.number,
+            )
+            .ok_or(Error::<T>::HeaderHashDoesNotExist)?;
+
+        // `validate` binds the header to `block_hash`, and with it the block number
+        ensure!(
+            event_proof.block_hash.0 == finalized_execution_header_hash.0 .0,
+            Error::<T>::BlockHashesDoNotMatch,
+        );
+
+        Ok(event_proof)
+    }
+
+    /// Verifies the proof, telling a bloom that doesn't match the logs apart from the other
+    /// failures.
+    fn verify_proof(event_proof: &EventProof) -> Result<(), Error<T>> {
+        event_proof.validate().map_err(|err| match err {
+            ValidationError::BloomMismatch { .. } => Error::<T>::BloomMismatch,
+            _ => Error::<T>::VerifyProofFail,
+        })
+    }
+
+    /// Everything an unsigned proof is checked for, both in the pool and when dispatched: there
+    /// is no deposit to lose, so only a verified proof of a new receipt of a watched chain passes.
+    fn check_unsigned_proof(
+        typed_chain_id: TypedChainId,
+        event_proof: &[u8],
+    ) -> Result<EventProof, Error<T>> {
+        ensure!(
+            T::AllowUnsignedProofs::get(),
+            Error::<T>::UnsignedProofsDisabled
+        );
+        ensure!(!Self::paused(), Error::<T>::Paused);
+
+        let event_proof = Self::decode_proof(typed_chain_id, event_proof)?;
+        ensure!(
+            !Self::is_receipt_processed(typed_chain_id, event_proof.transaction_receipt_hash),
+            Error::<T>::ReceiptAlreadyProcessed
+        );
+        Self::verify_proof(&event_proof)?;
+        // Nobody pays for an unsigned proof, so one that stores nothing is kept out of the pool
+        let addresses = Self::watched_contracts(typed_chain_id)
+            .ok_or(Error::<T>::NoMonitoredAddressesForChain)?;
+        ensure!(
+            addresses
+                .iter()
+                .any(|address| event_proof.transaction_receipt.has_log_from(*address)),
+            Error::<T>::NoWatchedLogs
+        );
+
+        Ok(event_proof)
+    }
+
+    /// Stores the logs of a verified receipt if a watched contract of the chain emitted any.
+    /// Returns whether they were stored and how many watched addresses were scanned.
+    fn process_receipt(
+        typed_chain_id: TypedChainId,
+        event_proof: &EventProof,
+    ) -> Result<(bool, u32), Error<T>> {
+        //2 checking the receipt includes a LOG emitted by a contract address we are watching.
+
+        let block_number = event_proof.block_header.number;
+        let transaction_receipt_hash = event_proof.transaction_receipt_hash;
+        let mut stored = false;
+
+        let addresses = Self::watched_contracts(typed_chain_id)
+            .ok_or(Error::<T>::NoMonitoredAddressesForChain)?;
+        let scanned_addresses = addresses.len() as u32;
+        for address in addresses {
+            if event_proof.transaction_receipt.has_log_from(address) {
+                ProcessedReceipts::<T>::insert(
+                    (typed_chain_id, block_number, transaction_receipt_hash),
+                    event_proof.transaction_receipt.receipt.logs.clone(),
+                );
+                ProcessedReceiptsHash::<T>::insert(typed_chain_id, transaction_receipt_hash, ());
+
+                Self::deposit_event(Event::SubmitProcessedReceipts {
+                    typed_chain_id,
+                    block_number,
+                    receipt_hash: transaction_receipt_hash,
+                });
+                stored = true;
+            }
+        }
+        if !stored {
+            Self::deposit_event(Event::ProofAcceptedNoMatch {
+                typed_chain_id,
+                receipt_hash: transaction_receipt_hash,
+            });
+        }
+        Ok((stored, scanned_addresses))
+    }
+
+    /// Whether the account may submit proofs, any account may while submission is not restricted.
+    pub fn is_relayer_allowed(relayer: &<T as frame_system::Config>::AccountId) -> bool {
+        !Self::restrict_relayers() || AllowedRelayers::<T>::contains_key(relayer)
+    }
+
+    /// Removes at most `max_removals` storage entries of the chain, processed receipts first, and
+    /// returns how many were removed.
+    fn remove_chain_entries(typed_chain_id: TypedChainId, max_removals: u32) -> u32 {
+        let mut removed = 0;
+
+        let receipts = ProcessedReceipts::<T>::iter_key_prefix((typed_chain_id,))
+            .take(max_removals as usize)
+            .collect::<Vec<_>>();
+        for (block_number, receipt_hash) in receipts {
+            ProcessedReceipts::<T>::remove((typed_chain_id, block_number, receipt_hash));
+            removed += 1;
+        }
+
+        let hashes = ProcessedReceiptsHash::<T>::iter_key_prefix(typed_chain_id)
+            .take((max_removals - removed) as usize)
+            .collect::<Vec<_>>();
+        for receipt_hash in hashes {
+            ProcessedReceiptsHash::<T>::remove(typed_chain_id, receipt_hash);
+            removed += 1;
+        }
+
+        if removed < max_removals && WatchedContracts::<T>::contains_key(typed_chain_id) {
+            WatchedContracts::<T>::remove(typed_chain_id);
+            removed += 1;
+        }
+        if removed < max_removals && ProofDeposit::<T>::contains_key(typed_chain_id) {
+            ProofDeposit::<T>::remove(typed_chain_id);
+            removed += 1;
+        }
+        if removed < max_removals && ProofReward::<T>::contains_key(typed_chain_id) {
+            ProofReward::<T>::remove(typed_chain_id);
+            removed += 1;
+        }
+        removed
+    }
+
+    /// Whether any storage entry of the chain is left for [`Pallet::clear_chain`] to remove.
+    fn has_chain_entries(typed_chain_id: TypedChainId) -> bool {
+        ProcessedReceipts::<T>::iter_key_prefix((typed_chain_id,))
+            .next()
+            .is_some()
+            || ProcessedReceiptsHash::<T>::iter_key_prefix(typed_chain_id)
+                .next()
+                .is_some()
+            || WatchedContracts::<T>::contains_key(typed_chain_id)
+            || ProofDeposit::<T>::contains_key(typed_chain_id)
+            || ProofReward::<T>::contains_key(typed_chain_id)
+    }
+
+    /// Whether a proof for the receipt has already been accepted.
+    pub fn is_receipt_processed(typed_chain_id: TypedChainId, receipt_hash: H256) -> bool {
+        ProcessedReceiptsHash::<T>::contains_key(typed_chain_id, receipt_hash)
+    }
+
+    /// Watched contracts of every chain, at most `limit` (capped at
+    /// [`MAX_WATCHED_CONTRACTS_PAGE`]) chains at a time. To get the next page, pass the last chain
+    /// of the previous one as `start_after`. Chains come in storage order, not sorted.
+    pub fn all_watched_contracts(
+        start_after: Option<TypedChainId>,
+        limit: u32,
+    ) -> Vec<(TypedChainId, Vec<H160>)> {
+        let contracts = match start_after {
+            Some(typed_chain_id) => WatchedContracts::<T>::iter_from(
+                WatchedContracts::<T>::hashed_key_for(typed_chain_id),
+            ),
+            None => WatchedContracts::<T>::iter(),
+        };
+        contracts
+            .take(limit.min(MAX_WATCHED_CONTRACTS_PAGE) as usize)
+            .map(|(typed_chain_id, addresses)| (typed_chain_id, addresses.into_inner()))
+            .collect()
+    }
+
+    /// Receipts of the chain processed in blocks `from_block..=to_block`, ordered by block, or
+    /// `None` if the range spans more than [`MAX_PROCESSED_RECEIPTS_RANGE`] blocks.
+    pub fn processed_receipts_in_range(
+        typed_chain_id: TypedChainId,
+        from_block: u64,
+        to_block: u64,
+    ) -> Option<Vec<(u64, H256, Vec<Log>)>> {
+        if to_block.saturating_sub(from_block) >= MAX_PROCESSED_RECEIPTS_RANGE {
+            return None;
+        }
+        let receipts = (from_block..=to_block)
+            .flat_map(|block_number| {
+                ProcessedReceipts::<T>::iter_prefix((typed_chain_id, block_number))
+                    .map(move |(receipt_hash, logs)| (block_number, receipt_hash, logs))
+            })
+            .collect();
+        Some(receipts)
+    }
+
+    /// Receipts of the chain processed in any block, at most `limit` (capped at
+    /// [`MAX_PROCESSED_RECEIPTS_PAGE`]) at a time. To get the next page, pass the block and hash
+    /// of the last receipt of the previous one as `start_after`. Receipts come in storage order,
+    /// not sorted by block.
+    pub fn processed_receipts_page(
+        typed_chain_id: TypedChainId,
+        start_after: Option<(u64, H256)>,
+        limit: u32,
+    ) -> Vec<(u64, H256, Vec<Log>)> {
+        let receipts = match start_after {
+            Some((block_number, receipt_hash)) => ProcessedReceipts::<T>::iter_prefix_from(
+                (typed_chain_id,),
+                ProcessedReceipts::<T>::hashed_key_for((
+                    typed_chain_id,
+                    block_number,
+                    receipt_hash,
+                )),
+            ),
+            None => ProcessedReceipts::<T>::iter_prefix((typed_chain_id,)),
+        };
+        receipts
+            .take(limit.min(MAX_PROCESSED_RECEIPTS_PAGE) as usize)
+            .map(|((block_number, receipt_hash), logs)| (block_number, receipt_hash, logs))
+            .collect()
+    }
+
+    #[deprecated(note = "use `TransactionReceipt::has_log_from` instead")]
     pub fn is_contract_address_in_log(
         transaction_receipt: &TransactionReceipt,
         address: H160,
     ) -> bool {
-        let index_of_log_address = transaction_receipt
-            .receipt
-            .logs
-            .iter()
-            .position(|x| x.address == address);
-
-        index_of_log_address.is_some()
+        transaction_receipt.has_log_from(address)
     }
 }
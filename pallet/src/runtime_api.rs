@@ -0,0 +1,21 @@
+use frame_support::sp_std::vec::Vec;
+use types::{Log, H160, H256};
+use webb_proposals::TypedChainId;
+
+sp_api::decl_runtime_apis! {
+    /// Read access to the registry for clients such as dashboards.
+    pub trait ReceiptRegistryApi {
+        /// Watched contracts of every chain, see [`crate::Pallet::all_watched_contracts`].
+        fn all_watched_contracts(
+            start_after: Option<TypedChainId>,
+            limit: u32,
+        ) -> Vec<(TypedChainId, Vec<H160>)>;
+
+        /// Processed receipts of a chain, see [`crate::Pallet::processed_receipts_page`].
+        fn processed_receipts(
+            typed_chain_id: TypedChainId,
+            start_after: Option<(u64, H256)>,
+            limit: u32,
+        ) -> Vec<(u64, H256, Vec<Log>)>;
+    }
+}
@@ -0,0 +1,74 @@
+use frame_support::{traits::Get, weights::Weight};
+
+/// Bound of the watched contracts of one chain, the most addresses a proof is checked against.
+pub const MAX_WATCHED_CONTRACTS: u32 = 100;
+
+// Estimates until the pallet is benchmarked.
+const SUBMIT_PROOF_BASE: u64 = 50_000_000;
+/// Deserializing the proof, per byte of the submitted json.
+const SUBMIT_PROOF_PER_BYTE: u64 = 1_000;
+/// Hashing the header, receipt and proof nodes, per byte of the submitted json.
+const VERIFY_PROOF_PER_BYTE: u64 = 1_000;
+/// Scanning the receipt logs for one watched address.
+const SUBMIT_PROOF_PER_ADDRESS: u64 = 1_000_000;
+
+/// Weight of a `submit_proof` call with a `proof_len` bytes proof that was checked against
+/// `scanned_addresses` watched contracts. A `duplicate` proof of an already accepted receipt
+/// skips verifying the proof, the scan and storing the logs, and only pays the deposit.
+pub fn submit_proof<T: frame_system::Config>(
+    proof_len: u32,
+    scanned_addresses: u32,
+    duplicate: bool,
+) -> Weight {
+    // Paused, RestrictRelayers, AllowedRelayers, the light client head and block hash,
+    // RewardAccount and ProcessedReceiptsHash.
+    let checks = T::DbWeight::get().reads(7);
+    // The fee and both accounts of the transfer.
+    let transfer = T::DbWeight::get().reads_writes(3, 2);
+    let outcome = if duplicate {
+        Weight::zero()
+    } else {
+        let verify = VERIFY_PROOF_PER_BYTE.saturating_mul(proof_len.into());
+        let scan = SUBMIT_PROOF_PER_ADDRESS.saturating_mul(scanned_addresses.into());
+        // WatchedContracts read, ProcessedReceipts, ProcessedReceiptsHash and RelayerRewards.
+        T::DbWeight::get()
+            .reads_writes(2, 3)
+            .saturating_add(Weight::from_parts(verify.saturating_add(scan), 0))
+    };
+
+    Weight::from_parts(
+        SUBMIT_PROOF_BASE.saturating_add(SUBMIT_PROOF_PER_BYTE.saturating_mul(proof_len.into())),
+        0,
+    )
+    .saturating_add(checks)
+    .saturating_add(transfer)
+    .saturating_add(outcome)
+}
+
+/// Weight of a `submit_proof_unsigned` call with a `proof_len` bytes proof that was checked against
+/// `scanned_addresses` watched contracts. No deposit or reward is transferred.
+pub fn submit_proof_unsigned<T: frame_system::Config>(
+    proof_len: u32,
+    scanned_addresses: u32,
+) -> Weight {
+    let proof = SUBMIT_PROOF_PER_BYTE
+        .saturating_add(VERIFY_PROOF_PER_BYTE)
+        .saturating_mul(proof_len.into());
+    let scan = SUBMIT_PROOF_PER_ADDRESS.saturating_mul(scanned_addresses.into());
+    // Paused, the light client head and block hash, ProcessedReceiptsHash and WatchedContracts
+    // read twice, ProcessedReceipts and ProcessedReceiptsHash written.
+    T::DbWeight::get()
+        .reads_writes(6, 2)
+        .saturating_add(Weight::from_parts(
+            SUBMIT_PROOF_BASE.saturating_add(proof).saturating_add(scan),
+            0,
+        ))
+}
+
+/// Weight of a `clear_chain` call removing `removals` storage entries. Finding the entries reads
+/// them, and checking whether any are left reads one more of each storage item.
+pub fn clear_chain<T: frame_system::Config>(removals: u32) -> Weight {
+    T::DbWeight::get()
+        .reads_writes(removals.into(), removals.into())
+        .saturating_add(T::DbWeight::get().reads(5))
+}
@@ -1,5 +1,9 @@
 use consensus::network_config::{Network, NetworkConfig};
-use frame_support::{parameter_types, sp_io, traits::GenesisBuild};
+use frame_support::pallet_prelude::DispatchResult;
+use frame_support::{
+    parameter_types, sp_io,
+    traits::{AsEnsureOriginWithArg, ConstU128, ConstU32, GenesisBuild},
+};
 use frame_support::{
     sp_runtime::{
         testing::Header,
@@ -10,7 +14,8 @@ use frame_support::{
     PalletId,
 };
 use frame_system as system;
-use frame_system::EnsureRoot;
+use frame_system::{EnsureRoot, EnsureSigned};
+use pallet_receipt_registry::{FungibleAsset, NativeCurrency, ProofCurrency};
 use sp_core::H256;
 use webb_proposals::TypedChainId;
 
@@ -105,6 +110,29 @@ impl pallet_balances::Config for Test {
     type WeightInfo = ();
 }
 
+impl pallet_assets::Config for Test {
+    type RuntimeEvent = RuntimeEvent;
+    type Balance = u128;
+    type RemoveItemsLimit = ConstU32<1000>;
+    type AssetId = u32;
+    type AssetIdParameter = u32;
+    type Currency = Balances;
+    type CreateOrigin = AsEnsureOriginWithArg<EnsureSigned<AccountId>>;
+    type ForceOrigin = EnsureRoot<AccountId>;
+    type AssetDeposit = ConstU128<1>;
+    type AssetAccountDeposit = ConstU128<1>;
+    type MetadataDepositBase = ConstU128<1>;
+    type MetadataDepositPerByte = ConstU128<1>;
+    type ApprovalDeposit = ConstU128<1>;
+    type StringLimit = ConstU32<50>;
+    type Freezer = ();
+    type Extra = ();
+    type CallbackHandle = ();
+    type WeightInfo = ();
+    #[cfg(feature = "runtime-benchmarks")]
+    type BenchmarkHelper = ();
+}
+
 parameter_types! {
     pub const MaxAdditionalFields: u32 = 5;
     pub const MaxResources: u32 = 32;
@@ -119,11 +147,54 @@ impl pallet_eth2_light_client::Config for Test {
     type Currency = Balances;
 }
 
+parameter_types! {
+    pub const MaxProofAge: u64 = 100;
+    pub const MaxLogsPerReceipt: u32 = 16;
+    pub const MaxLogDataLen: u32 = 1024;
+    pub const RewardAssetId: u32 = 1;
+    pub static AllowUnsignedProofs: bool = false;
+    pub static PayInRewardAsset: bool = false;
+}
+
+/// Pays in the `RewardAssetId` asset if `PayInRewardAsset` is set, in `Balances` otherwise.
+pub struct MockProofCurrency;
+
+type NativeProofCurrency = NativeCurrency<Balances>;
+type AssetProofCurrency = FungibleAsset<Assets, RewardAssetId>;
+
+impl ProofCurrency<AccountId> for MockProofCurrency {
+    type Balance = u128;
+
+    fn transfer(source: &AccountId, dest: &AccountId, amount: u128) -> DispatchResult {
+        if PayInRewardAsset::get() {
+            <AssetProofCurrency as ProofCurrency<AccountId>>::transfer(source, dest, amount)
+        } else {
+            <NativeProofCurrency as ProofCurrency<AccountId>>::transfer(source, dest, amount)
+        }
+    }
+
+    fn transfer_keep_alive(source: &AccountId, dest: &AccountId, amount: u128) -> DispatchResult {
+        if PayInRewardAsset::get() {
+            <AssetProofCurrency as ProofCurrency<AccountId>>::transfer_keep_alive(
+                source, dest, amount,
+            )
+        } else {
+            <NativeProofCurrency as ProofCurrency<AccountId>>::transfer_keep_alive(
+                source, dest, amount,
+            )
+        }
+    }
+}
+
 impl pallet_receipt_registry::Config for Test {
     type RuntimeEvent = RuntimeEvent;
     type PalletId = Eth2ClientPalletId;
-    type Currency = Balances;
+    type Currency = MockProofCurrency;
     type PrivilegedOrigin = EnsureRoot<AccountId>;
+    type MaxProofAge = MaxProofAge;
+    type MaxLogsPerReceipt = MaxLogsPerReceipt;
+    type MaxLogDataLen = MaxLogDataLen;
+    type AllowUnsignedProofs = AllowUnsignedProofs;
 }
 
 // Configure a mock runtime to test the pallet.
@@ -135,7 +206,8 @@ frame_support::construct_runtime!(
     {
         System: frame_system::{Pallet, Call, Config, Storage, Event<T>},
         Balances: pallet_balances::{Pallet, Call, Storage, Event<T>},
+        Assets: pallet_assets::{Pallet, Call, Storage, Event<T>},
         Eth2Client: pallet_eth2_light_client::{Pallet, Call, Storage, Event<T>},
-        ReceiptRegistry: pallet_receipt_registry::{Pallet, Call, Storage, Event<T>},
+        ReceiptRegistry: pallet_receipt_registry::{Pallet, Call, Storage, Event<T>, ValidateUnsigned},
     }
 );
@@ -1,13 +1,26 @@
 use eth_types::{eth2::LightClientUpdate, pallet::InitInput, BlockHeader};
+use frame_support::sp_runtime::transaction_validity::{
+    InvalidTransaction, TransactionSource, TransactionValidityError,
+};
 use frame_support::sp_runtime::AccountId32;
-use frame_support::{assert_err, assert_ok};
+use frame_support::sp_runtime::DispatchError;
+use frame_support::unsigned::ValidateUnsigned;
+use frame_support::{assert_err, assert_noop, assert_ok, dispatch::GetDispatchInfo, traits::Get};
+use frame_support::{Blake2_128Concat, StorageHasher};
 use webb_proposals::TypedChainId;
 
-use pallet_receipt_registry::Error;
-use types::{Bloom, EventProof, MerkleProof, TransactionReceipt, H160, H256, U256};
+use pallet_receipt_registry::{
+    typed_chain_id, ChainKind, Error, FungibleAsset, ProofCurrency, MAX_PROCESSED_RECEIPTS_RANGE,
+};
+use parity_scale_codec::Encode;
+use types::{Bloom, EventProof, H160, H256, U256};
 
 mod mock;
-use mock::{new_test_ext, Eth2Client, ReceiptRegistry, RuntimeOrigin, System, Test};
+use mock::{
+    new_test_ext, AllowUnsignedProofs, Assets, Eth2Client, MaxLogDataLen, MaxLogsPerReceipt,
+    MaxProofAge, PayInRewardAsset, ReceiptRegistry, RewardAssetId, RuntimeEvent, RuntimeOrigin,
+    System, Test,
+};
 
 mod test_utils;
 use test_utils::*;
@@ -18,6 +31,7 @@ pub mod common;
 pub const MAINNET_CHAIN: TypedChainId = TypedChainId::Evm(1);
 pub const GOERLI_CHAIN: TypedChainId = TypedChainId::Evm(5);
 pub const ALICE: AccountId32 = AccountId32::new([1u8; 32]);
+pub const CHARLIE: AccountId32 = AccountId32::new([3u8; 32]);
 
 pub fn get_test_context(
     init_options: Option<InitOptions<[u8; 32]>>,
@@ -43,18 +57,23 @@ pub fn get_test_context(
     (headers, updates, init_input_0)
 }
 
-fn balance_of_user(user: &AccountId32) -> u128 {
-    System::account(user).data.free
+/// [`get_test_context`] with updates validated, and their signatures verified against the
+/// trusted signer.
+pub fn get_signed_test_context() -> (
+    &'static Vec<Vec<BlockHeader>>,
+    &'static Vec<LightClientUpdate>,
+    InitInput<[u8; 32]>,
+) {
+    get_test_context(Some(InitOptions {
+        validate_updates: true,
+        verify_bls_signatures: true,
+        hashes_gc_threshold: 7100,
+        trusted_signer: Some([2u8; 32]),
+    }))
 }
 
-fn create_proof(receipts: &[TransactionReceipt], index_to_prove: usize) -> MerkleProof {
-    use merkle_generator::IterativeTrie;
-
-    let mut trie = merkle_generator::PatriciaTrie::new();
-    receipts.iter().enumerate().for_each(|(i, receipt)| {
-        trie.insert(alloy_rlp::encode(i), alloy_rlp::encode(receipt));
-    });
-    trie.merkle_proof(alloy_rlp::encode(index_to_prove))
+fn balance_of_user(user: &AccountId32) -> u128 {
+    System::account(user).data.free
 }
 
 fn block_header_convert(header: eth_types::BlockHeader) -> types::BlockHeader {
@@ -72,7 +91,7 @@ fn block_header_convert(header: eth_types::BlockHeader) -> types::BlockHeader {
         gas_used: header.gas_used.0.as_u64(),
         timestamp: header.timestamp,
         mix_hash: H256(header.mix_hash.0 .0),
-        base_fee_per_gas: Some(header.base_fee_per_gas.unwrap()),
+        base_fee_per_gas: header.base_fee_per_gas,
         extra_data: header.extra_data,
 
         // Defaults
@@ -89,15 +108,36 @@ fn block_header_convert(header: eth_types::BlockHeader) -> types::BlockHeader {
         ),
         nonce: header.nonce.0.to_low_u64_be(),
 
-        blob_gas_used: None,
-        excess_blob_gas: None,
-        parent_beacon_block_root: None,
+        // EIP-4844 and EIP-4788 fields, present since Dencun
+        blob_gas_used: header.blob_gas_used,
+        excess_blob_gas: header.excess_blob_gas,
+        parent_beacon_block_root: header.parent_beacon_block_root.map(|r| H256(r.0 .0)),
+        // The light client headers predate Prague
+        requests_hash: None,
     };
-    assert_eq!(hash, H256::hash(&block_header).0);
+    assert_eq!(hash, block_header.hash().0);
 
     block_header
 }
 
+#[test]
+pub fn block_header_convert_dencun_fields() {
+    // The first goerli header of the test data extended with the withdrawals and Dencun fields;
+    // `block_header_convert` checks that both hashers agree on them.
+    let header: BlockHeader =
+        serde_json::from_str(include_str!("./data/dencun_block_header.json")).unwrap();
+    let block_header = block_header_convert(header);
+
+    assert_eq!(block_header.blob_gas_used, Some(0x60000));
+    assert_eq!(block_header.excess_blob_gas, Some(0x4b80000));
+    assert_eq!(
+        block_header.parent_beacon_block_root,
+        Some(H256(hex_literal::hex!(
+            "4c7d8a8bb3fe2e5b6c2a4e0d6fa0e4d2a4b9f3a1c8d0e6f7a2b3c4d5e6f70812"
+        )))
+    );
+}
+
 #[test]
 pub fn test_submit_proof_deserialize_fail() {
     new_test_ext().execute_with(|| {
@@ -133,6 +173,7 @@ pub fn test_submit_proof_header_hash_do_not_exist() {
                 excess_blob_gas: None,
                 extra_data: vec![0],
                 parent_beacon_block_root: None,
+                requests_hash: None,
             },
             block_hash: types::H256::zero(),
             transaction_receipt: types::TransactionReceipt {
@@ -163,6 +204,43 @@ pub fn test_submit_proof_header_hash_do_not_exist() {
     });
 }
 
+#[test]
+pub fn test_submit_proof_receipt_too_large() {
+    new_test_ext().execute_with(|| {
+        let log = types::Log {
+            address: H160::zero(),
+            topics: vec![],
+            data: vec![0; MaxLogDataLen::get() as usize],
+        };
+        let submit = |logs: Vec<types::Log>| {
+            let mut proof: EventProof =
+                serde_json::from_str(include_str!("./data/goerli/event_proof_8652100.json"))
+                    .unwrap();
+            proof.transaction_receipt.receipt.logs = logs;
+            ReceiptRegistry::submit_proof(
+                RuntimeOrigin::signed(ALICE),
+                GOERLI_CHAIN,
+                serde_json::to_string(&proof).unwrap().into(),
+            )
+        };
+
+        // At the limits the proof gets past the guard to the light client lookup.
+        let at_limit = vec![log.clone(); MaxLogsPerReceipt::get() as usize];
+        assert_err!(
+            submit(at_limit.clone()),
+            Error::<Test>::HeaderHashDoesNotExist
+        );
+
+        let mut too_many = at_limit.clone();
+        too_many.push(log.clone());
+        assert_err!(submit(too_many), Error::<Test>::ReceiptTooLarge);
+
+        let mut too_long = at_limit;
+        too_long[0].data.push(0);
+        assert_err!(submit(too_long), Error::<Test>::ReceiptTooLarge);
+    });
+}
+
 #[test]
 pub fn test_submit_proof_block_hash_do_not_match() {
     new_test_ext().execute_with(|| {
@@ -190,6 +268,7 @@ pub fn test_submit_proof_block_hash_do_not_match() {
                 excess_blob_gas: None,
                 extra_data: vec![0],
                 parent_beacon_block_root: None,
+                requests_hash: None,
             },
             block_hash: types::H256::zero(),
             transaction_receipt: types::TransactionReceipt {
@@ -217,15 +296,64 @@ pub fn test_submit_proof_block_hash_do_not_match() {
     });
 }
 
+#[test]
+pub fn test_submit_proof_header_of_another_fork() {
+    new_test_ext().execute_with(|| {
+        let (headers, _updates, _init_input) = get_test_context(None);
+        let address = H160(hex_literal::hex!(
+            "228612206ba22b5af70b6812cb722dfe508a83ef"
+        ));
+        assert_ok!(ReceiptRegistry::update_watching_address(
+            RuntimeOrigin::root(),
+            GOERLI_CHAIN,
+            address,
+            true
+        ));
+
+        let block_header = block_header_convert(headers[0][0].clone());
+        let receipts = common::load_receipts(include_str!("./data/goerli/receipts_8652100.json"));
+
+        // A block with the same number and receipts as the finalized one, but another parent. The
+        // proof is consistent in itself, only the stored hash tells the blocks apart.
+        let mut forked_header = block_header.clone();
+        forked_header.parent_hash = H256([0xff; 32]);
+        let forked_hash = forked_header.hash();
+        let proof = EventProof::build(forked_header, forked_hash, &receipts, 0).unwrap();
+        assert!(proof.validate().is_ok());
+        assert_eq!(
+            Eth2Client::finalized_execution_blocks(GOERLI_CHAIN, block_header.number)
+                .map(|hash| H256(hash.0 .0)),
+            Some(block_header.hash())
+        );
+
+        assert_noop!(
+            ReceiptRegistry::submit_proof(
+                RuntimeOrigin::signed(ALICE),
+                GOERLI_CHAIN,
+                serde_json::to_vec(&proof).unwrap()
+            ),
+            Error::<Test>::BlockHashesDoNotMatch
+        );
+
+        // Claiming the stored hash for the forked header doesn't help, the header no longer
+        // hashes to it
+        let mut claimed = proof;
+        claimed.block_hash = block_header.hash();
+        assert_noop!(
+            ReceiptRegistry::submit_proof(
+                RuntimeOrigin::signed(ALICE),
+                GOERLI_CHAIN,
+                serde_json::to_vec(&claimed).unwrap()
+            ),
+            Error::<Test>::VerifyProofFail
+        );
+    });
+}
+
 #[test]
 pub fn test_submit_proof_processed_receipts_hash_do_not_contains_key_verify_proof_fail() {
     new_test_ext().execute_with(|| {
-        let (headers, _updates, _init_input) = get_test_context(Some(InitOptions {
-            validate_updates: true,
-            verify_bls_signatures: true,
-            hashes_gc_threshold: 7100,
-            trusted_signer: Some([2u8; 32]),
-        }));
+        let (headers, _updates, _init_input) = get_signed_test_context();
 
         let proof = EventProof {
             block_header: types::BlockHeader {
@@ -249,6 +377,7 @@ pub fn test_submit_proof_processed_receipts_hash_do_not_contains_key_verify_proo
                 excess_blob_gas: None,
                 extra_data: vec![0],
                 parent_beacon_block_root: None,
+                requests_hash: None,
             },
             block_hash: types::H256(headers[0][0].calculate_hash().0 .0),
             transaction_receipt: types::TransactionReceipt {
@@ -279,12 +408,7 @@ pub fn test_submit_proof_processed_receipts_hash_do_not_contains_key_verify_proo
 #[test]
 pub fn test_submit_proof_processed_receipts_hash_do_not_contains_key_verify_proof_success() {
     new_test_ext().execute_with(|| {
-        let (headers, _updates, _init_input) = get_test_context(Some(InitOptions {
-            validate_updates: true,
-            verify_bls_signatures: true,
-            hashes_gc_threshold: 7100,
-            trusted_signer: Some([2u8; 32]),
-        }));
+        let (headers, _updates, _init_input) = get_signed_test_context();
 
         const PROOF_DEPOSIT: u128 = 1;
         const PROOF_REWARD: u128 = 2;
@@ -317,21 +441,22 @@ pub fn test_submit_proof_processed_receipts_hash_do_not_contains_key_verify_proo
 
         let block_header = headers[0][0].clone();
         let block_header = block_header_convert(block_header);
-        let block_hash = H256::hash(block_header.clone());
+        let block_hash = block_header.hash();
         assert_eq!(block_header.number, 8652100);
 
         let receipts = common::load_receipts(include_str!("./data/goerli/receipts_8652100.json"));
-        let merkle_proof_of_receipt = create_proof(&receipts, 0);
-
-        let proof = EventProof {
-            block_header,
-            block_hash,
-            transaction_receipt: receipts[0].clone(),
-            transaction_receipt_hash: H256::hash(&receipts[0]),
-            merkle_proof_of_receipt,
-        };
+        let proof = EventProof::build(block_header, block_hash, &receipts, 0).unwrap();
 
         let serialized_proof = serde_json::to_string(&proof).unwrap();
+        // Hashes are submitted as hex strings rather than arrays of bytes.
+        assert!(
+            serialized_proof.contains(&format!("\"block_hash\":\"0x{}", hex::encode(block_hash.0)))
+        );
+
+        assert!(!ReceiptRegistry::is_receipt_processed(
+            GOERLI_CHAIN,
+            proof.transaction_receipt_hash
+        ));
 
         let balance_before = balance_of_user(&ALICE);
         assert_ok!(ReceiptRegistry::submit_proof(
@@ -342,6 +467,18 @@ pub fn test_submit_proof_processed_receipts_hash_do_not_contains_key_verify_proo
         let balance_after = balance_of_user(&ALICE);
 
         let transaction_receipt_hash = proof.transaction_receipt_hash;
+        assert!(ReceiptRegistry::is_receipt_processed(
+            GOERLI_CHAIN,
+            transaction_receipt_hash
+        ));
+        assert!(!ReceiptRegistry::is_receipt_processed(
+            MAINNET_CHAIN,
+            transaction_receipt_hash
+        ));
+        assert!(!ReceiptRegistry::is_receipt_processed(
+            GOERLI_CHAIN,
+            H256::hash(&receipts[1])
+        ));
         let block_number = proof.block_header.number;
         assert_eq!(
             ReceiptRegistry::processed_receipts((
@@ -362,28 +499,15 @@ pub fn test_submit_proof_processed_receipts_hash_do_not_contains_key_verify_proo
 #[test]
 pub fn test_submit_proof_processed_receipts_hash_do_not_contains_key_but_not_in_watch_contract() {
     new_test_ext().execute_with(|| {
-        let (headers, _updates, _init_input) = get_test_context(Some(InitOptions {
-            validate_updates: true,
-            verify_bls_signatures: true,
-            hashes_gc_threshold: 7100,
-            trusted_signer: Some([2u8; 32]),
-        }));
+        let (headers, _updates, _init_input) = get_signed_test_context();
 
         let block_header = headers[0][0].clone();
         let block_header = block_header_convert(block_header);
-        let block_hash = H256::hash(block_header.clone());
+        let block_hash = block_header.hash();
         assert_eq!(block_header.number, 8652100);
 
         let receipts = common::load_receipts(include_str!("./data/goerli/receipts_8652100.json"));
-        let merkle_proof_of_receipt = create_proof(&receipts, 0);
-
-        let proof = EventProof {
-            block_header,
-            block_hash,
-            transaction_receipt: receipts[0].clone(),
-            transaction_receipt_hash: H256::hash(&receipts[0]),
-            merkle_proof_of_receipt,
-        };
+        let proof = EventProof::build(block_header, block_hash, &receipts, 0).unwrap();
 
         let serialized_proof = serde_json::to_string(&proof).unwrap();
 
@@ -466,19 +590,11 @@ pub fn test_submit_proof_processed_receipts_hash_contains_key() {
 
         let block_header = headers[0][0].clone();
         let block_header = block_header_convert(block_header);
-        let block_hash = H256::hash(block_header.clone());
+        let block_hash = block_header.hash();
         assert_eq!(block_header.number, 8652100);
 
         let receipts = common::load_receipts(include_str!("./data/goerli/receipts_8652100.json"));
-        let merkle_proof_of_receipt = create_proof(&receipts, 0);
-
-        let proof = EventProof {
-            block_header,
-            block_hash,
-            transaction_receipt: receipts[0].clone(),
-            transaction_receipt_hash: H256::hash(&receipts[0]),
-            merkle_proof_of_receipt,
-        };
+        let proof = EventProof::build(block_header, block_hash, &receipts, 0).unwrap();
 
         let serialized_proof = serde_json::to_string(&proof).unwrap();
 
@@ -533,11 +649,13 @@ pub fn test_submit_proof_processed_receipts_hash_contains_key() {
 }
 
 #[test]
-pub fn test_update_watching_address() {
+pub fn test_submit_proof_duplicate_weighs_less() {
     new_test_ext().execute_with(|| {
-        assert_eq!(ReceiptRegistry::watched_contracts(GOERLI_CHAIN), None);
+        let (headers, _updates, _init_input) = get_signed_test_context();
 
-        let address: H160 = H160::from_slice(&[1u8; 20]);
+        let address = H160(hex_literal::hex!(
+            "228612206ba22b5af70b6812cb722dfe508a83ef"
+        ));
         assert_ok!(ReceiptRegistry::update_watching_address(
             RuntimeOrigin::root(),
             GOERLI_CHAIN,
@@ -545,47 +663,1526 @@ pub fn test_update_watching_address() {
             true
         ));
 
-        assert_eq!(
-            ReceiptRegistry::watched_contracts(GOERLI_CHAIN)
-                .unwrap()
-                .to_vec(),
-            vec![address]
-        );
+        let block_header = block_header_convert(headers[0][0].clone());
+        let block_hash = block_header.hash();
+        let receipts = common::load_receipts(include_str!("./data/goerli/receipts_8652100.json"));
+        let proof = EventProof::build(block_header, block_hash, &receipts, 0).unwrap();
+        let serialized_proof = serde_json::to_string(&proof).unwrap();
+
+        let full = ReceiptRegistry::submit_proof(
+            RuntimeOrigin::signed(ALICE),
+            GOERLI_CHAIN,
+            serialized_proof.clone().into(),
+        )
+        .unwrap()
+        .actual_weight
+        .unwrap();
+        let duplicate = ReceiptRegistry::submit_proof(
+            RuntimeOrigin::signed(ALICE),
+            GOERLI_CHAIN,
+            serialized_proof.clone().into(),
+        )
+        .unwrap()
+        .actual_weight
+        .unwrap();
+
+        let declared = pallet_receipt_registry::Call::<Test>::submit_proof {
+            typed_chain_id: GOERLI_CHAIN,
+            event_proof: serialized_proof.into(),
+        }
+        .get_dispatch_info()
+        .weight;
+
+        assert!(duplicate.all_lt(full));
+        assert!(full.all_lte(declared));
+    });
+}
+
+#[test]
+pub fn test_submit_proof_duplicate_is_not_verified_again() {
+    new_test_ext().execute_with(|| {
+        let (headers, _updates, _init_input) = get_signed_test_context();
 
+        let address = H160(hex_literal::hex!(
+            "228612206ba22b5af70b6812cb722dfe508a83ef"
+        ));
         assert_ok!(ReceiptRegistry::update_watching_address(
             RuntimeOrigin::root(),
             GOERLI_CHAIN,
             address,
-            false
+            true
+        ));
+
+        let block_header = block_header_convert(headers[0][0].clone());
+        let block_hash = block_header.hash();
+        let receipts = common::load_receipts(include_str!("./data/goerli/receipts_8652100.json"));
+        let proof = EventProof::build(block_header, block_hash, &receipts, 0).unwrap();
+        assert_ok!(ReceiptRegistry::submit_proof(
+            RuntimeOrigin::signed(ALICE),
+            GOERLI_CHAIN,
+            serde_json::to_vec(&proof).unwrap()
+        ));
+
+        // The receipt no longer matches its hash, which only a verification would notice
+        let mut tampered = proof;
+        tampered.transaction_receipt.receipt.cumulative_gas_used += 1;
+        assert!(tampered.validate().is_err());
+        let serialized_proof = serde_json::to_vec(&tampered).unwrap();
+        let proof_len = serialized_proof.len() as u32;
+
+        let balance_before = balance_of_user(&ALICE);
+        let duplicate = ReceiptRegistry::submit_proof(
+            RuntimeOrigin::signed(ALICE),
+            GOERLI_CHAIN,
+            serialized_proof,
+        )
+        .unwrap()
+        .actual_weight
+        .unwrap();
+
+        assert_eq!(
+            balance_of_user(&ALICE),
+            balance_before - ReceiptRegistry::proof_deposit(GOERLI_CHAIN)
+        );
+        assert_eq!(
+            duplicate,
+            pallet_receipt_registry::weights::submit_proof::<Test>(proof_len, 0, true)
+        );
+        // Not even the verification of a proof checked against no addresses is charged
+        assert!(
+            duplicate.all_lt(pallet_receipt_registry::weights::submit_proof::<Test>(
+                proof_len, 0, false
+            ))
+        );
+    });
+}
+
+/// Watches the contract of the first receipt of Goerli block 8652100, sets a proof fee and returns
+/// the proof of that receipt.
+fn unsigned_proof_context() -> EventProof {
+    let (headers, _updates, _init_input) = get_signed_test_context();
+    assert_ok!(ReceiptRegistry::update_proof_fee(
+        RuntimeOrigin::root(),
+        GOERLI_CHAIN,
+        1,
+        2
+    ));
+    assert_ok!(ReceiptRegistry::update_watching_address(
+        RuntimeOrigin::root(),
+        GOERLI_CHAIN,
+        H160(hex_literal::hex!(
+            "228612206ba22b5af70b6812cb722dfe508a83ef"
+        )),
+        true
+    ));
+
+    let block_header = block_header_convert(headers[0][0].clone());
+    let block_hash = block_header.hash();
+    let receipts = common::load_receipts(include_str!("./data/goerli/receipts_8652100.json"));
+    EventProof::build(block_header, block_hash, &receipts, 0).unwrap()
+}
+
+fn validate_unsigned_proof(proof: &EventProof) -> Result<(), InvalidTransaction> {
+    let call = pallet_receipt_registry::Call::<Test>::submit_proof_unsigned {
+        typed_chain_id: GOERLI_CHAIN,
+        event_proof: serde_json::to_vec(proof).unwrap(),
+    };
+    ReceiptRegistry::validate_unsigned(TransactionSource::External, &call)
+        .map(|_| ())
+        .map_err(|err| match err {
+            TransactionValidityError::Invalid(err) => err,
+            err => panic!("unexpected validity error {err:?}"),
+        })
+}
+
+#[test]
+pub fn unsigned_proofs_are_disabled_by_default() {
+    new_test_ext().execute_with(|| {
+        let proof = unsigned_proof_context();
+
+        assert_eq!(
+            validate_unsigned_proof(&proof),
+            Err(InvalidTransaction::Call)
+        );
+        assert_noop!(
+            ReceiptRegistry::submit_proof_unsigned(
+                RuntimeOrigin::none(),
+                GOERLI_CHAIN,
+                serde_json::to_vec(&proof).unwrap()
+            ),
+            Error::<Test>::UnsignedProofsDisabled
+        );
+    });
+}
+
+#[test]
+pub fn unsigned_proof_is_accepted_without_fees() {
+    new_test_ext().execute_with(|| {
+        AllowUnsignedProofs::set(true);
+        let proof = unsigned_proof_context();
+        let treasury = ReceiptRegistry::treasury_account();
+        let balances = (balance_of_user(&ALICE), balance_of_user(&treasury));
+
+        assert_eq!(validate_unsigned_proof(&proof), Ok(()));
+        // Only the unsigned path goes without a signature
+        assert_noop!(
+            ReceiptRegistry::submit_proof_unsigned(
+                RuntimeOrigin::signed(ALICE),
+                GOERLI_CHAIN,
+                serde_json::to_vec(&proof).unwrap()
+            ),
+            DispatchError::BadOrigin
+        );
+        assert_ok!(ReceiptRegistry::submit_proof_unsigned(
+            RuntimeOrigin::none(),
+            GOERLI_CHAIN,
+            serde_json::to_vec(&proof).unwrap()
         ));
 
+        System::assert_last_event(RuntimeEvent::ReceiptRegistry(
+            pallet_receipt_registry::Event::SubmitProcessedReceipts {
+                typed_chain_id: GOERLI_CHAIN,
+                block_number: proof.block_header.number,
+                receipt_hash: proof.transaction_receipt_hash,
+            },
+        ));
         assert_eq!(
-            ReceiptRegistry::watched_contracts(GOERLI_CHAIN).unwrap(),
-            vec![]
+            ReceiptRegistry::processed_receipts((
+                GOERLI_CHAIN,
+                proof.block_header.number,
+                proof.transaction_receipt_hash
+            )),
+            Some(proof.transaction_receipt.receipt.logs.clone())
+        );
+        assert_eq!(
+            (balance_of_user(&ALICE), balance_of_user(&treasury)),
+            balances
+        );
+        assert_eq!(ReceiptRegistry::relayer_rewards(&ALICE), 0);
+
+        // A proof of the same receipt again is kept out of the pool
+        assert_eq!(
+            validate_unsigned_proof(&proof),
+            Err(InvalidTransaction::Stale)
+        );
+        assert_noop!(
+            ReceiptRegistry::submit_proof_unsigned(
+                RuntimeOrigin::none(),
+                GOERLI_CHAIN,
+                serde_json::to_vec(&proof).unwrap()
+            ),
+            Error::<Test>::ReceiptAlreadyProcessed
         );
     });
 }
 
 #[test]
-pub fn update_proof_fee() {
+pub fn invalid_unsigned_proof_is_rejected() {
     new_test_ext().execute_with(|| {
+        AllowUnsignedProofs::set(true);
+        let mut proof = unsigned_proof_context();
+        let treasury = ReceiptRegistry::treasury_account();
+        let balances = (balance_of_user(&ALICE), balance_of_user(&treasury));
+
+        // The receipt no longer matches its hash
+        proof.transaction_receipt.receipt.cumulative_gas_used += 1;
+        assert!(proof.validate().is_err());
+
         assert_eq!(
-            ReceiptRegistry::proof_deposit(GOERLI_CHAIN),
-            Default::default()
+            validate_unsigned_proof(&proof),
+            Err(InvalidTransaction::BadProof)
+        );
+        assert_noop!(
+            ReceiptRegistry::submit_proof_unsigned(
+                RuntimeOrigin::none(),
+                GOERLI_CHAIN,
+                serde_json::to_vec(&proof).unwrap()
+            ),
+            Error::<Test>::VerifyProofFail
         );
+        assert!(!ReceiptRegistry::is_receipt_processed(
+            GOERLI_CHAIN,
+            proof.transaction_receipt_hash
+        ));
         assert_eq!(
-            ReceiptRegistry::proof_reward(GOERLI_CHAIN),
-            Default::default()
+            (balance_of_user(&ALICE), balance_of_user(&treasury)),
+            balances
         );
+    });
+}
 
-        assert_ok!(ReceiptRegistry::update_proof_fee(
+#[test]
+pub fn unsigned_proof_without_watched_logs_is_rejected() {
+    new_test_ext().execute_with(|| {
+        AllowUnsignedProofs::set(true);
+        let proof = unsigned_proof_context();
+
+        // The chain is watched, but not the contract that emitted the logs of the receipt
+        assert_ok!(ReceiptRegistry::update_watching_address(
             RuntimeOrigin::root(),
             GOERLI_CHAIN,
-            1,
-            2
+            H160(hex_literal::hex!(
+                "228612206ba22b5af70b6812cb722dfe508a83ef"
+            )),
+            false
+        ));
+        assert_ok!(ReceiptRegistry::update_watching_address(
+            RuntimeOrigin::root(),
+            GOERLI_CHAIN,
+            H160([0x11; 20]),
+            true
         ));
 
-        assert_eq!(ReceiptRegistry::proof_deposit(GOERLI_CHAIN), 1);
-        assert_eq!(ReceiptRegistry::proof_reward(GOERLI_CHAIN), 2);
+        assert_eq!(
+            validate_unsigned_proof(&proof),
+            Err(InvalidTransaction::Call)
+        );
+        assert_noop!(
+            ReceiptRegistry::submit_proof_unsigned(
+                RuntimeOrigin::none(),
+                GOERLI_CHAIN,
+                serde_json::to_vec(&proof).unwrap()
+            ),
+            Error::<Test>::NoWatchedLogs
+        );
+    });
+}
+
+#[test]
+pub fn test_submit_proof_bloom_mismatch() {
+    new_test_ext().execute_with(|| {
+        AllowUnsignedProofs::set(true);
+        let mut proof = unsigned_proof_context();
+
+        // A bloom hiding the logs, with the receipt hash following it
+        proof.transaction_receipt.bloom = Bloom::new([0; 256]);
+        proof.transaction_receipt_hash = H256::hash(&proof.transaction_receipt);
+        assert_ne!(
+            proof
+                .merkle_proof_of_receipt
+                .merkle_root(&proof.transaction_receipt),
+            proof.block_header.receipts_root
+        );
+
+        assert_noop!(
+            ReceiptRegistry::submit_proof(
+                RuntimeOrigin::signed(ALICE),
+                GOERLI_CHAIN,
+                serde_json::to_vec(&proof).unwrap()
+            ),
+            Error::<Test>::BloomMismatch
+        );
+        assert_eq!(
+            validate_unsigned_proof(&proof),
+            Err(InvalidTransaction::BadProof)
+        );
+        assert_noop!(
+            ReceiptRegistry::submit_proof_unsigned(
+                RuntimeOrigin::none(),
+                GOERLI_CHAIN,
+                serde_json::to_vec(&proof).unwrap()
+            ),
+            Error::<Test>::BloomMismatch
+        );
+    });
+}
+
+#[test]
+pub fn test_all_watched_contracts() {
+    new_test_ext().execute_with(|| {
+        assert_eq!(ReceiptRegistry::all_watched_contracts(None, 10), vec![]);
+
+        let mainnet_addresses = vec![H160([1; 20]), H160([2; 20])];
+        let goerli_addresses = vec![H160([3; 20])];
+        for (typed_chain_id, addresses) in [
+            (MAINNET_CHAIN, &mainnet_addresses),
+            (GOERLI_CHAIN, &goerli_addresses),
+        ] {
+            for address in addresses {
+                assert_ok!(ReceiptRegistry::update_watching_address(
+                    RuntimeOrigin::root(),
+                    typed_chain_id,
+                    *address,
+                    true
+                ));
+            }
+        }
+
+        let mut all = ReceiptRegistry::all_watched_contracts(None, 10);
+        all.sort_by_key(|(typed_chain_id, _)| typed_chain_id.chain_id());
+        assert_eq!(
+            all,
+            vec![
+                (MAINNET_CHAIN, mainnet_addresses.clone()),
+                (GOERLI_CHAIN, goerli_addresses.clone())
+            ]
+        );
+
+        // One chain per page
+        let first = ReceiptRegistry::all_watched_contracts(None, 1);
+        assert_eq!(first.len(), 1);
+        let second = ReceiptRegistry::all_watched_contracts(Some(first[0].0), 1);
+        assert_eq!(second.len(), 1);
+        assert_ne!(first[0].0, second[0].0);
+        assert_eq!(
+            ReceiptRegistry::all_watched_contracts(Some(second[0].0), 1),
+            vec![]
+        );
+    });
+}
+
+/// Stores the hash of a finalized execution block in the light client directly, as if it had
+/// been finalized.
+fn insert_finalized_execution_block(
+    typed_chain_id: TypedChainId,
+    block_number: u64,
+    block_hash: H256,
+) {
+    let mut key =
+        frame_support::storage::storage_prefix(b"Eth2Client", b"FinalizedExecutionBlocks").to_vec();
+    for part in [typed_chain_id.encode(), block_number.encode()] {
+        key.extend(Blake2_128Concat::hash(&part));
+    }
+    frame_support::storage::unhashed::put(&key, &block_hash.0);
+}
+
+/// Stores a processed receipt directly, as if its proof had been submitted.
+fn insert_processed_receipt(
+    typed_chain_id: TypedChainId,
+    block_number: u64,
+    receipt_hash: H256,
+    logs: Vec<types::Log>,
+) {
+    let mut key =
+        frame_support::storage::storage_prefix(b"ReceiptRegistry", b"ProcessedReceipts").to_vec();
+    for part in [
+        typed_chain_id.encode(),
+        block_number.encode(),
+        receipt_hash.encode(),
+    ] {
+        key.extend(Blake2_128Concat::hash(&part));
+    }
+    frame_support::storage::unhashed::put(&key, &logs);
+}
+
+#[test]
+pub fn test_processed_receipts_in_range() {
+    new_test_ext().execute_with(|| {
+        let log = |byte| types::Log {
+            address: H160([byte; 20]),
+            topics: vec![H256([byte; 32])],
+            data: vec![byte],
+        };
+        let receipts = vec![
+            (10, H256([1; 32]), vec![log(1)]),
+            (10, H256([2; 32]), vec![log(2), log(3)]),
+            (11, H256([3; 32]), vec![]),
+            (13, H256([4; 32]), vec![log(4)]),
+            (
+                10 + MAX_PROCESSED_RECEIPTS_RANGE,
+                H256([5; 32]),
+                vec![log(5)],
+            ),
+        ];
+        for (block_number, receipt_hash, logs) in receipts.clone() {
+            insert_processed_receipt(GOERLI_CHAIN, block_number, receipt_hash, logs);
+        }
+        insert_processed_receipt(MAINNET_CHAIN, 11, H256([6; 32]), vec![log(6)]);
+
+        let mut first = ReceiptRegistry::processed_receipts_in_range(GOERLI_CHAIN, 10, 12).unwrap();
+        first.sort_by_key(|(block_number, receipt_hash, _)| (*block_number, receipt_hash.0));
+        assert_eq!(first, receipts[..3]);
+
+        assert_eq!(
+            ReceiptRegistry::processed_receipts_in_range(GOERLI_CHAIN, 11, 13),
+            Some(receipts[2..4].to_vec())
+        );
+        assert_eq!(
+            ReceiptRegistry::processed_receipts_in_range(GOERLI_CHAIN, 12, 12),
+            Some(vec![])
+        );
+        assert_eq!(
+            ReceiptRegistry::processed_receipts_in_range(GOERLI_CHAIN, 13, 10),
+            Some(vec![])
+        );
+
+        // Ranges longer than MAX_PROCESSED_RECEIPTS_RANGE blocks are refused
+        assert_eq!(
+            ReceiptRegistry::processed_receipts_in_range(GOERLI_CHAIN, 10, u64::MAX),
+            None
+        );
+        assert_eq!(
+            ReceiptRegistry::processed_receipts_in_range(
+                GOERLI_CHAIN,
+                10,
+                10 + MAX_PROCESSED_RECEIPTS_RANGE
+            ),
+            None
+        );
+        assert_eq!(
+            ReceiptRegistry::processed_receipts_in_range(
+                GOERLI_CHAIN,
+                11,
+                10 + MAX_PROCESSED_RECEIPTS_RANGE
+            )
+            .unwrap()
+            .last(),
+            receipts.last()
+        );
+
+        // Two receipts per page
+        let mut all = vec![];
+        let mut start_after = None;
+        loop {
+            let page = ReceiptRegistry::processed_receipts_page(GOERLI_CHAIN, start_after, 2);
+            assert!(page.len() <= 2);
+            let Some((block_number, receipt_hash, _)) = page.last() else {
+                break;
+            };
+            start_after = Some((*block_number, *receipt_hash));
+            all.extend(page);
+        }
+        all.sort_by_key(|(block_number, receipt_hash, _)| (*block_number, receipt_hash.0));
+        assert_eq!(all, receipts);
+    });
+}
+
+#[test]
+pub fn test_update_watching_address() {
+    new_test_ext().execute_with(|| {
+        assert_eq!(ReceiptRegistry::watched_contracts(GOERLI_CHAIN), None);
+
+        let address: H160 = H160::from_slice(&[1u8; 20]);
+        assert_ok!(ReceiptRegistry::update_watching_address(
+            RuntimeOrigin::root(),
+            GOERLI_CHAIN,
+            address,
+            true
+        ));
+
+        assert_eq!(
+            ReceiptRegistry::watched_contracts(GOERLI_CHAIN)
+                .unwrap()
+                .to_vec(),
+            vec![address]
+        );
+
+        assert_ok!(ReceiptRegistry::update_watching_address(
+            RuntimeOrigin::root(),
+            GOERLI_CHAIN,
+            address,
+            false
+        ));
+
+        assert_eq!(
+            ReceiptRegistry::watched_contracts(GOERLI_CHAIN).unwrap(),
+            vec![]
+        );
+    });
+}
+
+#[test]
+pub fn watching_an_address_twice_keeps_it_once() {
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+
+        let address: H160 = H160::from_slice(&[1u8; 20]);
+        for _ in 0..2 {
+            assert_ok!(ReceiptRegistry::update_watching_address(
+                RuntimeOrigin::root(),
+                GOERLI_CHAIN,
+                address,
+                true
+            ));
+        }
+        assert_eq!(
+            ReceiptRegistry::watched_contracts(GOERLI_CHAIN)
+                .unwrap()
+                .to_vec(),
+            vec![address]
+        );
+        System::assert_last_event(RuntimeEvent::ReceiptRegistry(
+            pallet_receipt_registry::Event::ContractAddressAlreadyWatched {
+                typed_chain_id: GOERLI_CHAIN,
+                address,
+            },
+        ));
+
+        let other: H160 = H160::from_slice(&[2u8; 20]);
+        assert_ok!(ReceiptRegistry::set_watched_contracts(
+            RuntimeOrigin::root(),
+            GOERLI_CHAIN,
+            vec![other, address, other].try_into().unwrap()
+        ));
+        assert_eq!(
+            ReceiptRegistry::watched_contracts(GOERLI_CHAIN)
+                .unwrap()
+                .to_vec(),
+            vec![other, address]
+        );
+        System::assert_last_event(RuntimeEvent::ReceiptRegistry(
+            pallet_receipt_registry::Event::WatchedContractsReplaced {
+                typed_chain_id: GOERLI_CHAIN,
+                count: 2,
+            },
+        ));
+    });
+}
+
+#[test]
+pub fn force_mark_processed_needs_privileged_origin() {
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+
+        let receipt_hash = H256([7; 32]);
+        let logs = vec![types::Log {
+            address: H160([1; 20]),
+            topics: vec![H256([2; 32])],
+            data: vec![3; 32],
+        }];
+        assert_noop!(
+            ReceiptRegistry::force_mark_processed(
+                RuntimeOrigin::signed(ALICE),
+                GOERLI_CHAIN,
+                100,
+                receipt_hash,
+                logs.clone()
+            ),
+            DispatchError::BadOrigin
+        );
+        assert!(!ReceiptRegistry::is_receipt_processed(
+            GOERLI_CHAIN,
+            receipt_hash
+        ));
+
+        assert_ok!(ReceiptRegistry::force_mark_processed(
+            RuntimeOrigin::root(),
+            GOERLI_CHAIN,
+            100,
+            receipt_hash,
+            logs.clone()
+        ));
+        assert_eq!(
+            ReceiptRegistry::processed_receipts((GOERLI_CHAIN, 100, receipt_hash)),
+            Some(logs)
+        );
+        assert_eq!(
+            ReceiptRegistry::processed_receipts_hash(GOERLI_CHAIN, receipt_hash),
+            Some(())
+        );
+        assert!(ReceiptRegistry::is_receipt_processed(
+            GOERLI_CHAIN,
+            receipt_hash
+        ));
+        System::assert_last_event(RuntimeEvent::ReceiptRegistry(
+            pallet_receipt_registry::Event::ForceMarkedProcessed {
+                typed_chain_id: GOERLI_CHAIN,
+                block_number: 100,
+                receipt_hash,
+            },
+        ));
+    });
+}
+
+#[test]
+pub fn set_watched_contracts_replaces_list() {
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+
+        let old: H160 = H160::from_slice(&[1u8; 20]);
+        assert_ok!(ReceiptRegistry::update_watching_address(
+            RuntimeOrigin::root(),
+            GOERLI_CHAIN,
+            old,
+            true
+        ));
+
+        let new = vec![H160::from_slice(&[2u8; 20]), H160::from_slice(&[3u8; 20])];
+        assert_noop!(
+            ReceiptRegistry::set_watched_contracts(
+                RuntimeOrigin::signed(ALICE),
+                GOERLI_CHAIN,
+                new.clone().try_into().unwrap()
+            ),
+            DispatchError::BadOrigin
+        );
+
+        assert_ok!(ReceiptRegistry::set_watched_contracts(
+            RuntimeOrigin::root(),
+            GOERLI_CHAIN,
+            new.clone().try_into().unwrap()
+        ));
+        assert_eq!(
+            ReceiptRegistry::watched_contracts(GOERLI_CHAIN)
+                .unwrap()
+                .to_vec(),
+            new
+        );
+        System::assert_last_event(RuntimeEvent::ReceiptRegistry(
+            pallet_receipt_registry::Event::WatchedContractsReplaced {
+                typed_chain_id: GOERLI_CHAIN,
+                count: 2,
+            },
+        ));
+
+        assert_ok!(ReceiptRegistry::set_watched_contracts(
+            RuntimeOrigin::root(),
+            GOERLI_CHAIN,
+            Default::default()
+        ));
+        assert_eq!(ReceiptRegistry::watched_contracts(GOERLI_CHAIN), None);
+    });
+}
+
+#[test]
+pub fn update_proof_fee() {
+    new_test_ext().execute_with(|| {
+        assert_eq!(
+            ReceiptRegistry::proof_deposit(GOERLI_CHAIN),
+            Default::default()
+        );
+        assert_eq!(
+            ReceiptRegistry::proof_reward(GOERLI_CHAIN),
+            Default::default()
+        );
+
+        assert_ok!(ReceiptRegistry::update_proof_fee(
+            RuntimeOrigin::root(),
+            GOERLI_CHAIN,
+            1,
+            2
+        ));
+
+        assert_eq!(ReceiptRegistry::proof_deposit(GOERLI_CHAIN), 1);
+        assert_eq!(ReceiptRegistry::proof_reward(GOERLI_CHAIN), 2);
+    });
+}
+
+#[test]
+pub fn set_paused_requires_privileged_origin() {
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+        assert!(!ReceiptRegistry::paused());
+
+        assert_noop!(
+            ReceiptRegistry::set_paused(RuntimeOrigin::signed(ALICE), true),
+            DispatchError::BadOrigin
+        );
+
+        assert_ok!(ReceiptRegistry::set_paused(RuntimeOrigin::root(), true));
+        assert!(ReceiptRegistry::paused());
+        System::assert_last_event(RuntimeEvent::ReceiptRegistry(
+            pallet_receipt_registry::Event::Paused,
+        ));
+
+        assert_ok!(ReceiptRegistry::set_paused(RuntimeOrigin::root(), false));
+        assert!(!ReceiptRegistry::paused());
+        System::assert_last_event(RuntimeEvent::ReceiptRegistry(
+            pallet_receipt_registry::Event::Unpaused,
+        ));
+    });
+}
+
+#[test]
+pub fn test_submit_proof_paused() {
+    new_test_ext().execute_with(|| {
+        let (headers, _updates, _init_input) = get_signed_test_context();
+
+        let address = H160(hex_literal::hex!(
+            "228612206ba22b5af70b6812cb722dfe508a83ef"
+        ));
+        assert_ok!(ReceiptRegistry::update_watching_address(
+            RuntimeOrigin::root(),
+            GOERLI_CHAIN,
+            address,
+            true
+        ));
+
+        let block_header = block_header_convert(headers[0][0].clone());
+        let block_hash = block_header.hash();
+        let receipts = common::load_receipts(include_str!("./data/goerli/receipts_8652100.json"));
+        let proof = EventProof::build(block_header, block_hash, &receipts, 0).unwrap();
+        let serialized_proof = serde_json::to_string(&proof).unwrap();
+
+        assert_ok!(ReceiptRegistry::set_paused(RuntimeOrigin::root(), true));
+        assert_noop!(
+            ReceiptRegistry::submit_proof(
+                RuntimeOrigin::signed(ALICE),
+                GOERLI_CHAIN,
+                serialized_proof.clone().into()
+            ),
+            Error::<Test>::Paused
+        );
+
+        assert_ok!(ReceiptRegistry::set_paused(RuntimeOrigin::root(), false));
+        assert_ok!(ReceiptRegistry::submit_proof(
+            RuntimeOrigin::signed(ALICE),
+            GOERLI_CHAIN,
+            serialized_proof.into()
+        ));
+        assert_eq!(
+            ReceiptRegistry::processed_receipts_hash(GOERLI_CHAIN, proof.transaction_receipt_hash),
+            Some(())
+        );
+    });
+}
+
+#[test]
+pub fn test_submit_proof_restricted_relayers() {
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+        let (headers, _updates, _init_input) = get_signed_test_context();
+
+        let address = H160(hex_literal::hex!(
+            "228612206ba22b5af70b6812cb722dfe508a83ef"
+        ));
+        assert_ok!(ReceiptRegistry::update_watching_address(
+            RuntimeOrigin::root(),
+            GOERLI_CHAIN,
+            address,
+            true
+        ));
+
+        let block_header = block_header_convert(headers[0][0].clone());
+        let block_hash = block_header.hash();
+        let receipts = common::load_receipts(include_str!("./data/goerli/receipts_8652100.json"));
+        let proof = EventProof::build(block_header, block_hash, &receipts, 0).unwrap();
+        let serialized_proof = serde_json::to_string(&proof).unwrap();
+
+        assert_noop!(
+            ReceiptRegistry::set_restrict_relayers(RuntimeOrigin::signed(ALICE), true),
+            DispatchError::BadOrigin
+        );
+        assert_noop!(
+            ReceiptRegistry::update_allowed_relayer(RuntimeOrigin::signed(ALICE), ALICE, true),
+            DispatchError::BadOrigin
+        );
+
+        assert_ok!(ReceiptRegistry::set_restrict_relayers(
+            RuntimeOrigin::root(),
+            true
+        ));
+        System::assert_last_event(RuntimeEvent::ReceiptRegistry(
+            pallet_receipt_registry::Event::UpdateRestrictRelayers { restricted: true },
+        ));
+        assert_noop!(
+            ReceiptRegistry::submit_proof(
+                RuntimeOrigin::signed(ALICE),
+                GOERLI_CHAIN,
+                serialized_proof.clone().into()
+            ),
+            Error::<Test>::RelayerNotAllowed
+        );
+
+        assert_ok!(ReceiptRegistry::update_allowed_relayer(
+            RuntimeOrigin::root(),
+            ALICE,
+            true
+        ));
+        System::assert_last_event(RuntimeEvent::ReceiptRegistry(
+            pallet_receipt_registry::Event::AddedAllowedRelayer { relayer: ALICE },
+        ));
+        assert_ok!(ReceiptRegistry::submit_proof(
+            RuntimeOrigin::signed(ALICE),
+            GOERLI_CHAIN,
+            serialized_proof.into()
+        ));
+
+        assert_ok!(ReceiptRegistry::update_allowed_relayer(
+            RuntimeOrigin::root(),
+            ALICE,
+            false
+        ));
+        System::assert_last_event(RuntimeEvent::ReceiptRegistry(
+            pallet_receipt_registry::Event::RemovedAllowedRelayer { relayer: ALICE },
+        ));
+        assert!(!ReceiptRegistry::is_relayer_allowed(&ALICE));
+    });
+}
+
+#[test]
+pub fn relayers_are_open_by_default() {
+    new_test_ext().execute_with(|| {
+        assert!(!ReceiptRegistry::restrict_relayers());
+        assert_eq!(ReceiptRegistry::allowed_relayers(ALICE), None);
+        assert!(ReceiptRegistry::is_relayer_allowed(&ALICE));
+        assert!(ReceiptRegistry::is_relayer_allowed(&CHARLIE));
+
+        assert_ok!(ReceiptRegistry::update_allowed_relayer(
+            RuntimeOrigin::root(),
+            ALICE,
+            true
+        ));
+        assert_ok!(ReceiptRegistry::set_restrict_relayers(
+            RuntimeOrigin::root(),
+            true
+        ));
+        assert!(ReceiptRegistry::is_relayer_allowed(&ALICE));
+        assert!(!ReceiptRegistry::is_relayer_allowed(&CHARLIE));
+
+        assert_ok!(ReceiptRegistry::set_restrict_relayers(
+            RuntimeOrigin::root(),
+            false
+        ));
+        assert!(ReceiptRegistry::is_relayer_allowed(&CHARLIE));
+    });
+}
+
+#[test]
+pub fn test_submit_proof_uses_reward_account() {
+    new_test_ext().execute_with(|| {
+        let (headers, _updates, _init_input) = get_signed_test_context();
+
+        const PROOF_DEPOSIT: u128 = 1;
+        const PROOF_REWARD: u128 = 2;
+        assert_ok!(ReceiptRegistry::update_proof_fee(
+            RuntimeOrigin::root(),
+            GOERLI_CHAIN,
+            PROOF_DEPOSIT,
+            PROOF_REWARD
+        ));
+        assert_ok!(ReceiptRegistry::update_watching_address(
+            RuntimeOrigin::root(),
+            GOERLI_CHAIN,
+            H160(hex_literal::hex!(
+                "228612206ba22b5af70b6812cb722dfe508a83ef"
+            )),
+            true
+        ));
+
+        assert_noop!(
+            ReceiptRegistry::set_reward_account(RuntimeOrigin::signed(ALICE), Some(CHARLIE)),
+            DispatchError::BadOrigin
+        );
+        assert_ok!(ReceiptRegistry::set_reward_account(
+            RuntimeOrigin::root(),
+            Some(CHARLIE)
+        ));
+        assert_eq!(ReceiptRegistry::treasury_account(), CHARLIE);
+
+        let block_header = block_header_convert(headers[0][0].clone());
+        let block_hash = block_header.hash();
+        let receipts = common::load_receipts(include_str!("./data/goerli/receipts_8652100.json"));
+        let proof = EventProof::build(block_header, block_hash, &receipts, 0).unwrap();
+        let serialized_proof = serde_json::to_string(&proof).unwrap();
+
+        let pallet_balance_before = balance_of_user(&ReceiptRegistry::account_id());
+        let reward_balance_before = balance_of_user(&CHARLIE);
+
+        // The reward is paid from the custom account.
+        assert_ok!(ReceiptRegistry::submit_proof(
+            RuntimeOrigin::signed(ALICE),
+            GOERLI_CHAIN,
+            serialized_proof.clone().into()
+        ));
+        assert_eq!(
+            balance_of_user(&CHARLIE),
+            reward_balance_before - PROOF_REWARD
+        );
+
+        // The deposit for an already processed receipt is paid to it.
+        assert_ok!(ReceiptRegistry::submit_proof(
+            RuntimeOrigin::signed(ALICE),
+            GOERLI_CHAIN,
+            serialized_proof.into()
+        ));
+        assert_eq!(
+            balance_of_user(&CHARLIE),
+            reward_balance_before - PROOF_REWARD + PROOF_DEPOSIT
+        );
+        assert_eq!(
+            balance_of_user(&ReceiptRegistry::account_id()),
+            pallet_balance_before
+        );
+
+        assert_ok!(ReceiptRegistry::set_reward_account(
+            RuntimeOrigin::root(),
+            None
+        ));
+        assert_eq!(
+            ReceiptRegistry::treasury_account(),
+            ReceiptRegistry::account_id()
+        );
+    });
+}
+
+#[test]
+pub fn test_submit_proof_too_old() {
+    new_test_ext().execute_with(|| {
+        let (headers, _updates, _init_input) = get_signed_test_context();
+        let latest = Eth2Client::last_block_number(GOERLI_CHAIN);
+        assert_ok!(ReceiptRegistry::update_watching_address(
+            RuntimeOrigin::root(),
+            GOERLI_CHAIN,
+            H160(hex_literal::hex!(
+                "228612206ba22b5af70b6812cb722dfe508a83ef"
+            )),
+            true
+        ));
+
+        // The receipts of the finalized block, moved to a block exactly at the age limit whose
+        // hash the light client knows
+        let mut block_header = block_header_convert(headers[0][0].clone());
+        block_header.number = latest - MaxProofAge::get();
+        let block_hash = block_header.hash();
+        insert_finalized_execution_block(GOERLI_CHAIN, block_header.number, block_hash);
+        assert_eq!(
+            Eth2Client::finalized_execution_blocks(GOERLI_CHAIN, block_header.number)
+                .map(|hash| H256(hash.0 .0)),
+            Some(block_hash)
+        );
+        let receipts = common::load_receipts(include_str!("./data/goerli/receipts_8652100.json"));
+        let mut proof = EventProof::build(block_header, block_hash, &receipts, 0).unwrap();
+
+        assert_ok!(ReceiptRegistry::submit_proof(
+            RuntimeOrigin::signed(ALICE),
+            GOERLI_CHAIN,
+            serde_json::to_string(&proof).unwrap().into()
+        ));
+        assert!(ReceiptRegistry::is_receipt_processed(
+            GOERLI_CHAIN,
+            proof.transaction_receipt_hash
+        ));
+
+        proof.block_header.number = latest - MaxProofAge::get() - 1;
+        assert_noop!(
+            ReceiptRegistry::submit_proof(
+                RuntimeOrigin::signed(ALICE),
+                GOERLI_CHAIN,
+                serde_json::to_string(&proof).unwrap().into()
+            ),
+            Error::<Test>::ProofTooOld
+        );
+    });
+}
+
+#[test]
+pub fn fungible_asset_moves_rewards_and_deposits() {
+    new_test_ext().execute_with(|| {
+        type RewardAsset = FungibleAsset<Assets, RewardAssetId>;
+        let treasury = ReceiptRegistry::account_id();
+        assert_ok!(Assets::force_create(
+            RuntimeOrigin::root(),
+            RewardAssetId::get(),
+            treasury.clone(),
+            true,
+            1
+        ));
+        assert_ok!(Assets::mint(
+            RuntimeOrigin::signed(treasury.clone()),
+            RewardAssetId::get(),
+            treasury.clone(),
+            100
+        ));
+
+        // Reward
+        assert_ok!(<RewardAsset as ProofCurrency<AccountId32>>::transfer(
+            &treasury, &ALICE, 30
+        ));
+        // Deposit
+        assert_ok!(<RewardAsset as ProofCurrency<AccountId32>>::transfer(
+            &ALICE, &treasury, 10
+        ));
+        assert_eq!(Assets::balance(RewardAssetId::get(), &ALICE), 20);
+        assert_eq!(Assets::balance(RewardAssetId::get(), &treasury), 80);
+
+        assert!(
+            <RewardAsset as ProofCurrency<AccountId32>>::transfer(&ALICE, &treasury, 21).is_err()
+        );
+        // The native balance is untouched.
+        assert_eq!(balance_of_user(&ALICE), 10u128.pow(18));
+    });
+}
+
+#[test]
+pub fn submit_proof_pays_in_reward_asset() {
+    new_test_ext().execute_with(|| {
+        let (headers, _updates, _init_input) = get_signed_test_context();
+        PayInRewardAsset::set(true);
+        let treasury = ReceiptRegistry::treasury_account();
+        assert_ok!(Assets::force_create(
+            RuntimeOrigin::root(),
+            RewardAssetId::get(),
+            treasury.clone(),
+            true,
+            1
+        ));
+        for (account, amount) in [(&treasury, 100), (&ALICE, 10)] {
+            assert_ok!(Assets::mint(
+                RuntimeOrigin::signed(treasury.clone()),
+                RewardAssetId::get(),
+                account.clone(),
+                amount
+            ));
+        }
+
+        const PROOF_DEPOSIT: u128 = 3;
+        const PROOF_REWARD: u128 = 5;
+        assert_ok!(ReceiptRegistry::update_proof_fee(
+            RuntimeOrigin::root(),
+            GOERLI_CHAIN,
+            PROOF_DEPOSIT,
+            PROOF_REWARD
+        ));
+        assert_ok!(ReceiptRegistry::update_watching_address(
+            RuntimeOrigin::root(),
+            GOERLI_CHAIN,
+            H160(hex_literal::hex!(
+                "228612206ba22b5af70b6812cb722dfe508a83ef"
+            )),
+            true
+        ));
+
+        let block_header = block_header_convert(headers[0][0].clone());
+        let block_hash = block_header.hash();
+        let receipts = common::load_receipts(include_str!("./data/goerli/receipts_8652100.json"));
+        let proof = EventProof::build(block_header, block_hash, &receipts, 0).unwrap();
+        let native_balances = (balance_of_user(&ALICE), balance_of_user(&treasury));
+
+        // The reward is paid in the asset
+        assert_ok!(ReceiptRegistry::submit_proof(
+            RuntimeOrigin::signed(ALICE),
+            GOERLI_CHAIN,
+            serde_json::to_vec(&proof).unwrap()
+        ));
+        assert_eq!(
+            Assets::balance(RewardAssetId::get(), &ALICE),
+            10 + PROOF_REWARD
+        );
+        assert_eq!(
+            Assets::balance(RewardAssetId::get(), &treasury),
+            100 - PROOF_REWARD
+        );
+
+        // And the deposit of a duplicate is taken in it
+        assert_ok!(ReceiptRegistry::submit_proof(
+            RuntimeOrigin::signed(ALICE),
+            GOERLI_CHAIN,
+            serde_json::to_vec(&proof).unwrap()
+        ));
+        assert_eq!(
+            Assets::balance(RewardAssetId::get(), &ALICE),
+            10 + PROOF_REWARD - PROOF_DEPOSIT
+        );
+        assert_eq!(
+            Assets::balance(RewardAssetId::get(), &treasury),
+            100 - PROOF_REWARD + PROOF_DEPOSIT
+        );
+
+        assert_eq!(
+            (balance_of_user(&ALICE), balance_of_user(&treasury)),
+            native_balances
+        );
+    });
+}
+
+#[test]
+pub fn test_submit_proof_no_watched_contract_in_logs() {
+    new_test_ext().execute_with(|| {
+        let (headers, _updates, _init_input) = get_signed_test_context();
+        System::set_block_number(1);
+
+        const PROOF_DEPOSIT: u128 = 1;
+        assert_ok!(ReceiptRegistry::update_proof_fee(
+            RuntimeOrigin::root(),
+            GOERLI_CHAIN,
+            PROOF_DEPOSIT,
+            2
+        ));
+        assert_ok!(ReceiptRegistry::update_watching_address(
+            RuntimeOrigin::root(),
+            GOERLI_CHAIN,
+            H160([1u8; 20]),
+            true
+        ));
+
+        let block_header = block_header_convert(headers[0][0].clone());
+        let block_hash = block_header.hash();
+        let receipts = common::load_receipts(include_str!("./data/goerli/receipts_8652100.json"));
+        let proof = EventProof::build(block_header, block_hash, &receipts, 0).unwrap();
+
+        let balance_before = balance_of_user(&ALICE);
+        assert_ok!(ReceiptRegistry::submit_proof(
+            RuntimeOrigin::signed(ALICE),
+            GOERLI_CHAIN,
+            serde_json::to_string(&proof).unwrap().into()
+        ));
+
+        // The deposit transfer is emitted after it, so it isn't the last event.
+        let expected =
+            RuntimeEvent::ReceiptRegistry(pallet_receipt_registry::Event::ProofAcceptedNoMatch {
+                typed_chain_id: GOERLI_CHAIN,
+                receipt_hash: proof.transaction_receipt_hash,
+            });
+        assert!(System::events()
+            .iter()
+            .any(|record| record.event == expected));
+        assert_eq!(balance_of_user(&ALICE), balance_before - PROOF_DEPOSIT);
+        assert!(!ReceiptRegistry::is_receipt_processed(
+            GOERLI_CHAIN,
+            proof.transaction_receipt_hash
+        ));
+    });
+}
+
+#[test]
+pub fn deposits_can_be_swept() {
+    new_test_ext().execute_with(|| {
+        let (headers, _updates, _init_input) = get_signed_test_context();
+        System::set_block_number(1);
+
+        const PROOF_DEPOSIT: u128 = 1_000;
+        assert_ok!(ReceiptRegistry::update_proof_fee(
+            RuntimeOrigin::root(),
+            GOERLI_CHAIN,
+            PROOF_DEPOSIT,
+            2
+        ));
+        assert_ok!(ReceiptRegistry::update_watching_address(
+            RuntimeOrigin::root(),
+            GOERLI_CHAIN,
+            H160([1u8; 20]),
+            true
+        ));
+
+        let block_header = block_header_convert(headers[0][0].clone());
+        let block_hash = block_header.hash();
+        let receipts = common::load_receipts(include_str!("./data/goerli/receipts_8652100.json"));
+        let proof = EventProof::build(block_header, block_hash, &receipts, 0).unwrap();
+
+        // Proofs without logs of watched contracts pay the deposit
+        let pallet_account = ReceiptRegistry::account_id();
+        let pallet_balance = balance_of_user(&pallet_account);
+        for _ in 0..2 {
+            assert_ok!(ReceiptRegistry::submit_proof(
+                RuntimeOrigin::signed(ALICE),
+                GOERLI_CHAIN,
+                serde_json::to_string(&proof).unwrap().into()
+            ));
+        }
+        let deposits = 2 * PROOF_DEPOSIT;
+        assert_eq!(balance_of_user(&pallet_account), pallet_balance + deposits);
+
+        assert_noop!(
+            ReceiptRegistry::sweep_deposits(RuntimeOrigin::signed(ALICE), CHARLIE, deposits),
+            DispatchError::BadOrigin
+        );
+
+        let charlie_balance = balance_of_user(&CHARLIE);
+        assert_ok!(ReceiptRegistry::sweep_deposits(
+            RuntimeOrigin::root(),
+            CHARLIE,
+            deposits
+        ));
+        assert_eq!(balance_of_user(&pallet_account), pallet_balance);
+        assert_eq!(balance_of_user(&CHARLIE), charlie_balance + deposits);
+        System::assert_last_event(RuntimeEvent::ReceiptRegistry(
+            pallet_receipt_registry::Event::DepositsSwept {
+                dest: CHARLIE,
+                amount: deposits,
+            },
+        ));
+
+        // The pallet account can't be emptied below the existential deposit
+        assert!(
+            ReceiptRegistry::sweep_deposits(RuntimeOrigin::root(), CHARLIE, pallet_balance)
+                .is_err()
+        );
+        assert_eq!(balance_of_user(&pallet_account), pallet_balance);
+    });
+}
+
+#[test]
+pub fn deposits_are_swept_from_the_reward_account() {
+    new_test_ext().execute_with(|| {
+        let (headers, _updates, _init_input) = get_signed_test_context();
+        System::set_block_number(1);
+
+        const PROOF_DEPOSIT: u128 = 1_000;
+        assert_ok!(ReceiptRegistry::set_reward_account(
+            RuntimeOrigin::root(),
+            Some(CHARLIE)
+        ));
+        assert_ok!(ReceiptRegistry::update_proof_fee(
+            RuntimeOrigin::root(),
+            GOERLI_CHAIN,
+            PROOF_DEPOSIT,
+            2
+        ));
+        assert_ok!(ReceiptRegistry::update_watching_address(
+            RuntimeOrigin::root(),
+            GOERLI_CHAIN,
+            H160([1u8; 20]),
+            true
+        ));
+
+        let block_header = block_header_convert(headers[0][0].clone());
+        let block_hash = block_header.hash();
+        let receipts = common::load_receipts(include_str!("./data/goerli/receipts_8652100.json"));
+        let proof = EventProof::build(block_header, block_hash, &receipts, 0).unwrap();
+
+        // Deposits are paid to the reward account, not the pallet account
+        let pallet_account = ReceiptRegistry::account_id();
+        let pallet_balance = balance_of_user(&pallet_account);
+        let charlie_balance = balance_of_user(&CHARLIE);
+        assert_ok!(ReceiptRegistry::submit_proof(
+            RuntimeOrigin::signed(ALICE),
+            GOERLI_CHAIN,
+            serde_json::to_string(&proof).unwrap().into()
+        ));
+        assert_eq!(balance_of_user(&CHARLIE), charlie_balance + PROOF_DEPOSIT);
+
+        let alice_balance = balance_of_user(&ALICE);
+        assert_ok!(ReceiptRegistry::sweep_deposits(
+            RuntimeOrigin::root(),
+            ALICE,
+            PROOF_DEPOSIT
+        ));
+        assert_eq!(balance_of_user(&CHARLIE), charlie_balance);
+        assert_eq!(balance_of_user(&ALICE), alice_balance + PROOF_DEPOSIT);
+        assert_eq!(balance_of_user(&pallet_account), pallet_balance);
+    });
+}
+
+/// Watches an address, sets the proof fees and processes `receipts` receipts of the chain, which
+/// makes `2 * receipts + 3` storage entries.
+fn populate_chain(typed_chain_id: TypedChainId, receipts: u8) {
+    assert_ok!(ReceiptRegistry::update_watching_address(
+        RuntimeOrigin::root(),
+        typed_chain_id,
+        H160([1; 20]),
+        true
+    ));
+    assert_ok!(ReceiptRegistry::update_proof_fee(
+        RuntimeOrigin::root(),
+        typed_chain_id,
+        1,
+        2
+    ));
+    for i in 0..receipts {
+        assert_ok!(ReceiptRegistry::force_mark_processed(
+            RuntimeOrigin::root(),
+            typed_chain_id,
+            100 + u64::from(i % 3),
+            H256([i; 32]),
+            vec![]
+        ));
+    }
+}
+
+fn chain_cleared(typed_chain_id: TypedChainId) -> bool {
+    System::events().iter().any(|record| {
+        record.event
+            == RuntimeEvent::ReceiptRegistry(pallet_receipt_registry::Event::ChainCleared {
+                typed_chain_id,
+            })
+    })
+}
+
+#[test]
+pub fn clear_chain_in_steps() {
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+        populate_chain(GOERLI_CHAIN, 10);
+        populate_chain(MAINNET_CHAIN, 2);
+
+        assert_noop!(
+            ReceiptRegistry::clear_chain(RuntimeOrigin::signed(ALICE), GOERLI_CHAIN, 100),
+            DispatchError::BadOrigin
+        );
+
+        // 23 entries, removed 7 at a time
+        for _ in 0..3 {
+            System::reset_events();
+            assert_ok!(ReceiptRegistry::clear_chain(
+                RuntimeOrigin::root(),
+                GOERLI_CHAIN,
+                7
+            ));
+            System::assert_has_event(RuntimeEvent::ReceiptRegistry(
+                pallet_receipt_registry::Event::ChainStorageRemoved {
+                    typed_chain_id: GOERLI_CHAIN,
+                    removed: 7,
+                },
+            ));
+            assert!(!chain_cleared(GOERLI_CHAIN));
+        }
+        // The receipts are removed first
+        assert!(ReceiptRegistry::processed_receipts_page(GOERLI_CHAIN, None, 100).is_empty());
+        assert!(!ReceiptRegistry::is_receipt_processed(
+            GOERLI_CHAIN,
+            H256([9; 32])
+        ));
+        assert_eq!(ReceiptRegistry::proof_reward(GOERLI_CHAIN), 2);
+
+        System::reset_events();
+        let weight = ReceiptRegistry::clear_chain(RuntimeOrigin::root(), GOERLI_CHAIN, 7)
+            .unwrap()
+            .actual_weight
+            .unwrap();
+        assert_eq!(
+            weight,
+            pallet_receipt_registry::weights::clear_chain::<Test>(2)
+        );
+        System::assert_has_event(RuntimeEvent::ReceiptRegistry(
+            pallet_receipt_registry::Event::ChainStorageRemoved {
+                typed_chain_id: GOERLI_CHAIN,
+                removed: 2,
+            },
+        ));
+        System::assert_last_event(RuntimeEvent::ReceiptRegistry(
+            pallet_receipt_registry::Event::ChainCleared {
+                typed_chain_id: GOERLI_CHAIN,
+            },
+        ));
+
+        for i in 0..10 {
+            assert!(!ReceiptRegistry::is_receipt_processed(
+                GOERLI_CHAIN,
+                H256([i; 32])
+            ));
+        }
+        assert_eq!(ReceiptRegistry::watched_contracts(GOERLI_CHAIN), None);
+        assert_eq!(ReceiptRegistry::proof_deposit(GOERLI_CHAIN), 0);
+        assert_eq!(ReceiptRegistry::proof_reward(GOERLI_CHAIN), 0);
+
+        // Other chains are left alone
+        assert_eq!(
+            ReceiptRegistry::processed_receipts_page(MAINNET_CHAIN, None, 100).len(),
+            2
+        );
+        assert!(ReceiptRegistry::is_receipt_processed(
+            MAINNET_CHAIN,
+            H256([1; 32])
+        ));
+        assert!(ReceiptRegistry::watched_contracts(MAINNET_CHAIN).is_some());
+        assert_eq!(ReceiptRegistry::proof_reward(MAINNET_CHAIN), 2);
+
+        // Clearing a cleared chain removes nothing
+        System::reset_events();
+        assert_ok!(ReceiptRegistry::clear_chain(
+            RuntimeOrigin::root(),
+            GOERLI_CHAIN,
+            7
+        ));
+        System::assert_has_event(RuntimeEvent::ReceiptRegistry(
+            pallet_receipt_registry::Event::ChainStorageRemoved {
+                typed_chain_id: GOERLI_CHAIN,
+                removed: 0,
+            },
+        ));
+        assert!(chain_cleared(GOERLI_CHAIN));
+    });
+}
+
+#[test]
+pub fn test_relayer_rewards_accumulate() {
+    new_test_ext().execute_with(|| {
+        let (headers, _updates, _init_input) = get_signed_test_context();
+        System::set_block_number(1);
+
+        const PROOF_REWARD: u128 = 2;
+        assert_ok!(ReceiptRegistry::update_proof_fee(
+            RuntimeOrigin::root(),
+            GOERLI_CHAIN,
+            1,
+            PROOF_REWARD
+        ));
+        for address in [
+            hex_literal::hex!("228612206ba22b5af70b6812cb722dfe508a83ef"),
+            hex_literal::hex!("5a94dc6cc85fda49d8e9a8b85dde8629025c42be"),
+        ] {
+            assert_ok!(ReceiptRegistry::update_watching_address(
+                RuntimeOrigin::root(),
+                GOERLI_CHAIN,
+                H160(address),
+                true
+            ));
+        }
+
+        let block_header = block_header_convert(headers[0][0].clone());
+        let block_hash = block_header.hash();
+        let receipts = common::load_receipts(include_str!("./data/goerli/receipts_8652100.json"));
+
+        assert_eq!(ReceiptRegistry::relayer_rewards(&ALICE), 0);
+        for (index, total) in [(0, PROOF_REWARD), (2, 2 * PROOF_REWARD)] {
+            let proof =
+                EventProof::build(block_header.clone(), block_hash, &receipts, index).unwrap();
+            assert_ok!(ReceiptRegistry::submit_proof(
+                RuntimeOrigin::signed(ALICE),
+                GOERLI_CHAIN,
+                serde_json::to_string(&proof).unwrap().into()
+            ));
+
+            assert_eq!(ReceiptRegistry::relayer_rewards(&ALICE), total);
+            let expected =
+                RuntimeEvent::ReceiptRegistry(pallet_receipt_registry::Event::RelayerRewarded {
+                    relayer: ALICE,
+                    reward: PROOF_REWARD,
+                    total,
+                });
+            assert!(System::events()
+                .iter()
+                .any(|record| record.event == expected));
+        }
+        assert_eq!(ReceiptRegistry::relayer_rewards(&CHARLIE), 0);
+    });
+}
+
+#[test]
+pub fn non_evm_chains_have_own_keys() {
+    new_test_ext().execute_with(|| {
+        assert_eq!(typed_chain_id(ChainKind::Evm, 5), GOERLI_CHAIN);
+        let substrate_chain = typed_chain_id(ChainKind::Substrate, 5);
+        assert_eq!(substrate_chain, TypedChainId::Substrate(5));
+        assert_ne!(typed_chain_id(ChainKind::Cosmos, 5), substrate_chain);
+
+        let address = H160([1u8; 20]);
+        assert_ok!(ReceiptRegistry::update_watching_address(
+            RuntimeOrigin::root(),
+            substrate_chain,
+            address,
+            true
+        ));
+        assert_eq!(
+            ReceiptRegistry::watched_contracts(substrate_chain)
+                .unwrap()
+                .to_vec(),
+            vec![address]
+        );
+        assert_eq!(ReceiptRegistry::watched_contracts(GOERLI_CHAIN), None);
+    });
+}
+
+/// The proof is built by the relayer, see `build_proof_matches_pallet_fixture`, and submitted in the
+/// same encoding the relayer sends.
+#[test]
+pub fn test_submit_relayer_built_proof() {
+    new_test_ext().execute_with(|| {
+        let (headers, _updates, _init_input) = get_signed_test_context();
+        assert_ok!(ReceiptRegistry::update_watching_address(
+            RuntimeOrigin::root(),
+            GOERLI_CHAIN,
+            H160(hex_literal::hex!(
+                "228612206ba22b5af70b6812cb722dfe508a83ef"
+            )),
+            true
+        ));
+
+        let serialized_proof = include_str!("./data/goerli/event_proof_8652100.json").trim_end();
+        let proof: EventProof = serde_json::from_str(serialized_proof).unwrap();
+        let block_header = block_header_convert(headers[0][0].clone());
+        assert_eq!(proof.block_header, block_header);
+        assert_eq!(proof.block_hash, block_header.hash());
+
+        assert_ok!(ReceiptRegistry::submit_proof(
+            RuntimeOrigin::signed(ALICE),
+            GOERLI_CHAIN,
+            serialized_proof.as_bytes().to_vec()
+        ));
+        assert!(ReceiptRegistry::is_receipt_processed(
+            GOERLI_CHAIN,
+            proof.transaction_receipt_hash
+        ));
     });
 }
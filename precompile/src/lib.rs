@@ -70,7 +70,10 @@ where
 
         let data = if let Some(data) =
             pallet_receipt_registry::Pallet::<Runtime>::processed_receipts((
-                webb_proposals::TypedChainId::Evm(chain_id),
+                pallet_receipt_registry::typed_chain_id(
+                    pallet_receipt_registry::ChainKind::Evm,
+                    chain_id,
+                ),
                 block_number,
                 types::H256(receipt_hash.0),
             )) {
@@ -81,7 +84,7 @@ where
 
         let (topics, data): (Vec<_>, Vec<_>) = data
             .into_iter()
-            .filter(|log| log.address == contract_address)
+            .filter(|log| log.address.ct_eq(&contract_address))
             .map(|log| {
                 let topics = log
                     .topics
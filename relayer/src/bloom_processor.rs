@@ -1,15 +1,34 @@
-use std::sync::atomic::AtomicBool;
+use std::collections::{BTreeMap, HashSet};
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 
-use ethers::providers::{Http, Middleware, Provider};
+use ethers::providers::{Http, JsonRpcError, Middleware, Provider, RpcError};
+use ethers::types::BlockNumber;
 use futures::future::join_all;
-use types::{BlockHeaderWithTransaction, TransactionReceipt, H160, H256};
+use types::{
+    BlockHeaderWithTransaction, EventProof, MerkleProof, TransactionReceipt, ValidationError, H160,
+    H256,
+};
 
 use crate::common::*;
-use crate::config::Config;
+use crate::config::{Config, ProcessingOrder};
 use crate::consts::SLEEP_DURATION;
 use crate::db::DB;
-use crate::substrate_client::SubstrateClient;
+use crate::metrics::metrics;
+use crate::substrate_client::{encode_proof, SubmitError, SubstrateClient};
+
+/// What a single [`BloomProcessor::process_blocks`] pass did.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PassOutcome {
+    /// Unprocessed blocks selected for the pass
+    pub blocks: usize,
+    /// Blocks whose proofs were accepted
+    pub submitted: usize,
+    /// Blocks whose receipts couldn't be fetched or whose proofs failed
+    pub failed: usize,
+}
 
 pub struct BloomProcessor {
     db: DB,
@@ -18,6 +37,17 @@ pub struct BloomProcessor {
     term: Arc<AtomicBool>,
     chain_id: u32,
     limit_processing_blocks_per_iteration: u64,
+    processing_order: ProcessingOrder,
+    // Event signatures required in the logs of the address
+    watched_topics: Vec<(H160, H256)>,
+    // Cleared once the node turns out not to support `eth_getBlockReceipts`
+    block_receipts_supported: AtomicBool,
+    max_fee_reward_ratio: Option<f64>,
+    max_proof_bytes: usize,
+    max_in_flight_submissions: usize,
+    // How long a block whose proofs were sent is not selected again
+    submission_cooldown: Option<Duration>,
+    dry_run: bool,
 
     // Cache of watched addresses
     watched_addresses: Option<Vec<H160>>,
@@ -34,12 +64,12 @@ impl BloomProcessor {
         let limit_processing_blocks_per_iteration = config
             .bloom_processor_limit_per_block
             .unwrap_or(crate::consts::DEFAULT_LIMIT_PROCESSING_BLOCKS_PER_ITERATION);
-        let config = prepare_config(&config);
+        let helios_config = prepare_config(&config)?;
         let fetch_rpc =
-            Provider::<Http>::try_from(config.execution_rpc.as_str()).map_err(|err| {
+            Provider::<Http>::try_from(helios_config.execution_rpc.as_str()).map_err(|err| {
                 eyre::eyre!(
                     "Failed to connect to execution RPC at {} with error: {}",
-                    config.execution_rpc,
+                    helios_config.execution_rpc,
                     err
                 )
             })?;
@@ -52,6 +82,18 @@ impl BloomProcessor {
             chain_id,
             watched_addresses: None,
             limit_processing_blocks_per_iteration,
+            processing_order: config.processing_order,
+            watched_topics: config.watched_topics,
+            block_receipts_supported: AtomicBool::new(true),
+            max_fee_reward_ratio: config.max_fee_reward_ratio,
+            max_proof_bytes: config
+                .max_proof_bytes
+                .unwrap_or(crate::consts::DEFAULT_MAX_PROOF_BYTES),
+            max_in_flight_submissions: config
+                .max_in_flight_submissions
+                .unwrap_or(crate::consts::DEFAULT_MAX_IN_FLIGHT_SUBMISSIONS),
+            submission_cooldown: config.submission_cooldown_secs.map(Duration::from_secs),
+            dry_run: config.dry_run,
         })
     }
 
@@ -62,124 +104,170 @@ impl BloomProcessor {
         // Let's allow light client to sync
         let mut sleep = true;
         loop {
-            exit_if_term(self.term.clone());
+            if is_terminated(&self.term) {
+                return;
+            }
             if sleep {
                 log::info!(target: TARGET, "Sleeping for {} secs", SLEEP_DURATION.as_secs());
                 tokio::time::sleep(SLEEP_DURATION).await;
             }
 
-            let latest_finalized_block_on_chain = self
-                .substrate_client
-                .last_known_block_block_number(self.chain_id)
-                .await
-                .unwrap_or(0);
+            sleep = match self.process_blocks().await {
+                Ok(pass) => pass.blocks < self.limit_processing_blocks_per_iteration as usize,
+                Err(e) => {
+                    log::warn!(target: TARGET, "Skipping iteration: {}", e);
+                    true
+                }
+            };
+        }
+    }
+
+    /// Proves the events of up to `limit_processing_blocks_per_iteration` unprocessed blocks and
+    /// submits the proofs.
+    pub async fn process_blocks(&mut self) -> eyre::Result<PassOutcome> {
+        const TARGET: &str = "relayer::bloom_processor::process_blocks";
 
-            let blocks_to_process = self.db.select_blocks_to_process(
-                latest_finalized_block_on_chain,
-                self.limit_processing_blocks_per_iteration,
-            );
-            if blocks_to_process.is_err() {
-                log::warn!(target: TARGET, "Error while selecting blocks to process");
-                continue;
-            }
+        let latest_finalized_block_on_chain = self
+            .substrate_client
+            .last_known_block_block_number(self.chain_id)
+            .await;
+
+        let block_to_process = select_finalized_blocks(
+            &self.db,
+            latest_finalized_block_on_chain,
+            self.limit_processing_blocks_per_iteration,
+            self.processing_order,
+            self.submission_cooldown
+                .map(|cooldown| SystemTime::now() - cooldown),
+        )?;
+        if block_to_process.is_empty() {
+            log::info!(target: TARGET, "No blocks to process");
+            return Ok(PassOutcome::default());
+        }
+        let mut outcome = PassOutcome {
+            blocks: block_to_process.len(),
+            ..Default::default()
+        };
 
-            let block_to_process = blocks_to_process.unwrap();
-            if block_to_process.is_empty() {
-                log::info!(target: TARGET, "No blocks to process. Sleeping");
-                sleep = true;
+        log::info!(target: TARGET, "Processing {} blocks", block_to_process.len());
+        if let Ok(watched_addr) = self.substrate_client.watched_addresses(self.chain_id).await {
+            self.watched_addresses = Some(watched_addr);
+        }
+
+        let Some(watched_address) = &self.watched_addresses else {
+            return Err(eyre::eyre!("Watched addresses are not set"));
+        };
+
+        let receipts = block_to_process
+            .iter()
+            .map(|(_, _, block)| self.fetch_receipts(block));
+        let receipts = join_all(receipts).await;
+
+        log::info!(target: TARGET, "Fetched {} receipts", receipts.len());
+        let mut merkle_proofs = Vec::new();
+
+        for (block_data, receipt_data) in block_to_process.into_iter().zip(receipts.into_iter()) {
+            let (block_height, block_hash, block) = block_data;
+            if receipt_data.is_err() {
+                log::warn!(target: TARGET, "Error while fetching receipts for block {}", block_height);
+                outcome.failed += 1;
                 continue;
             }
-            sleep = block_to_process.len() < self.limit_processing_blocks_per_iteration as usize;
-
-            log::info!(target: TARGET, "Processing {} blocks", block_to_process.len());
-            if let Ok(watched_addr) = self.substrate_client.watched_addresses(self.chain_id).await {
-                self.watched_addresses = Some(watched_addr);
+            let receipts = receipt_data.unwrap();
+            if !blooms_match(block_height, &receipts) {
+                outcome.failed += 1;
+                continue;
             }
 
-            let watched_address = if let Some(watched_addr) = &self.watched_addresses {
-                watched_addr
-            } else {
-                log::warn!(target: TARGET, "Watched addresses are not set");
-                continue;
-            };
+            // We need to validate that the bloom filter contains the watch addresses as they might be false positives
+            let mut created_proof = false;
+            let mut skipped_oversized = false;
+            let mut proof_time = Duration::ZERO;
+            for (i, receipt) in receipts.iter().enumerate() {
+                let event_exist = has_watched_event(receipt, watched_address, &self.watched_topics);
 
-            let receipts = block_to_process
-                .iter()
-                .map(|(_, _, block)| self.fetch_receipts(block));
-            let receipts = join_all(receipts).await;
-
-            log::info!(target: TARGET, "Fetched {} receipts", receipts.len());
-            let mut merkle_proofs = Vec::new();
-
-            for (block_data, receipt_data) in block_to_process.into_iter().zip(receipts.into_iter())
-            {
-                let (block_height, block_hash, block) = block_data;
-                if receipt_data.is_err() {
-                    log::warn!(target: TARGET, "Error while fetching receipts for block {}", block_height);
-                    continue;
-                }
-                let receipts = receipt_data.unwrap();
-
-                // We need to validate that the bloom filter contains the watch addresses as they might be false positives
-                let mut created_proof = false;
-                for (i, receipt) in receipts.iter().enumerate() {
-                    let event_exist = watched_address.iter().any(|addr| {
-                        log::trace!(target: TARGET, "bloom positive: {:?}, but addr is {}", receipt.bloom.check_address(addr), receipt.receipt.logs.iter().any(|l| l.address == *addr));
-                        receipt.bloom.check_address(addr)
-                            && receipt.receipt.logs.iter().any(|l| l.address == *addr)
-                    });
-
-                    if event_exist {
-                        log::trace!(target: TARGET, "Found event for address {:?} in block {}", watched_address, block_height);
-                        // Check maybe the event is already submitted
-                        let receipt_hash = H256::hash(receipt);
-                        if self
-                            .substrate_client
-                            .is_item_proved(self.chain_id, receipt_hash)
-                            .await
-                            .unwrap_or_default()
-                        {
-                            log::trace!(target: TARGET, "Event already submitted");
-                            continue;
-                        }
+                if event_exist {
+                    log::trace!(target: TARGET, "Found event for address {:?} in block {}", watched_address, block_height);
+                    // Check maybe the event is already submitted
+                    let receipt_hash = H256::hash(receipt);
+                    if self
+                        .substrate_client
+                        .is_item_proved(self.chain_id, receipt_hash)
+                        .await
+                        .unwrap_or_default()
+                    {
+                        log::trace!(target: TARGET, "Event already submitted");
+                        continue;
+                    }
 
-                        if let Ok(proof) = build_receipt_proof(block_hash, &block, &receipts, i) {
+                    let started = Instant::now();
+                    let proof = build_receipt_proof(
+                        block_hash,
+                        &block,
+                        &receipts,
+                        i,
+                        Some(self.max_proof_bytes),
+                    );
+                    proof_time += started.elapsed();
+                    match proof {
+                        Ok(proof) => {
+                            let merkle_proof = &proof.merkle_proof_of_receipt;
+                            log::debug!(target: TARGET, "Proof of receipt {} in block {}: {} nodes, depth {}, {} bytes", i, block_height, merkle_proof.len(), merkle_proof.depth(), merkle_proof.encoded_byte_len());
+                            metrics().proofs_built.inc();
                             created_proof = true;
                             merkle_proofs.push(proof);
                         }
+                        Err(e) if e.is::<OversizedProof>() => skipped_oversized = true,
+                        Err(_) => {}
                     }
                 }
+            }
 
-                if !created_proof {
+            if !proof_time.is_zero() {
+                log::debug!(target: TARGET, "Built proofs for block {} with {} receipts in {} ms", block_height, receipts.len(), proof_time.as_millis());
+            }
+
+            if !created_proof {
+                if skipped_oversized {
+                    log::info!(target: TARGET, "Only oversized proofs in block {}, nothing to submit", block_height);
+                } else {
                     log::info!(target: TARGET, "false positive bloom filter for block {}", block_height);
-                    if let Err(e) = self.db.mark_block_processed(block_height) {
-                        log::warn!(target: TARGET, "Error while marking block {} as processed: {}", block_height, e);
-                    }
                 }
+                mark_processed(&self.db, self.dry_run, block_height);
             }
+        }
 
-            log::info!(target: TARGET, "Created {} event proofs", merkle_proofs.len());
+        let merkle_proofs = dedup_proofs(merkle_proofs);
+        log::info!(target: TARGET, "Created {} event proofs", merkle_proofs.len());
+        let merkle_proofs = retain_stored_blocks(merkle_proofs, |number| {
+            self.substrate_client
+                .finalized_block_hash(self.chain_id, number)
+        })
+        .await;
+        let merkle_proofs = match self.max_fee_reward_ratio {
+            Some(ratio) => match self.substrate_client.proof_economics(self.chain_id).await {
+                Ok(economics) => {
+                    retain_economical_proofs(merkle_proofs, economics.reward, ratio, |proof| {
+                        self.substrate_client.estimate_proof_fee(proof)
+                    })
+                    .await
+                }
+                Err(e) => {
+                    log::warn!(target: TARGET, "Error while querying the proof reward, retrying later: {}", e);
+                    Vec::new()
+                }
+            },
+            None => merkle_proofs,
+        };
 
+        let (submitted, failed) = submit_proofs(&self.db, self.dry_run, merkle_proofs, |proofs| {
             self.substrate_client
-                .send_event_proofs(merkle_proofs)
-                .await
-                .into_iter()
-                .for_each(|(height, res)| match res {
-                    Ok(_) => {
-                        log::info!(target: TARGET, "Successfully sent event proofs for block {}", height);
-                        if let Err(e) = self.db.mark_block_processed(height) {
-                            log::warn!(target: TARGET, "Error while marking block {} as processed: {}", height, e);
-                        }
-                    }
-                    Err(e) => {
-                        log::warn!(target: TARGET,
-                            "Error while sending event proofs for block {}: {}",
-                            height,
-                            e
-                        );
-                    }
-                });
-        }
+                .send_event_proofs(proofs, self.max_in_flight_submissions)
+        })
+        .await;
+        outcome.submitted += submitted;
+        outcome.failed += failed;
+        Ok(outcome)
     }
 
     async fn fetch_receipts(
@@ -188,6 +276,15 @@ impl BloomProcessor {
     ) -> eyre::Result<Vec<TransactionReceipt>> {
         const TARGET: &str = "relayer::bloom_processor::fetch_receipts";
 
+        if self.block_receipts_supported.load(Ordering::Relaxed) {
+            match self.fetch_block_receipts(block).await {
+                Ok(receipts) => return Ok(receipts),
+                Err(e) => {
+                    log::debug!(target: TARGET, "Falling back to per transaction receipts for block {}: {}", block.header.number, e);
+                }
+            }
+        }
+
         let mut receipts = Vec::with_capacity(block.transactions.len());
         let transaction_fut = block.transactions.iter().map(|tx| {
             let tx_hash = ethers::types::H256(tx.0);
@@ -197,9 +294,13 @@ impl BloomProcessor {
 
         for transaction in transactions {
             match transaction {
-                Ok(Some(receipt)) => {
-                    receipts.push(convert_ethers_receipt(receipt)?);
-                }
+                Ok(Some(receipt)) => match convert_ethers_receipt(receipt) {
+                    Ok(receipt) => receipts.push(receipt),
+                    Err(e) => {
+                        log::warn!(target: TARGET, "{}", e);
+                        return Err(e.into());
+                    }
+                },
                 Ok(None) => {
                     log::warn!(target: TARGET, "Transaction not found");
                     return Err(eyre::eyre!("transaction not found"));
@@ -217,35 +318,887 @@ impl BloomProcessor {
         );
         Ok(receipts)
     }
+
+    /// Fetches all receipts of the block in a single `eth_getBlockReceipts` call. Stops using the
+    /// method once the node reports it doesn't support it, other errors are transient.
+    async fn fetch_block_receipts(
+        &self,
+        block: &BlockHeaderWithTransaction,
+    ) -> eyre::Result<Vec<TransactionReceipt>> {
+        const TARGET: &str = "relayer::bloom_processor::fetch_block_receipts";
+
+        let result = self
+            .fetch_rpc
+            .request::<_, serde_json::Value>(
+                "eth_getBlockReceipts",
+                [BlockNumber::Number(block.header.number.into())],
+            )
+            .await;
+        let result = match result {
+            Ok(result) => result,
+            Err(e) => {
+                if e.as_error_response().is_some_and(is_unsupported_method) {
+                    log::info!(target: TARGET, "eth_getBlockReceipts is not supported by the node: {}", e);
+                    self.block_receipts_supported
+                        .store(false, Ordering::Relaxed);
+                }
+                return Err(e.into());
+            }
+        };
+
+        let receipts = convert_block_receipts(result)?;
+        if receipts.len() != block.transactions.len() {
+            return Err(eyre::eyre!(
+                "expected {} receipts, got {}",
+                block.transactions.len(),
+                receipts.len()
+            ));
+        }
+        Ok(receipts)
+    }
+}
+
+/// Whether the JSON-RPC error tells the node doesn't support the method: `-32601` "method not
+/// found", or `-32004` "method not supported" of EIP-1474. Rate limits or a block the node doesn't
+/// have yet are worth retrying.
+fn is_unsupported_method(error: &JsonRpcError) -> bool {
+    matches!(error.code, -32601 | -32004)
+}
+
+/// Whether the receipt has a log of a watched contract. For contracts with configured topics, the
+/// log's event signature, its first topic, has to be one of them.
+fn has_watched_event(
+    receipt: &TransactionReceipt,
+    watched_addresses: &[H160],
+    watched_topics: &[(H160, H256)],
+) -> bool {
+    const TARGET: &str = "relayer::bloom_processor::has_watched_event";
+
+    watched_addresses.iter().any(|address| {
+        log::trace!(target: TARGET, "bloom positive: {:?}, but addr is {}", receipt.bloom.check_address(address), receipt.has_log_from(*address));
+        if !receipt.bloom.check_address(address) {
+            return false;
+        }
+        receipt.logs_for_address(*address).any(|log| {
+            let mut topics = watched_topics
+                .iter()
+                .filter(|(topic_address, _)| topic_address.ct_eq(address))
+                .map(|(_, topic)| topic)
+                .peekable();
+            topics.peek().is_none()
+                || log
+                    .topics
+                    .first()
+                    .is_some_and(|signature| topics.any(|topic| topic.ct_eq(signature)))
+        })
+    })
+}
+
+/// Selects up to `limit` unprocessed blocks up to the light client's finalized height, in
+/// `order`, leaving out those whose proofs were sent at or after `attempted_before`. Fails if the
+/// height couldn't be queried, so a transient error never lets us treat the chain as having no
+/// finalized blocks, or prove blocks the light client doesn't know about yet.
+fn select_finalized_blocks(
+    db: &DB,
+    finalized_height: eyre::Result<u64>,
+    limit: u64,
+    order: ProcessingOrder,
+    attempted_before: Option<SystemTime>,
+) -> eyre::Result<Vec<(u64, H256, BlockHeaderWithTransaction)>> {
+    let finalized_height = finalized_height
+        .map_err(|e| eyre::eyre!("failed to query the light client finalized height: {e}"))?;
+    // The finalized block itself is stored by the light client too
+    let max_block = finalized_height.saturating_add(1);
+    match order {
+        ProcessingOrder::OldestFirst => {
+            db.select_blocks_to_process(max_block, limit, attempted_before)
+        }
+        ProcessingOrder::NewestFirst => {
+            db.select_blocks_to_process_desc(max_block, limit, attempted_before)
+        }
+    }
+    .map_err(|e| eyre::eyre!("failed to select blocks to process: {e}"))
+}
+
+/// Marks the block as processed. In dry-run mode it is left for a later real run.
+fn mark_processed(db: &DB, dry_run: bool, block_height: u64) {
+    const TARGET: &str = "relayer::bloom_processor::mark_processed";
+
+    if dry_run {
+        return;
+    }
+    if let Err(e) = db.mark_block_processed(block_height) {
+        log::warn!(target: TARGET, "Error while marking block {} as processed: {}", block_height, e);
+    }
+}
+
+/// Sends the proofs with `send` and marks a block processed once each of its proofs was accepted
+/// or failed for good. Blocks with a proof that failed for a transient reason are retried in a
+/// later iteration. The blocks are recorded as attempted before sending, so the
+/// submission cooldown holds them back if the relayer stops before marking them. In dry-run mode
+/// the proofs are only logged. Returns the number of blocks whose proofs were accepted and of
+/// those that failed.
+async fn submit_proofs<F, Fut>(
+    db: &DB,
+    dry_run: bool,
+    proofs: Vec<EventProof>,
+    send: F,
+) -> (usize, usize)
+where
+    F: FnOnce(Vec<EventProof>) -> Fut,
+    Fut: Future<Output = Vec<(u64, Result<(), SubmitError>)>>,
+{
+    const TARGET: &str = "relayer::bloom_processor::submit_proofs";
+
+    if dry_run {
+        for proof in proofs {
+            log::info!(target: TARGET, "Dry run: would submit proof of receipt {:?} in block {}", proof.transaction_receipt_hash, proof.block_header.number);
+        }
+        return (0, 0);
+    }
+
+    let heights: HashSet<_> = proofs
+        .iter()
+        .map(|proof| proof.block_header.number)
+        .collect();
+    let heights: Vec<_> = heights.into_iter().collect();
+    if let Err(e) = db.mark_submission_attempted(&heights, SystemTime::now()) {
+        log::warn!(target: TARGET, "Error while recording the submission of blocks {:?}: {}", heights, e);
+    }
+
+    // Results come one per proof, a block may have several
+    let mut results_by_height = BTreeMap::<_, Vec<_>>::new();
+    for (height, res) in send(proofs).await {
+        results_by_height.entry(height).or_default().push(res);
+    }
+
+    let (mut submitted, mut failed) = (0, 0);
+    for (height, results) in results_by_height {
+        let errors: Vec<_> = results.into_iter().filter_map(Result::err).collect();
+        if errors.is_empty() {
+            log::info!(target: TARGET, "Successfully sent event proofs for block {}", height);
+            mark_processed(db, dry_run, height);
+            submitted += 1;
+        } else if let Some(e) = errors.iter().find(|e| !e.is_permanent()) {
+            log::warn!(target: TARGET, "Error while sending event proofs for block {}, retrying later: {}", height, e);
+            failed += 1;
+        } else {
+            log::warn!(target: TARGET, "Giving up on event proofs for block {}: {}", height, errors[0]);
+            mark_processed(db, dry_run, height);
+            failed += 1;
+        }
+    }
+    (submitted, failed)
+}
+
+/// Keeps the proofs whose block the light client stores under the same hash. `submit_proof`
+/// rejects the others, so their blocks are left unprocessed and retried in a later iteration.
+async fn retain_stored_blocks<F, Fut>(
+    proofs: Vec<EventProof>,
+    mut stored_hash: F,
+) -> Vec<EventProof>
+where
+    F: FnMut(u64) -> Fut,
+    Fut: Future<Output = eyre::Result<Option<H256>>>,
+{
+    const TARGET: &str = "relayer::bloom_processor::retain_stored_blocks";
+
+    let mut stored = Vec::with_capacity(proofs.len());
+    for proof in proofs {
+        let number = proof.block_header.number;
+        match stored_hash(number).await {
+            Ok(Some(hash)) if hash == proof.block_hash => stored.push(proof),
+            Ok(Some(hash)) => {
+                log::warn!(target: TARGET, "Block {} is stored by the light client as {:?}, not {:?}", number, hash, proof.block_hash);
+            }
+            Ok(None) => {
+                log::info!(target: TARGET, "Block {} is not stored by the light client, retrying later", number);
+            }
+            Err(e) => {
+                log::warn!(target: TARGET, "Error while checking block {} on the light client: {}", number, e);
+            }
+        }
+    }
+    stored
+}
+
+/// Keeps the proofs whose estimated fee is at most `max_fee_reward_ratio` times the `reward`.
+/// All proofs of a block with a proof costing more, or whose fee couldn't be estimated, are
+/// dropped, so the block is left unprocessed and retried once fees drop.
+async fn retain_economical_proofs<F, Fut>(
+    proofs: Vec<EventProof>,
+    reward: u128,
+    max_fee_reward_ratio: f64,
+    mut estimate_fee: F,
+) -> Vec<EventProof>
+where
+    F: FnMut(Vec<u8>) -> Fut,
+    Fut: Future<Output = eyre::Result<u128>>,
+{
+    const TARGET: &str = "relayer::bloom_processor::retain_economical_proofs";
+
+    let max_fee = reward as f64 * max_fee_reward_ratio;
+    let mut deferred = HashSet::new();
+    for proof in &proofs {
+        let number = proof.block_header.number;
+        if deferred.contains(&number) {
+            continue;
+        }
+        match estimate_fee(encode_proof(proof)).await {
+            Ok(fee) if fee as f64 <= max_fee => {}
+            Ok(fee) => {
+                log::info!(target: TARGET, "Deferring proofs of block {}: fee {} exceeds {} times the reward {}", number, fee, max_fee_reward_ratio, reward);
+                metrics().proofs_deferred_for_fee.inc();
+                deferred.insert(number);
+            }
+            Err(e) => {
+                log::warn!(target: TARGET, "Error while estimating the fee of a proof of block {}, retrying later: {}", number, e);
+                deferred.insert(number);
+            }
+        }
+    }
+    proofs
+        .into_iter()
+        .filter(|proof| !deferred.contains(&proof.block_header.number))
+        .collect()
 }
 
-fn build_receipt_proof(
+/// Drops proofs of receipts that are already proven by another proof in the batch, keeping the
+/// one from the earliest block. Submitting the same receipt twice only wastes a deposit.
+fn dedup_proofs(mut proofs: Vec<EventProof>) -> Vec<EventProof> {
+    proofs.sort_by_key(|proof| proof.block_header.number);
+    let mut seen = HashSet::new();
+    proofs.retain(|proof| seen.insert(proof.transaction_receipt_hash));
+    proofs
+}
+
+/// A proof skipped by [`build_receipt_proof`] for being larger than the chain accepts.
+#[derive(Debug)]
+pub(crate) struct OversizedProof {
+    bytes: usize,
+    max_bytes: usize,
+}
+
+impl std::fmt::Display for OversizedProof {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "event proof of {} bytes exceeds {} bytes",
+            self.bytes, self.max_bytes
+        )
+    }
+}
+
+impl std::error::Error for OversizedProof {}
+
+pub(crate) fn build_receipt_proof(
     block_hash: H256,
     block: &BlockHeaderWithTransaction,
     receipts: &[TransactionReceipt],
     receipt_index: usize,
-) -> eyre::Result<types::EventProof, eyre::Error> {
-    use merkle_generator::IterativeTrie;
+    max_proof_bytes: Option<usize>,
+) -> eyre::Result<EventProof, eyre::Error> {
+    const TARGET: &str = "relayer::bloom_processor::build_receipt_proof";
+
+    // Building the receipt trie is most of the work, it grows with the receipts of the block
+    let timer = metrics().proof_build_seconds.start_timer();
+    let proof = EventProof::build(block.header.clone(), block_hash, receipts, receipt_index);
+    let elapsed = timer.stop_and_record();
+    log::debug!(target: TARGET, "Proof of receipt {} in block {} with {} receipts took {:.3} s", receipt_index, block.header.number, receipts.len(), elapsed);
+    let proof = proof.map_err(|e| {
+        report_rejection(block.header.number, receipt_index, &e);
+        if matches!(e, ValidationError::IncorrectReceiptRoot { .. }) {
+            if let Ok(merkle_proof) = MerkleProof::from_transactions(receipts, receipt_index) {
+                log::debug!(target: TARGET, "Rejected proof of receipt {} in block {}: {}", receipt_index, block.header.number, merkle_proof);
+            }
+        }
+        eyre::eyre!("invalid event proof: {:?}", e)
+    })?;
 
-    let mut trie = merkle_generator::PatriciaTrie::new();
+    // Submitting a proof the chain can't include would only cost the fee
+    let proof_bytes = encode_proof(&proof).len();
+    if let Some(max_proof_bytes) = max_proof_bytes.filter(|max| proof_bytes > *max) {
+        log::warn!(target: TARGET, "Skipping proof of receipt {} in block {}: {} bytes, more than {}", receipt_index, block.header.number, proof_bytes, max_proof_bytes);
+        metrics().proofs_oversized.inc();
+        return Err(OversizedProof {
+            bytes: proof_bytes,
+            max_bytes: max_proof_bytes,
+        }
+        .into());
+    }
+    Ok(proof)
+}
 
-    for (index, receipt) in receipts.iter().enumerate() {
-        let key = alloy_rlp::encode(index);
-        trie.insert(key, alloy_rlp::encode(receipt));
+/// Label of the `proof_rejections_total` metric for `err`.
+fn rejection_reason(err: &ValidationError) -> &'static str {
+    match err {
+        ValidationError::IncorrectBodyHash { .. } => "block_hash",
+        ValidationError::IncorrectReceiptHash { .. } => "receipt_hash",
+        ValidationError::IncorrectReceiptRoot { .. } => "receipts_root",
+        ValidationError::InvalidProofKey => "proof_key",
+        ValidationError::ProofPathMismatch { .. } => "proof_path",
+        ValidationError::BloomMismatch { .. } => "bloom",
+        ValidationError::TransactionIndexOutOfBounds { .. } => "transaction_index",
+    }
+}
+
+/// Whether the bloom of every receipt is the bloom of its logs, otherwise the first mismatch is
+/// reported as a rejection.
+fn blooms_match(block_height: u64, receipts: &[TransactionReceipt]) -> bool {
+    match receipts.iter().position(|receipt| !receipt.verify_bloom()) {
+        Some(transaction_index) => {
+            report_rejection(
+                block_height,
+                transaction_index,
+                &ValidationError::BloomMismatch { transaction_index },
+            );
+            false
+        }
+        None => true,
+    }
+}
+
+/// Logs which field of a proof didn't match and counts the rejection.
+fn report_rejection(block_height: u64, receipt_index: usize, err: &ValidationError) {
+    const TARGET: &str = "relayer::bloom_processor::proof_rejection";
+
+    let reason = rejection_reason(err);
+    match err {
+        ValidationError::IncorrectBodyHash { expected, actual } => {
+            log::warn!(target: TARGET,
+                "block {block_height}, receipt {receipt_index}: {reason} mismatch, expected 0x{}, header hashes to 0x{} (check the header conversion)",
+                hex::encode(expected.0), hex::encode(actual.0));
+        }
+        ValidationError::IncorrectReceiptHash { expected, actual } => {
+            log::warn!(target: TARGET,
+                "block {block_height}, receipt {receipt_index}: {reason} mismatch, expected 0x{}, actual 0x{} (check the receipt conversion)",
+                hex::encode(expected.0), hex::encode(actual.0));
+        }
+        ValidationError::IncorrectReceiptRoot {
+            expected,
+            actual,
+            transaction_index,
+        } => {
+            log::warn!(target: TARGET,
+                "block {block_height}, receipt {receipt_index}: {reason} mismatch for the proof of transaction {transaction_index}, expected 0x{}, actual 0x{} (check the receipt conversion)",
+                hex::encode(expected.0), hex::encode(actual.0));
+        }
+        _ => {
+            log::warn!(target: TARGET, "block {block_height}, receipt {receipt_index}: {reason} invalid: {err:?}");
+        }
     }
+    metrics()
+        .proof_rejections
+        .with_label_values(&[reason])
+        .inc();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::{Duration, SystemTime};
+
+    use tempfile::tempdir;
+    use types::{
+        BlockHeaderWithTransaction, Bloom, EventProof, Log, MerkleProof, Receipt,
+        TransactionReceipt, TxType, ValidationError, H160, H256,
+    };
 
-    let merkle_proof = trie.merkle_proof(alloy_rlp::encode(receipt_index));
-    let event_proof = types::EventProof {
-        block_header: block.header.clone(),
-        block_hash,
-        transaction_receipt: receipts[receipt_index].clone(),
-        transaction_receipt_hash: H256::hash(&receipts[receipt_index]),
-        merkle_proof_of_receipt: merkle_proof,
+    use super::{
+        blooms_match, build_receipt_proof, dedup_proofs, has_watched_event, is_unsupported_method,
+        mark_processed, rejection_reason, report_rejection, retain_economical_proofs,
+        retain_stored_blocks, select_finalized_blocks, submit_proofs, OversizedProof,
     };
+    use crate::build_proof::build_proof;
+    use crate::config::{BuildProofArgs, ProcessingOrder};
+    use crate::consts::DEFAULT_MAX_PROOF_BYTES;
+    use crate::db::DB;
+    use crate::metrics::metrics;
+    use crate::substrate_client::SubmitError;
+
+    fn proof(index: usize) -> EventProof {
+        let suits = PathBuf::from(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../merkle/tests/suits"
+        ));
+        build_proof(&BuildProofArgs {
+            block: suits.join("block_17819525.json"),
+            receipts: suits.join("block_17819525_receipts.json"),
+            index,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn dedup_keeps_earliest_block() {
+        let mut later = proof(0);
+        later.block_header.number += 1;
+        let other = proof(1);
+
+        let proofs = dedup_proofs(vec![later, other, proof(0)]);
+        assert_eq!(proofs.len(), 2);
+        let kept = proofs
+            .iter()
+            .find(|p| p.transaction_receipt_hash == proof(0).transaction_receipt_hash)
+            .unwrap();
+        assert_eq!(kept.block_header.number, 17819525);
+    }
+
+    #[test]
+    fn only_unsupported_method_disables_block_receipts() {
+        let error = |code, message: &str| ethers::providers::JsonRpcError {
+            code,
+            message: message.into(),
+            data: None,
+        };
+
+        assert!(is_unsupported_method(&error(
+            -32601,
+            "the method eth_getBlockReceipts does not exist/is not available"
+        )));
+        assert!(is_unsupported_method(&error(
+            -32004,
+            "method not supported"
+        )));
+        assert!(!is_unsupported_method(&error(
+            -32005,
+            "rate limit exceeded"
+        )));
+        assert!(!is_unsupported_method(&error(-32000, "block not found")));
+    }
+
+    #[test]
+    fn watched_topics_filter_events() {
+        let address = H160([0x11; 20]);
+        let signature = H256([0x22; 32]);
+        let other_signature = H256([0x33; 32]);
+        let receipt = |topics: Vec<H256>| {
+            let logs = vec![Log {
+                address,
+                topics,
+                data: vec![],
+            }];
+            TransactionReceipt {
+                bloom: Bloom::from_logs(&logs),
+                receipt: Receipt {
+                    tx_type: TxType::EIP1559,
+                    success: true,
+                    cumulative_gas_used: 21000,
+                    logs,
+                },
+            }
+        };
+        let other_event = receipt(vec![other_signature, signature]);
+
+        // Any event of an address without topics
+        assert!(has_watched_event(&other_event, &[address], &[]));
+        assert!(!has_watched_event(&other_event, &[H160([0x44; 20])], &[]));
+
+        // Only the event signature counts, not the other topics
+        let watched_topics = [(address, signature)];
+        assert!(!has_watched_event(
+            &other_event,
+            &[address],
+            &watched_topics
+        ));
+        assert!(!has_watched_event(
+            &receipt(vec![]),
+            &[address],
+            &watched_topics
+        ));
+        assert!(has_watched_event(
+            &receipt(vec![signature]),
+            &[address],
+            &watched_topics
+        ));
+    }
+
+    #[test]
+    fn oversized_proof_is_skipped() {
+        // A block whose only receipt has a hundred 10 kB logs
+        let logs: Vec<_> = (0..100)
+            .map(|_| Log {
+                address: H160([0x11; 20]),
+                topics: vec![H256([0x22; 32])],
+                data: vec![0xff; 10_000],
+            })
+            .collect();
+        let receipts = vec![TransactionReceipt {
+            bloom: Bloom::from_logs(&logs),
+            receipt: Receipt {
+                tx_type: TxType::EIP1559,
+                success: true,
+                cumulative_gas_used: 21000,
+                logs,
+            },
+        }];
+        let mut header = proof(0).block_header;
+        header.receipts_root = MerkleProof::from_transactions(&receipts, 0)
+            .unwrap()
+            .merkle_root(&receipts[0]);
+        let block_hash = header.hash();
+        let block = BlockHeaderWithTransaction {
+            header,
+            transactions: vec![],
+        };
+
+        // Each data byte ends up twice in the JSON, as part of the receipt and of its trie leaf
+        let oversized = metrics().proofs_oversized.get();
+        assert!(build_receipt_proof(
+            block_hash,
+            &block,
+            &receipts,
+            0,
+            Some(DEFAULT_MAX_PROOF_BYTES)
+        )
+        .unwrap_err()
+        .is::<OversizedProof>());
+        assert_eq!(metrics().proofs_oversized.get(), oversized + 1);
+
+        assert!(build_receipt_proof(block_hash, &block, &receipts, 0, Some(5_000_000)).is_ok());
+        assert!(build_receipt_proof(block_hash, &block, &receipts, 0, None).is_ok());
+        assert_eq!(metrics().proofs_oversized.get(), oversized + 1);
+    }
+
+    #[test]
+    fn corrupted_receipt_is_reported() {
+        let mut proof = proof(0);
+        proof.transaction_receipt.receipt.cumulative_gas_used += 1;
+
+        let err = proof.validate().unwrap_err();
+        assert!(matches!(err, ValidationError::IncorrectReceiptHash { .. }));
+        assert_eq!(rejection_reason(&err), "receipt_hash");
+
+        let rejections = metrics()
+            .proof_rejections
+            .with_label_values(&["receipt_hash"]);
+        let before = rejections.get();
+        report_rejection(proof.block_header.number, 0, &err);
+        assert_eq!(rejections.get(), before + 1);
+        assert!(metrics()
+            .encode()
+            .contains("relayer_proof_rejections_total{reason=\"receipt_hash\"}"));
+    }
+
+    #[test]
+    fn bloom_mismatch_is_reported() {
+        let receipts = vec![proof(0).transaction_receipt, proof(1).transaction_receipt];
+        assert!(blooms_match(17819525, &receipts));
 
-    if let Err(e) = event_proof.validate() {
-        Err(eyre::eyre!("invalid event proof: {:?}", e))
-    } else {
-        Ok(event_proof)
+        let mut tampered = receipts;
+        tampered[1].bloom = Bloom::new([0; 256]);
+        let rejections = metrics().proof_rejections.with_label_values(&["bloom"]);
+        let before = rejections.get();
+        assert!(!blooms_match(17819525, &tampered));
+        assert_eq!(rejections.get(), before + 1);
+    }
+
+    #[test]
+    fn proof_build_time_is_recorded() {
+        let built = metrics().proof_build_seconds.get_sample_count();
+        // Goes through `build_receipt_proof`
+        proof(0);
+        assert!(metrics().proof_build_seconds.get_sample_count() > built);
+        assert!(metrics()
+            .encode()
+            .contains("relayer_proof_build_seconds_bucket{le=\"0.001\"}"));
+    }
+
+    /// Submits `proofs` for blocks stored in a fresh database, each ending with `outcome`.
+    /// Returns the number of proofs handed to the sender and the blocks left to process.
+    async fn submit(
+        dry_run: bool,
+        proofs: Vec<EventProof>,
+        outcome: Result<(), SubmitError>,
+    ) -> (usize, Vec<u64>) {
+        let dir = tempdir().unwrap();
+        let db = DB::new(dir.path()).unwrap();
+        db.create_tables().unwrap();
+        for proof in &proofs {
+            let block = BlockHeaderWithTransaction {
+                header: proof.block_header.clone(),
+                transactions: vec![],
+            };
+            db.insert_block(proof.block_header.number, proof.block_hash, block, true)
+                .unwrap();
+        }
+        // A bloom false positive. Headers are unique in the database.
+        let block = proofs[0].block_header.number + 1;
+        let mut header = proofs[0].block_header.clone();
+        header.number = block;
+        db.insert_block(
+            block,
+            Default::default(),
+            BlockHeaderWithTransaction {
+                header,
+                transactions: vec![],
+            },
+            true,
+        )
+        .unwrap();
+        mark_processed(&db, dry_run, block);
+
+        let sent = AtomicUsize::new(0);
+        submit_proofs(&db, dry_run, proofs, |proofs| {
+            sent.fetch_add(proofs.len(), Ordering::Relaxed);
+            let results: Vec<_> = proofs
+                .iter()
+                .map(|proof| (proof.block_header.number, outcome.clone()))
+                .collect();
+            async move { results }
+        })
+        .await;
+
+        let left = db
+            .select_blocks_to_process(i64::MAX as u64, 10, None)
+            .unwrap()
+            .into_iter()
+            .map(|(height, _, _)| height)
+            .collect();
+        (sent.into_inner(), left)
+    }
+
+    #[tokio::test]
+    async fn dry_run_sends_and_marks_nothing() {
+        assert_eq!(
+            submit(true, vec![proof(0)], Ok(())).await,
+            (0, vec![17819525, 17819526])
+        );
+        assert_eq!(submit(false, vec![proof(0)], Ok(())).await, (1, vec![]));
+    }
+
+    #[tokio::test]
+    async fn only_transient_errors_are_retried() {
+        let verify_failed = SubmitError::DispatchModule {
+            pallet: "EthReceiptRegistry".into(),
+            error: "VerifyProofFail".into(),
+        };
+        assert_eq!(
+            submit(false, vec![proof(0)], Err(verify_failed)).await,
+            (1, vec![])
+        );
+        assert_eq!(
+            submit(false, vec![proof(0)], Err(SubmitError::AlreadyProcessed)).await,
+            (1, vec![])
+        );
+        assert_eq!(
+            submit(false, vec![proof(0)], Err(SubmitError::Timeout)).await,
+            (1, vec![17819525])
+        );
+    }
+
+    #[tokio::test]
+    async fn submitted_and_failed_blocks_are_counted() {
+        let dir = tempdir().unwrap();
+        let db = DB::new(dir.path()).unwrap();
+        db.create_tables().unwrap();
+        let results = vec![
+            (1, Ok(())),
+            (2, Err(SubmitError::AlreadyProcessed)),
+            (3, Err(SubmitError::Timeout)),
+        ];
+        assert_eq!(
+            submit_proofs(&db, false, vec![proof(0)], |_| async move { results }).await,
+            (1, 2)
+        );
+        assert_eq!(
+            submit_proofs(&db, true, vec![proof(0)], |_| async { Vec::new() }).await,
+            (0, 0)
+        );
+    }
+
+    #[tokio::test]
+    async fn block_is_retried_until_all_its_proofs_are_done() {
+        let dir = tempdir().unwrap();
+        let db = DB::new(dir.path()).unwrap();
+        db.create_tables().unwrap();
+        let proofs = vec![proof(0), proof(1)];
+        let height = proofs[0].block_header.number;
+        let block = BlockHeaderWithTransaction {
+            header: proofs[0].block_header.clone(),
+            transactions: vec![],
+        };
+        db.insert_block(height, proofs[0].block_hash, block, true)
+            .unwrap();
+        let left = || {
+            db.select_blocks_to_process(i64::MAX as u64, 10, None)
+                .unwrap()
+                .into_iter()
+                .map(|(height, _, _)| height)
+                .collect::<Vec<_>>()
+        };
+
+        let results = vec![
+            (height, Ok(())),
+            (
+                height,
+                Err(SubmitError::Transport("connection reset".into())),
+            ),
+        ];
+        assert_eq!(
+            submit_proofs(&db, false, proofs.clone(), |_| async move { results }).await,
+            (0, 1)
+        );
+        assert_eq!(left(), [height]);
+
+        let results = vec![
+            (height, Ok(())),
+            (height, Err(SubmitError::AlreadyProcessed)),
+        ];
+        assert_eq!(
+            submit_proofs(&db, false, proofs, |_| async move { results }).await,
+            (0, 1)
+        );
+        assert!(left().is_empty());
+    }
+
+    #[tokio::test]
+    async fn attempted_blocks_wait_for_cooldown() {
+        let dir = tempdir().unwrap();
+        let db = DB::new(dir.path()).unwrap();
+        db.create_tables().unwrap();
+        let proof = proof(0);
+        let height = proof.block_header.number;
+        let block = BlockHeaderWithTransaction {
+            header: proof.block_header.clone(),
+            transactions: vec![],
+        };
+        db.insert_block(height, proof.block_hash, block, true)
+            .unwrap();
+
+        // The relayer stops after sending, before the block is marked processed
+        submit_proofs(&db, false, vec![proof], |_| async { Vec::new() }).await;
+
+        let cooldown = Duration::from_secs(10 * 60);
+        let select = |attempted_before| {
+            select_finalized_blocks(
+                &db,
+                Ok(height),
+                10,
+                ProcessingOrder::OldestFirst,
+                attempted_before,
+            )
+            .unwrap()
+            .len()
+        };
+        assert_eq!(select(Some(SystemTime::now() - cooldown)), 0);
+        assert_eq!(select(Some(SystemTime::now() + Duration::from_secs(1))), 1);
+        assert_eq!(select(None), 1);
+    }
+
+    #[test]
+    fn failed_height_query_selects_nothing() {
+        let dir = tempdir().unwrap();
+        let db = DB::new(dir.path()).unwrap();
+        db.create_tables().unwrap();
+        let proof = proof(0);
+        let block = BlockHeaderWithTransaction {
+            header: proof.block_header.clone(),
+            transactions: vec![],
+        };
+        db.insert_block(proof.block_header.number, proof.block_hash, block, true)
+            .unwrap();
+
+        let failed = select_finalized_blocks(
+            &db,
+            Err(eyre::eyre!("No finalized header")),
+            10,
+            ProcessingOrder::OldestFirst,
+            None,
+        );
+        assert!(failed.is_err());
+        assert_eq!(
+            db.select_blocks_to_process(i64::MAX as u64, 10, None)
+                .unwrap()
+                .len(),
+            1
+        );
+
+        // The finalized block itself can be proven
+        let selected = select_finalized_blocks(
+            &db,
+            Ok(proof.block_header.number),
+            10,
+            ProcessingOrder::NewestFirst,
+            None,
+        )
+        .unwrap();
+        assert_eq!(selected.len(), 1);
+        let selected = select_finalized_blocks(
+            &db,
+            Ok(proof.block_header.number - 1),
+            10,
+            ProcessingOrder::OldestFirst,
+            None,
+        )
+        .unwrap();
+        assert!(selected.is_empty());
+    }
+
+    #[tokio::test]
+    async fn only_stored_blocks_are_submitted() {
+        // The light client stores the blocks up to the proof's block, all with its hash
+        let boundary = proof(0).block_header.number;
+        let hash = proof(0).block_hash;
+        let stored_hash =
+            |number| async move { Ok::<_, eyre::Report>((number <= boundary).then_some(hash)) };
+
+        let mut too_new = proof(1);
+        too_new.block_header.number = boundary + 1;
+        let mut reorged = proof(2);
+        reorged.block_header.number = boundary - 1;
+        reorged.block_hash = H256([0xab; 32]);
+
+        let retained = retain_stored_blocks(vec![proof(0), too_new, reorged], stored_hash).await;
+        assert_eq!(retained.len(), 1);
+        assert_eq!(retained[0].block_header.number, boundary);
+        assert_eq!(
+            retained[0].transaction_receipt_hash,
+            proof(0).transaction_receipt_hash
+        );
+
+        let retained = retain_stored_blocks(vec![proof(0)], |_| async {
+            Err(eyre::eyre!("connection closed"))
+        })
+        .await;
+        assert!(retained.is_empty());
+    }
+
+    #[tokio::test]
+    async fn expensive_proofs_are_deferred() {
+        let estimate = |fee: u128| move |_: Vec<u8>| async move { Ok::<_, eyre::Report>(fee) };
+        let deferred = metrics().proofs_deferred_for_fee.get();
+
+        // The fee may be up to 1.5 times the reward of 10
+        let retained = retain_economical_proofs(vec![proof(0)], 10, 1.5, estimate(15)).await;
+        assert_eq!(retained.len(), 1);
+        assert_eq!(metrics().proofs_deferred_for_fee.get(), deferred);
+
+        let retained = retain_economical_proofs(vec![proof(0)], 10, 1.5, estimate(16)).await;
+        assert!(retained.is_empty());
+        assert_eq!(metrics().proofs_deferred_for_fee.get(), deferred + 1);
+
+        // Another proof of a block with a deferred proof is deferred too
+        let mut other_block = proof(1);
+        other_block.block_header.number += 1;
+        let mut fees = [20, 5].into_iter();
+        let retained =
+            retain_economical_proofs(vec![proof(0), proof(2), other_block], 10, 1.5, |_| {
+                let fee = fees.next().unwrap();
+                async move { Ok::<_, eyre::Report>(fee) }
+            })
+            .await;
+        assert_eq!(retained.len(), 1);
+        assert_eq!(
+            retained[0].block_header.number,
+            proof(0).block_header.number + 1
+        );
+
+        let retained = retain_economical_proofs(vec![proof(0)], 10, 1.5, |_| async {
+            Err::<u128, _>(eyre::eyre!("connection closed"))
+        })
+        .await;
+        assert!(retained.is_empty());
     }
 }
@@ -0,0 +1,36 @@
+use std::fs;
+
+use types::{EventProof, H256};
+
+use crate::bloom_processor::build_receipt_proof;
+use crate::common::{convert_ethers_block, convert_ethers_receipt};
+use crate::config::BuildProofArgs;
+
+/// Builds the proof the relayer would submit for `args.index` and prints it to stdout.
+pub fn run(args: &BuildProofArgs) -> eyre::Result<()> {
+    let proof = build_proof(args)?;
+    println!("{}", serde_json::to_string(&proof)?);
+    Ok(())
+}
+
+pub(crate) fn build_proof(args: &BuildProofArgs) -> eyre::Result<EventProof> {
+    let block: ethers::types::Block<ethers::types::H256> =
+        serde_json::from_str(&fs::read_to_string(&args.block)?)?;
+    let block_hash = H256(
+        block
+            .hash
+            .ok_or_else(|| eyre::eyre!("block has no hash"))?
+            .0,
+    );
+    let block = convert_ethers_block(block)?;
+
+    let receipts: Vec<ethers::types::TransactionReceipt> =
+        serde_json::from_str(&fs::read_to_string(&args.receipts)?)?;
+    let receipts = receipts
+        .into_iter()
+        .map(convert_ethers_receipt)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // Proofs built offline are printed whatever their size
+    build_receipt_proof(block_hash, &block, &receipts, args.index, None)
+}
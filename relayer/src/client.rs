@@ -1,23 +1,31 @@
 use std::{
+    collections::{HashMap, VecDeque},
+    future::Future,
+    ops::RangeInclusive,
     sync::{atomic::AtomicBool, Arc},
     time::Duration,
 };
 
-use ethers::providers::{Http, Provider};
-use ethers::{providers::Middleware, types::U64};
+use ethers::providers::{Http, Middleware, Provider};
 use eyre::Result;
+use futures::{StreamExt, TryStreamExt};
 use helios::{
     client::{Client as HeliosClient, ClientBuilder, FileDB},
-    types::{Block, BlockTag},
+    types::BlockTag,
 };
-use types::{BlockHeaderWithTransaction, H160, H256};
+use rand::Rng;
+use types::{BlockHeaderWithTransaction, Bloom, H160, H256};
 
 use crate::{
     common::*,
     config::Config,
-    consts::{BLOCK_AMOUNT_TO_STORE, SLEEP_DURATION},
+    consts::{
+        BLOCK_AMOUNT_TO_STORE, DEFAULT_BLOCK_CACHE_SIZE, DEFAULT_PARALLEL_FETCH_THRESHOLD,
+        FETCH_CHUNK_SIZE, PARALLEL_FETCH_CONCURRENCY, RETRY_BASE_DELAY, RETRY_BUDGET,
+        RETRY_MAX_DELAY, SLEEP_DURATION,
+    },
     db::DB,
-    network_name_to_id,
+    network::EthNetwork,
     substrate_client::SubstrateClient,
 };
 
@@ -30,6 +38,23 @@ pub struct Client {
     chain_id: u32,
     // TODO: rotate blocks in the database
     blocks_to_store: u64,
+    // First block to fetch into an empty database
+    start_block: Option<u64>,
+    // Extra blocks to wait for on top of finality
+    confirmation_depth: u64,
+    // Gaps larger than this are fetched by number instead of walking parent hashes
+    parallel_fetch_threshold: u64,
+    // Topics required in the block bloom for the address
+    watched_topics: Vec<(H160, H256)>,
+    // Blocks fetched by hash while walking back parent hashes
+    block_cache: BlockCache,
+    // Whether an interrupted fetch by number may have left a gap in the database
+    check_gaps: bool,
+    // Lowest block to store, the start block or the first block of a fetch by number
+    fetch_floor: Option<u64>,
+    backoff: Backoff,
+    // Fetch a single finality update instead of polling
+    once: bool,
 
     // Cache of watched addresses
     watched_addresses: Option<Vec<H160>>,
@@ -42,7 +67,7 @@ impl Client {
         term: Arc<AtomicBool>,
         substrate_client: SubstrateClient,
     ) -> Result<Self> {
-        let helios_config = prepare_config(&config);
+        let helios_config = prepare_config(&config)?;
         let block_rpc =
             Provider::<Http>::try_from(&helios_config.execution_rpc).map_err(|err| {
                 eyre::eyre!(
@@ -53,7 +78,12 @@ impl Client {
             })?;
         let client: HeliosClient<FileDB> = ClientBuilder::new()
             .config(helios_config)
-            .data_dir(config.database.join("helios"))
+            .data_dir(
+                config
+                    .helios_data_dir
+                    .clone()
+                    .unwrap_or_else(|| config.database.join("helios")),
+            )
             .build()?;
         Ok(Client {
             client,
@@ -61,131 +91,227 @@ impl Client {
             db,
             term,
             substrate_client,
-            chain_id: network_name_to_id(&config.network)?,
+            chain_id: EthNetwork::try_from(config.network.as_str())?.chain_id,
             blocks_to_store: config.blocks_to_store.unwrap_or(BLOCK_AMOUNT_TO_STORE),
+            start_block: config.start_block,
+            confirmation_depth: config.confirmation_depth.unwrap_or_default(),
+            parallel_fetch_threshold: config
+                .parallel_fetch_threshold
+                .unwrap_or(DEFAULT_PARALLEL_FETCH_THRESHOLD),
+            watched_topics: config.watched_topics,
+            block_cache: BlockCache::new(
+                config.block_cache_size.unwrap_or(DEFAULT_BLOCK_CACHE_SIZE),
+            ),
+            // A previous run may have been stopped while fetching by number
+            check_gaps: true,
+            fetch_floor: config.start_block,
+            backoff: Backoff {
+                base: config
+                    .retry_base_delay_ms
+                    .map_or(RETRY_BASE_DELAY, Duration::from_millis),
+                cap: config
+                    .retry_max_delay_ms
+                    .map_or(RETRY_MAX_DELAY, Duration::from_millis),
+                budget: RETRY_BUDGET,
+            },
+            once: config.once,
             watched_addresses: None,
         })
     }
 
     pub async fn start(&mut self) -> Result<()> {
-        exit_if_term(self.term.clone());
+        if is_terminated(&self.term) {
+            return Ok(());
+        }
         log::info!(target: "relayer::client::start","starting client");
         self.client.start().await?;
         log::info!(target: "relayer::client::start","client started");
 
+        let finalized_block = self
+            .block_rpc
+            .get_block(ethers::types::BlockNumber::Finalized)
+            .await?
+            .ok_or_else(|| eyre::eyre!("Finalized block not found"))?;
+        check_header_encoding(finalized_block)?;
+        log::info!(target: "relayer::client::start","header encoding self-test passed");
+
         self.finalization_loop().await?;
 
         Ok(())
     }
 
     /// Tries to get finalized block from Helios and start fetching if any updates are available.
+    /// In `--once` mode a single attempt is made and its error is returned.
     async fn finalization_loop(&mut self) -> Result<()> {
         const TARGET: &str = "relayer::client::finalization_loop";
 
         let mut latest_fetched_block = self.db.select_latest_fetched_block_height()?;
         loop {
-            exit_if_term(self.term.clone());
-            tokio::time::sleep(SLEEP_DURATION).await;
-            let finalized_block = self
-                .client
-                .get_block_by_number(BlockTag::Finalized, false)
-                .await;
-            let finalized_block = if let Ok(Some(finalized_block)) = finalized_block {
-                finalized_block
-            } else {
-                log::warn!(target: TARGET,"Failed to get finalized block, retrying in {} seconds", SLEEP_DURATION.as_secs());
-                continue;
-            };
-
-            if Some(finalized_block.number.as_u64()) == latest_fetched_block {
-                log::info!(target: TARGET,"No new finalized blocks, retrying in {} seconds", SLEEP_DURATION.as_secs());
-                continue;
+            if is_terminated(&self.term) {
+                return Ok(());
             }
-            log::info!(target: TARGET,"New blocks to fetch. Latest finalized: {}, Latest processed: {latest_fetched_block:?}", finalized_block.number );
-
-            // We have received finality update. It happens not that often, let's check watched addresses.
-            if let Ok(watched_addresses) =
-                self.substrate_client.watched_addresses(self.chain_id).await
-            {
-                // Update cache only if we have successfully fetched
-                // TODO: ideally after we noticed that we have new addresses, we should check for blocks stored in db to verify that we didn't miss some txs
-                self.watched_addresses = Some(watched_addresses);
+            if !self.once {
+                tokio::time::sleep(SLEEP_DURATION).await;
             }
-
-            // If we could never get watched addresses, there is no point in fetching blocks.
-            if self.watched_addresses.is_none() {
-                log::warn!(target: TARGET,"Failed to get watched addresses, retrying in {} seconds", SLEEP_DURATION.as_secs());
-                continue;
+            let update = self.fetch_finality_update(latest_fetched_block).await;
+            if self.once {
+                return update;
+            }
+            if let Err(e) = update {
+                log::warn!(target: TARGET,"{e}, retrying in {} seconds", SLEEP_DURATION.as_secs());
             }
-
-            if let Err(e) = self
-                .collect_blocks_after_finality_update(finalized_block, latest_fetched_block)
-                .await
-            {
-                log::error!(target: TARGET,"Failed to process finality update: {}", e);
-            } else {
-                log::info!(target: TARGET,"Processed finality update");
-            };
 
             // Update latest fetched block after fetching. This is needed to avoid querying db on every iteration.
             latest_fetched_block = self.db.select_latest_fetched_block_height()?;
         }
     }
 
+    /// Fetches the blocks after `latest_fetched_block` up to the highest block that may be relayed.
+    async fn fetch_finality_update(&mut self, latest_fetched_block: Option<u64>) -> Result<()> {
+        const TARGET: &str = "relayer::client::fetch_finality_update";
+
+        let finalized_block = match self
+            .client
+            .get_block_by_number(BlockTag::Finalized, false)
+            .await
+        {
+            Ok(Some(finalized_block)) => finalized_block,
+            _ => return Err(eyre::eyre!("Failed to get finalized block")),
+        };
+
+        let finalized_number = finalized_block.number.as_u64();
+        // Proofs of blocks the light client on GGX hasn't finalized yet would be rejected
+        let light_client_height = self
+            .substrate_client
+            .last_known_block_block_number(self.chain_id)
+            .await
+            .map_err(|e| eyre::eyre!("Failed to get the light client finalized height: {e}"))?;
+        let ceiling = relay_ceiling(
+            finalized_number,
+            self.confirmation_depth,
+            light_client_height,
+        );
+        if latest_fetched_block.is_some_and(|latest| latest >= ceiling) {
+            log::info!(target: TARGET,"No new finalized blocks");
+            return Ok(());
+        }
+        log::info!(target: TARGET,"New blocks to fetch. Latest finalized: {finalized_number}, light client: {light_client_height}, relaying up to: {ceiling}, Latest processed: {latest_fetched_block:?}");
+
+        // We have received finality update. It happens not that often, let's check watched addresses.
+        if let Ok(watched_addresses) = self.substrate_client.watched_addresses(self.chain_id).await
+        {
+            // Update cache only if we have successfully fetched
+            // TODO: ideally after we noticed that we have new addresses, we should check for blocks stored in db to verify that we didn't miss some txs
+            self.watched_addresses = Some(watched_addresses);
+        }
+
+        // If we could never get watched addresses, there is no point in fetching blocks.
+        if self.watched_addresses.is_none() {
+            return Err(eyre::eyre!("Failed to get watched addresses"));
+        }
+
+        if let Err(e) = self
+            .collect_blocks_after_finality_update(
+                finalized_number,
+                H256(finalized_block.hash.0),
+                ceiling,
+                latest_fetched_block,
+            )
+            .await
+        {
+            log::error!(target: TARGET,"Failed to process finality update: {}", e);
+            return Err(e);
+        }
+        log::info!(target: TARGET,"Processed finality update");
+        Ok(())
+    }
+
     /// Fetches all blocks from the web3 provider. The fetching goes backwards from the latest finalized block
-    /// to the latest processed block using parent hash.
+    /// to the latest processed block using parent hash, only blocks up to `ceiling` are stored. Large
+    /// gaps are fetched by number concurrently in chunks, see [`Client::fetch_blocks_in_chunks`].
     async fn collect_blocks_after_finality_update(
         &mut self,
-        finalized_block: Block,
+        finalized_number: u64,
+        finalized_hash: H256,
+        ceiling: u64,
         latest_fetched_block: Option<u64>,
     ) -> Result<()> {
         const TARGET: &str = "relayer::client::collect_blocks_after_finality_update";
 
         log::info!(target: TARGET,"Processing finality update");
-        let latest_fetched_block =
-            latest_fetched_block.unwrap_or(finalized_block.number.as_u64() - self.blocks_to_store);
+        if self.check_gaps {
+            while let Some(gap) = self.db.select_first_gap(self.fetch_floor)? {
+                log::info!(target: TARGET,"Filling the gap of blocks {}..={}", gap.start(), gap.end());
+                let above = *gap.end() + 1;
+                let (_, block_above) = self
+                    .db
+                    .select_block_by_height(above)?
+                    .ok_or_else(|| eyre::eyre!("Block {above} not found"))?;
+                self.fetch_blocks_in_chunks(gap, block_above.header.parent_hash)
+                    .await?;
+            }
+            self.check_gaps = false;
+        }
+
+        // Helios only serves the blocks of recent head updates, so the ceiling is reached by walking
+        // parent hashes down from the finalized block
+        let block_rpc = &self.block_rpc;
+        let head_hash = hash_at_height(
+            &mut self.block_cache,
+            finalized_number,
+            finalized_hash,
+            ceiling,
+            |hash| get_block_by_hash(block_rpc, hash),
+        )
+        .await?;
 
-        log::info!(target: TARGET,"Latest fetched block: {}", latest_fetched_block);
+        let blocks = blocks_to_fetch(
+            ceiling,
+            latest_fetched_block,
+            self.start_block,
+            self.blocks_to_store,
+        )?;
+        let first_block = *blocks.start();
+
+        log::info!(target: TARGET,"First block to fetch: {}", first_block);
+
+        if use_parallel_fetch(&blocks, self.parallel_fetch_threshold) {
+            log::info!(target: TARGET,"Fetching {} blocks by number", blocks.clone().count());
+            return self.fetch_blocks_in_chunks(blocks, head_hash).await;
+        }
 
         // Now we have fetch missing blocks using previous block hash until we hit latest processed block.
-        // If it's first run, we have to backtrack for self.block_to_fetch blocks.
-        let mut blocks_to_process =
-            Vec::with_capacity((finalized_block.number.as_u64() - latest_fetched_block) as usize);
+        // If it's first run, we have to backtrack to the start block or for self.blocks_to_store blocks.
+        let mut blocks_to_process = Vec::with_capacity(blocks.clone().count());
 
-        let mut current_block = finalized_block.number - 1;
-        let mut prev_block_hash = finalized_block.parent_hash;
+        // `None` once the genesis block has been fetched
+        let mut current_block = ceiling.checked_sub(1);
         let block = self
-            .block_rpc
-            .get_block(finalized_block.hash)
-            .await?
-            .ok_or_else(|| eyre::eyre!("Block not found"))?;
-        // push first finalized block to the queue
-        blocks_to_process.push((convert_ethers_block(block)?, H256(finalized_block.hash.0)));
+            .fetch_block_by_hash(ethers::types::H256(head_hash.0))
+            .await?;
+        let mut prev_block_hash = ethers::types::H256(block.header.parent_hash.0);
+        // push the block at the ceiling to the queue
+        blocks_to_process.push((block, head_hash));
 
         let mut repeat = 0;
 
-        while current_block.as_u64() != latest_fetched_block {
+        while let Some(number) = current_block.filter(|number| *number >= first_block) {
             // Fetch block by parent hash using web3 interface
-            let execution_block = self.block_rpc.get_block(prev_block_hash).await;
-            let execution_block = if let Ok(Some(execution_block)) = execution_block {
-                execution_block
-            } else {
-                log::warn!(target: TARGET, "Failed to get block by hash.\nBlock number: {current_block}");
-                repeat = repeat_cycle(repeat).await?;
-                continue;
-            };
-            let tmp = execution_block.parent_hash;
-            // parse block to our format
-            if let Ok(parsed_block) = convert_ethers_block(execution_block) {
-                // store requested hash to verify later
-                blocks_to_process.push((parsed_block, H256(prev_block_hash.0)));
-                current_block = current_block.saturating_sub(U64::one());
-                prev_block_hash = tmp;
-                // reset repeat as we had a success.
-                repeat = 0;
-            } else {
-                log::warn!(target: TARGET, "Failed to parse block.\nBlock number: {current_block}");
-                repeat = repeat_cycle(repeat).await?;
+            match self.fetch_block_by_hash(prev_block_hash).await {
+                Ok(parsed_block) => {
+                    let parent_hash = ethers::types::H256(parsed_block.header.parent_hash.0);
+                    // store requested hash to verify later
+                    blocks_to_process.push((parsed_block, H256(prev_block_hash.0)));
+                    current_block = number.checked_sub(1);
+                    prev_block_hash = parent_hash;
+                    // reset repeat as we had a success.
+                    repeat = 0;
+                }
+                Err(e) => {
+                    // The parent hash chain can't skip a block, so refetch it.
+                    log::warn!(target: TARGET, "Refetching block {number}: {e}");
+                    repeat = self.backoff.repeat_cycle(repeat).await?;
+                }
             }
         }
         self.process_fetched_blocks(blocks_to_process)?;
@@ -193,10 +319,66 @@ impl Client {
         Ok(())
     }
 
+    /// Fetches `blocks` by number down from `head_hash`, the hash of the last block, and stores
+    /// every chunk of [`FETCH_CHUNK_SIZE`] blocks once it is verified. An error leaves the stored
+    /// chunks in place, the gap below them is filled before the next finality update.
+    async fn fetch_blocks_in_chunks(
+        &mut self,
+        blocks: RangeInclusive<u64>,
+        head_hash: H256,
+    ) -> Result<()> {
+        self.check_gaps = true;
+        self.fetch_floor = Some(
+            self.fetch_floor
+                .map_or(*blocks.start(), |floor| floor.min(*blocks.start())),
+        );
+        fetch_in_chunks(
+            blocks,
+            head_hash,
+            FETCH_CHUNK_SIZE,
+            PARALLEL_FETCH_CONCURRENCY,
+            |number| self.fetch_block_by_number(number),
+            |chunk| self.process_fetched_blocks(chunk),
+        )
+        .await?;
+        self.check_gaps = false;
+        Ok(())
+    }
+
+    /// Fetches the block with `hash`, unless it is still cached from an earlier attempt.
+    async fn fetch_block_by_hash(
+        &mut self,
+        hash: ethers::types::H256,
+    ) -> Result<BlockHeaderWithTransaction> {
+        let block_rpc = &self.block_rpc;
+        self.block_cache
+            .get_or_fetch(H256(hash.0), || get_block_by_hash(block_rpc, H256(hash.0)))
+            .await
+    }
+
+    /// Fetches a single block by number, retrying with backoff.
+    async fn fetch_block_by_number(&self, number: u64) -> Result<BlockHeaderWithTransaction> {
+        const TARGET: &str = "relayer::client::fetch_block_by_number";
+
+        let mut repeat = 0;
+        loop {
+            match self.block_rpc.get_block(number).await {
+                Ok(Some(block)) => match convert_ethers_block(block) {
+                    Ok(block) => return Ok(block),
+                    Err(e) => log::warn!(target: TARGET, "Refetching block {number}: {e}"),
+                },
+                Ok(None) => log::warn!(target: TARGET, "Block {number} not found"),
+                Err(e) => log::warn!(target: TARGET, "Failed to get block {number}: {e}"),
+            }
+            repeat = self.backoff.repeat_cycle(repeat).await?;
+        }
+    }
+
     /// Process fetched blocks, check the block hash, bloom filter and store records in the database.
-    /// The blocks are processed from the latest processed block + 1 to the latest block.
+    /// The blocks are processed from the lowest one, which has to follow the stored block below it
+    /// if there is one, to the latest block.
     fn process_fetched_blocks(
-        &mut self,
+        &self,
         blocks: Vec<(BlockHeaderWithTransaction, H256)>,
     ) -> Result<()> {
         const TARGET: &str = "relayer::client::process_fetched_blocks";
@@ -210,11 +392,14 @@ impl Client {
             return Ok(());
         }
 
-        // Load latest processed block hash from the database.
-        let mut processed_block_hash = self
-            .db
-            .select_latest_fetched_block_hash()?
-            .unwrap_or_else(|| blocks.last().unwrap().0.header.parent_hash);
+        // Load the hash of the stored block below the lowest one from the database.
+        let lowest = &blocks.last().unwrap().0.header;
+        let below = match lowest.number.checked_sub(1) {
+            Some(number) => self.db.select_block_by_height(number)?,
+            None => None,
+        };
+        let mut processed_block_hash = below.map_or(lowest.parent_hash, |(hash, _)| hash);
+        let mut rlp = Vec::new();
         for (block, block_hash) in blocks.into_iter().rev() {
             // First initial check that it's in order. And that the parent block hash is expected.
             if processed_block_hash != block.header.parent_hash {
@@ -223,7 +408,7 @@ impl Client {
             }
 
             // Verify block hash correctness
-            let hash = H256::hash(&block.header);
+            let hash = H256::hash_with_buffer(&block.header, &mut rlp);
             if hash != block_hash {
                 log::error!(target: TARGET,"Block hash mismatch");
                 return Err(eyre::eyre!("Block hash mismatch"));
@@ -232,9 +417,11 @@ impl Client {
             let block_number = block.header.number;
 
             // Check the bloom filter over expected contracts
-            let should_process = watched_addresses
-                .iter()
-                .any(|address| block.header.logs_bloom.check_address(address));
+            let should_process = should_process(
+                &block.header.logs_bloom,
+                watched_addresses,
+                &self.watched_topics,
+            );
 
             // Store block in the database
             self.db
@@ -246,14 +433,691 @@ impl Client {
     }
 }
 
-async fn repeat_cycle(repeat_counter: u64) -> Result<u64> {
-    const RETRIES: u64 = 10;
-    if repeat_counter < RETRIES {
-        log::warn!(target: "relayer::client::repeat_cycle","Sleeping for 5 seconds");
-        tokio::time::sleep(Duration::from_secs(5)).await;
+/// Whether the bloom may contain an event of a watched contract. For addresses with configured
+/// topics, at least one of the topics has to be in the bloom as well.
+fn should_process(
+    bloom: &Bloom,
+    watched_addresses: &[H160],
+    watched_topics: &[(H160, H256)],
+) -> bool {
+    watched_addresses.iter().any(|address| {
+        if !bloom.check_address(address) {
+            return false;
+        }
+        let mut topics = watched_topics
+            .iter()
+            .filter(|(topic_address, _)| topic_address.ct_eq(address))
+            .map(|(_, topic)| topic)
+            .peekable();
+        topics.peek().is_none() || topics.any(|topic| bloom.check_topic(topic))
+    })
+}
+
+/// Blocks to fetch when `head` is the highest block to relay: the ones after the latest fetched
+/// block or, if nothing was fetched yet, from `start_block` or the last `blocks_to_store` blocks.
+fn blocks_to_fetch(
+    head: u64,
+    latest_fetched: Option<u64>,
+    start_block: Option<u64>,
+    blocks_to_store: u64,
+) -> Result<RangeInclusive<u64>> {
+    let first = match (latest_fetched, start_block) {
+        (Some(latest), _) => latest + 1,
+        (None, Some(start)) if start > head => {
+            return Err(eyre::eyre!(
+                "Start block {start} is above the finalized head {head}"
+            ))
+        }
+        (None, Some(start)) => start,
+        // Down to genesis on a chain shorter than `blocks_to_store`
+        (None, None) => (head + 1).saturating_sub(blocks_to_store),
+    };
+    Ok(first..=head)
+}
+
+/// Whether `blocks` are too many to walk back one parent hash at a time.
+fn use_parallel_fetch(blocks: &RangeInclusive<u64>, threshold: u64) -> bool {
+    blocks.end().saturating_sub(*blocks.start()) >= threshold
+}
+
+/// Fetches the block with `hash` from the execution RPC.
+async fn get_block_by_hash(
+    block_rpc: &Provider<Http>,
+    hash: H256,
+) -> Result<BlockHeaderWithTransaction> {
+    let block = block_rpc
+        .get_block(ethers::types::H256(hash.0))
+        .await?
+        .ok_or_else(|| eyre::eyre!("Block not found"))?;
+    Ok(convert_ethers_block(block)?)
+}
+
+/// The hash of block `height`, found by walking parent hashes down from block `number` with
+/// `hash`. Every block on the way has to hash to its expected hash, so the result is as trusted as
+/// `hash`. The blocks are cached, as the walk is repeated while the light client on GGX lags.
+async fn hash_at_height<F, Fut>(
+    cache: &mut BlockCache,
+    number: u64,
+    hash: H256,
+    height: u64,
+    mut fetch: F,
+) -> Result<H256>
+where
+    F: FnMut(H256) -> Fut,
+    Fut: Future<Output = Result<BlockHeaderWithTransaction>>,
+{
+    let (mut number, mut hash) = (number, hash);
+    let mut rlp = Vec::new();
+    while number > height {
+        let block = cache.get_or_fetch(hash, || fetch(hash)).await?;
+        if H256::hash_with_buffer(&block.header, &mut rlp) != hash {
+            return Err(eyre::eyre!(
+                "Block {number} is not part of the finalized chain"
+            ));
+        }
+        hash = block.header.parent_hash;
+        number -= 1;
+    }
+    Ok(hash)
+}
+
+/// Fetches the blocks of `range` by number, at most `concurrency` at a time, and links them through
+/// their parent hashes down from `head_hash`, the hash of the last block of the range. Returns the
+/// blocks newest first with their expected hashes, like the parent hash walk does.
+async fn fetch_blocks_by_number<F, Fut>(
+    range: RangeInclusive<u64>,
+    head_hash: H256,
+    concurrency: usize,
+    fetch: F,
+) -> Result<Vec<(BlockHeaderWithTransaction, H256)>>
+where
+    F: FnMut(u64) -> Fut,
+    Fut: Future<Output = Result<BlockHeaderWithTransaction>>,
+{
+    let blocks: Vec<_> = futures::stream::iter(range)
+        .map(fetch)
+        .buffered(concurrency)
+        .try_collect()
+        .await?;
+
+    let mut expected_hash = head_hash;
+    let mut linked = Vec::with_capacity(blocks.len());
+    for block in blocks.into_iter().rev() {
+        let parent_hash = block.header.parent_hash;
+        linked.push((block, expected_hash));
+        expected_hash = parent_hash;
+    }
+    verify_chain(&linked)?;
+    Ok(linked)
+}
+
+/// Fetches the blocks of `range` like [`fetch_blocks_by_number`], but at most `chunk_size` at a
+/// time down from `head_hash`. Every chunk is passed to `store`, newest first, before the next
+/// one is fetched, and is linked to the chunk above through the parent hash of its lowest block.
+async fn fetch_in_chunks<F, Fut, S>(
+    range: RangeInclusive<u64>,
+    head_hash: H256,
+    chunk_size: u64,
+    concurrency: usize,
+    mut fetch: F,
+    mut store: S,
+) -> Result<()>
+where
+    F: FnMut(u64) -> Fut,
+    Fut: Future<Output = Result<BlockHeaderWithTransaction>>,
+    S: FnMut(Vec<(BlockHeaderWithTransaction, H256)>) -> Result<()>,
+{
+    let mut expected_hash = head_hash;
+    for chunk in chunks_down(range, chunk_size) {
+        let blocks = fetch_blocks_by_number(chunk, expected_hash, concurrency, &mut fetch).await?;
+        expected_hash = blocks
+            .last()
+            .expect("chunks aren't empty")
+            .0
+            .header
+            .parent_hash;
+        store(blocks)?;
+    }
+    Ok(())
+}
+
+/// `range` split into ranges of at most `size` blocks, the newest first.
+fn chunks_down(range: RangeInclusive<u64>, size: u64) -> impl Iterator<Item = RangeInclusive<u64>> {
+    let (first, last) = range.into_inner();
+    let mut next = (first <= last).then_some(last);
+    std::iter::from_fn(move || {
+        let end = next?;
+        let start = end.saturating_sub(size.max(1) - 1).max(first);
+        next = start.checked_sub(1).filter(|number| *number >= first);
+        Some(start..=end)
+    })
+}
+
+/// Checks that every block hashes to its expected hash. For blocks linked by [`fetch_blocks_by_number`]
+/// this means they form a parent hash chain ending at the head hash.
+fn verify_chain(blocks: &[(BlockHeaderWithTransaction, H256)]) -> Result<()> {
+    let mut rlp = Vec::new();
+    for (block, expected_hash) in blocks {
+        if H256::hash_with_buffer(&block.header, &mut rlp) != *expected_hash {
+            return Err(eyre::eyre!(
+                "Block {} is not part of the finalized chain",
+                block.header.number
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// The highest block that may be relayed when `finalized` is the finalized head and the light
+/// client on GGX has finalized up to `light_client_height`.
+fn relay_ceiling(finalized: u64, confirmation_depth: u64, light_client_height: u64) -> u64 {
+    finalized
+        .saturating_sub(confirmation_depth)
+        .min(light_client_height)
+}
+
+/// Recently fetched blocks by hash, at most `capacity` of them. The least recently used block is
+/// evicted first.
+struct BlockCache {
+    capacity: usize,
+    blocks: HashMap<H256, BlockHeaderWithTransaction>,
+    // Hashes of the cached blocks, from the least to the most recently used
+    order: VecDeque<H256>,
+}
+
+impl BlockCache {
+    fn new(capacity: usize) -> Self {
+        BlockCache {
+            capacity,
+            blocks: HashMap::with_capacity(capacity),
+            order: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Returns the block with `hash`, calling `fetch` only if it isn't cached. Failures aren't
+    /// cached.
+    async fn get_or_fetch<F, Fut>(
+        &mut self,
+        hash: H256,
+        fetch: F,
+    ) -> Result<BlockHeaderWithTransaction>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<BlockHeaderWithTransaction>>,
+    {
+        if let Some(block) = self.blocks.get(&hash) {
+            let block = block.clone();
+            self.touch(hash);
+            return Ok(block);
+        }
+
+        let block = fetch().await?;
+        if self.capacity > 0 {
+            if self.blocks.len() >= self.capacity {
+                if let Some(evicted) = self.order.pop_front() {
+                    self.blocks.remove(&evicted);
+                }
+            }
+            self.blocks.insert(hash, block.clone());
+            self.order.push_back(hash);
+        }
+        Ok(block)
+    }
+
+    /// Marks the block with `hash` as the most recently used.
+    fn touch(&mut self, hash: H256) {
+        if let Some(position) = self.order.iter().position(|cached| *cached == hash) {
+            self.order.remove(position);
+        }
+        self.order.push_back(hash);
+    }
+}
+
+/// Exponential backoff with jitter between retries of RPC requests.
+#[derive(Debug, Clone, Copy)]
+struct Backoff {
+    base: Duration,
+    cap: Duration,
+    // Total time to spend sleeping before giving up
+    budget: Duration,
+}
+
+impl Backoff {
+    /// Relative amount of random noise added to every delay, so relayers don't retry in lockstep.
+    const JITTER: f64 = 0.1;
+
+    /// Delay before retry number `attempt`, without jitter.
+    fn nominal_delay(&self, attempt: u64) -> Duration {
+        let factor = 1u32.checked_shl(attempt as u32).unwrap_or(u32::MAX);
+        self.base.saturating_mul(factor).min(self.cap)
+    }
+
+    fn delay(&self, attempt: u64) -> Duration {
+        let jitter = rand::thread_rng().gen_range(-Self::JITTER, Self::JITTER);
+        self.nominal_delay(attempt).mul_f64(1.0 + jitter)
+    }
+
+    /// Sleeps before the next retry and returns the updated counter, or fails once the budget is
+    /// spent.
+    async fn repeat_cycle(&self, repeat_counter: u64) -> Result<u64> {
+        const TARGET: &str = "relayer::client::repeat_cycle";
+
+        let spent: Duration = (0..=repeat_counter).map(|a| self.nominal_delay(a)).sum();
+        if spent > self.budget {
+            log::error!(target: TARGET, "Multiple retries happened. Exiting.");
+            return Err(eyre::eyre!("Multiple retries happened"));
+        }
+
+        let delay = self.delay(repeat_counter);
+        log::warn!(target: TARGET, "Sleeping for {} ms", delay.as_millis());
+        tokio::time::sleep(delay).await;
         Ok(repeat_counter + 1)
-    } else {
-        log::error!(target: "relayer::client::repeat_cycle","Multiple retries happened. Exiting.");
-        Err(eyre::eyre!("Multiple retries happened"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::BloomInput;
+    use types::{BlockHeaderWithTransaction, Bloom, H160, H256};
+
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use super::{
+        blocks_to_fetch, chunks_down, fetch_blocks_by_number, fetch_in_chunks, hash_at_height,
+        relay_ceiling, should_process, use_parallel_fetch, Backoff, BlockCache,
+    };
+    use crate::build_proof::build_proof;
+    use crate::config::BuildProofArgs;
+    use crate::consts::DEFAULT_PARALLEL_FETCH_THRESHOLD;
+
+    fn bloom(inputs: &[&[u8]]) -> Bloom {
+        let mut bloom = ethers::types::Bloom::default();
+        for input in inputs {
+            bloom.accrue(BloomInput::Raw(input));
+        }
+        Bloom::new(bloom.0)
+    }
+
+    #[test]
+    fn should_process_requires_watched_topic() {
+        let address = H160([1; 20]);
+        let other_address = H160([2; 20]);
+        let transfer = H256([3; 32]);
+        let approval = H256([4; 32]);
+        let block_bloom = bloom(&[&address.0, &approval.0]);
+
+        // Address only
+        assert!(should_process(&block_bloom, &[address], &[]));
+        assert!(!should_process(&block_bloom, &[other_address], &[]));
+
+        // Address positive, but topic negative
+        assert!(!should_process(
+            &block_bloom,
+            &[address],
+            &[(address, transfer)]
+        ));
+        assert!(should_process(
+            &block_bloom,
+            &[address],
+            &[(address, transfer), (address, approval)]
+        ));
+        // Topics of other addresses don't matter
+        assert!(should_process(
+            &block_bloom,
+            &[address],
+            &[(other_address, transfer)]
+        ));
+    }
+
+    #[test]
+    fn relay_ceiling_respects_confirmation_depth() {
+        assert_eq!(relay_ceiling(100, 0, u64::MAX), 100);
+        assert_eq!(relay_ceiling(100, 5, u64::MAX), 95);
+        assert_eq!(relay_ceiling(3, 5, u64::MAX), 0);
+    }
+
+    #[test]
+    fn lagging_light_client_defers_blocks() {
+        // Helios finalized 100, the light client on GGX only 90
+        let ceiling = relay_ceiling(100, 0, 90);
+        assert_eq!(ceiling, 90);
+        assert_eq!(
+            blocks_to_fetch(ceiling, Some(80), None, 10).unwrap(),
+            81..=90
+        );
+        // Blocks 91..=100 are fetched once the light client catches up
+        assert_eq!(
+            blocks_to_fetch(relay_ceiling(100, 0, 100), Some(90), None, 10).unwrap(),
+            91..=100
+        );
+        // The confirmation depth still applies when the light client is ahead
+        assert_eq!(relay_ceiling(100, 5, 98), 95);
+    }
+
+    #[tokio::test]
+    async fn lagging_light_client_ceiling_is_found_below_finalized() {
+        // Helios finalized block 109, the light client on GGX only 104
+        let (blocks, finalized_hash) = chain(100, 10);
+        let fetched = AtomicUsize::new(0);
+        let fetch = |hash: H256| {
+            let block = blocks
+                .iter()
+                .find(|block| block.header.hash() == hash)
+                .cloned();
+            let fetched = &fetched;
+            async move {
+                fetched.fetch_add(1, Ordering::SeqCst);
+                block.ok_or_else(|| eyre::eyre!("Block not found"))
+            }
+        };
+
+        let mut cache = BlockCache::new(16);
+        let ceiling = relay_ceiling(109, 0, 104);
+        let hash = hash_at_height(&mut cache, 109, finalized_hash, ceiling, fetch)
+            .await
+            .unwrap();
+        assert_eq!(hash, blocks[4].header.hash());
+        // Only blocks 105..=109 are fetched, on the way down
+        assert_eq!(fetched.load(Ordering::SeqCst), 5);
+
+        // The next finality update walks down through the cache
+        let hash = hash_at_height(&mut cache, 109, finalized_hash, ceiling, fetch)
+            .await
+            .unwrap();
+        assert_eq!(hash, blocks[4].header.hash());
+        assert_eq!(fetched.load(Ordering::SeqCst), 5);
+
+        // Nothing is fetched once the light client caught up
+        let hash = hash_at_height(&mut cache, 109, finalized_hash, 109, fetch)
+            .await
+            .unwrap();
+        assert_eq!(hash, finalized_hash);
+
+        // A block of another fork on the way down is rejected
+        let fork = |hash: H256| {
+            let mut block = blocks
+                .iter()
+                .find(|block| block.header.hash() == hash)
+                .cloned()
+                .unwrap();
+            if block.header.number == 107 {
+                block.header.gas_used += 1;
+            }
+            async move { Ok::<_, eyre::Report>(block) }
+        };
+        let mut cache = BlockCache::new(0);
+        assert!(
+            hash_at_height(&mut cache, 109, finalized_hash, ceiling, fork)
+                .await
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn first_fetch_starts_at_start_block() {
+        assert_eq!(
+            blocks_to_fetch(1000, None, Some(100), 10).unwrap(),
+            100..=1000
+        );
+        assert_eq!(
+            blocks_to_fetch(1000, None, Some(1000), 10).unwrap(),
+            1000..=1000
+        );
+        assert!(blocks_to_fetch(1000, None, Some(1001), 10).is_err());
+        // Without a start block only the last `blocks_to_store` blocks are fetched
+        assert_eq!(blocks_to_fetch(1000, None, None, 10).unwrap(), 991..=1000);
+        assert_eq!(blocks_to_fetch(5, None, None, 10).unwrap(), 0..=5);
+        assert_eq!(blocks_to_fetch(9, None, None, 10).unwrap(), 0..=9);
+        assert_eq!(blocks_to_fetch(10, None, None, 10).unwrap(), 1..=10);
+        assert_eq!(blocks_to_fetch(0, None, None, 10).unwrap(), 0..=0);
+        // The start block is ignored once the database has blocks
+        assert_eq!(
+            blocks_to_fetch(1000, Some(900), Some(100), 10).unwrap(),
+            901..=1000
+        );
+    }
+
+    #[test]
+    fn backoff_grows_up_to_cap() {
+        let backoff = Backoff {
+            base: Duration::from_secs(1),
+            cap: Duration::from_secs(30),
+            budget: Duration::from_secs(120),
+        };
+        let delays: Vec<_> = (0..8).map(|a| backoff.nominal_delay(a)).collect();
+        assert_eq!(delays[..5], [1, 2, 4, 8, 16].map(Duration::from_secs));
+        assert!(delays.windows(2).all(|w| w[0] <= w[1]));
+        assert_eq!(delays[7], backoff.cap);
+        assert_eq!(backoff.nominal_delay(u64::MAX), backoff.cap);
+
+        for attempt in 0..8 {
+            let nominal = backoff.nominal_delay(attempt);
+            for _ in 0..100 {
+                let delay = backoff.delay(attempt);
+                assert!(delay >= nominal.mul_f64(1.0 - Backoff::JITTER));
+                assert!(delay <= nominal.mul_f64(1.0 + Backoff::JITTER));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn backoff_fails_after_budget() {
+        let backoff = Backoff {
+            base: Duration::from_millis(1),
+            cap: Duration::from_millis(4),
+            budget: Duration::from_millis(10),
+        };
+        // 1 + 2 + 4 = 7ms fit into the budget, another 4ms don't.
+        let mut repeat = 0;
+        for _ in 0..3 {
+            repeat = backoff.repeat_cycle(repeat).await.unwrap();
+        }
+        assert_eq!(repeat, 3);
+        assert!(backoff.repeat_cycle(repeat).await.is_err());
+    }
+
+    /// A valid chain of `len` blocks following `first` and the hash of its head.
+    fn chain(first: u64, len: u64) -> (Vec<BlockHeaderWithTransaction>, H256) {
+        let suits = PathBuf::from(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../merkle/tests/suits"
+        ));
+        let mut header = build_proof(&BuildProofArgs {
+            block: suits.join("block_17819525.json"),
+            receipts: suits.join("block_17819525_receipts.json"),
+            index: 0,
+        })
+        .unwrap()
+        .block_header;
+
+        let mut blocks = vec![];
+        let mut parent_hash = H256([0; 32]);
+        for number in first..first + len {
+            header.number = number;
+            header.parent_hash = parent_hash;
+            parent_hash = header.hash();
+            blocks.push(BlockHeaderWithTransaction {
+                header: header.clone(),
+                transactions: vec![],
+            });
+        }
+        (blocks, parent_hash)
+    }
+
+    #[tokio::test]
+    async fn large_gap_is_fetched_in_parallel() {
+        let (latest_fetched, head) = (17_000_000, 17_001_000);
+        let range = blocks_to_fetch(head, Some(latest_fetched), None, 100).unwrap();
+        assert!(use_parallel_fetch(&range, DEFAULT_PARALLEL_FETCH_THRESHOLD));
+        assert!(!use_parallel_fetch(
+            &blocks_to_fetch(head, Some(head - 10), None, 100).unwrap(),
+            DEFAULT_PARALLEL_FETCH_THRESHOLD
+        ));
+
+        let (blocks, head_hash) = chain(latest_fetched + 1, 1000);
+        let in_flight = AtomicUsize::new(0);
+        let max_in_flight = AtomicUsize::new(0);
+        let fetch = |number: u64| {
+            let block = blocks[(number - latest_fetched - 1) as usize].clone();
+            let (in_flight, max_in_flight) = (&in_flight, &max_in_flight);
+            async move {
+                let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                max_in_flight.fetch_max(current, Ordering::SeqCst);
+                tokio::task::yield_now().await;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                Ok(block)
+            }
+        };
+
+        let fetched = fetch_blocks_by_number(range.clone(), head_hash, 8, fetch)
+            .await
+            .unwrap();
+        assert_eq!(fetched.len(), 1000);
+        assert_eq!(fetched[0], (blocks[999].clone(), head_hash));
+        assert_eq!(fetched[999].0.header.number, latest_fetched + 1);
+        let max_in_flight = max_in_flight.into_inner();
+        assert!(max_in_flight > 1 && max_in_flight <= 8);
+
+        // A block from another fork breaks the chain
+        let fetch = |number: u64| {
+            let mut block = blocks[(number - latest_fetched - 1) as usize].clone();
+            if number == latest_fetched + 500 {
+                block.header.gas_used += 1;
+            }
+            async move { Ok(block) }
+        };
+        assert!(fetch_blocks_by_number(range, head_hash, 8, fetch)
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn chunks_go_down_from_the_head() {
+        let chunks = |range, size| chunks_down(range, size).collect::<Vec<_>>();
+        assert_eq!(chunks(0..=9, 4), [6..=9, 2..=5, 0..=1]);
+        assert_eq!(chunks(10..=17, 4), [14..=17, 10..=13]);
+        assert_eq!(chunks(5..=5, 4), [5..=5]);
+        #[allow(clippy::reversed_empty_ranges)]
+        let empty = chunks(6..=5, 4);
+        assert!(empty.is_empty());
+    }
+
+    #[tokio::test]
+    async fn stored_chunks_survive_a_failed_fetch() {
+        let first = 17_000_000;
+        let (blocks, head_hash) = chain(first, 1000);
+        let range = first..=first + 999;
+        let fetch = |failing: Option<u64>| {
+            let blocks = &blocks;
+            move |number: u64| {
+                let block = blocks[(number - first) as usize].clone();
+                async move {
+                    if Some(number) == failing {
+                        return Err(eyre::eyre!("Block {number} not found"));
+                    }
+                    Ok(block)
+                }
+            }
+        };
+
+        let mut stored = vec![];
+        fetch_in_chunks(range.clone(), head_hash, 300, 8, fetch(None), |chunk| {
+            stored.push(chunk);
+            Ok(())
+        })
+        .await
+        .unwrap();
+        // Newest chunk first, every chunk newest block first
+        let lowest: Vec<_> = stored
+            .iter()
+            .map(|chunk| chunk.last().unwrap().0.header.number - first)
+            .collect();
+        assert_eq!(lowest, [700, 400, 100, 0]);
+        assert_eq!(stored[0][0], (blocks[999].clone(), head_hash));
+        assert_eq!(stored[1][0].1, blocks[699].header.hash());
+        assert_eq!(stored.concat().len(), 1000);
+
+        // The chunks above the failed block are stored, nothing below it
+        let mut stored = vec![];
+        assert!(fetch_in_chunks(
+            range.clone(),
+            head_hash,
+            300,
+            8,
+            fetch(Some(first + 250)),
+            |chunk| {
+                stored.push(chunk);
+                Ok(())
+            }
+        )
+        .await
+        .is_err());
+        assert_eq!(stored.len(), 2);
+        assert_eq!(stored[1].last().unwrap().0.header.number, first + 400);
+
+        // A lower chunk has to link to the one above it
+        let fork = |number: u64| {
+            let mut block = blocks[(number - first) as usize].clone();
+            if number == first + 699 {
+                block.header.gas_used += 1;
+            }
+            async move { Ok(block) }
+        };
+        let mut stored = 0;
+        assert!(fetch_in_chunks(range, head_hash, 300, 8, fork, |_| {
+            stored += 1;
+            Ok(())
+        })
+        .await
+        .is_err());
+        assert_eq!(stored, 1);
+    }
+
+    #[tokio::test]
+    async fn repeated_blocks_are_served_from_cache() {
+        let (blocks, _) = chain(17_000_000, 3);
+        let hashes: Vec<_> = blocks.iter().map(|block| block.header.hash()).collect();
+        let fetched = AtomicUsize::new(0);
+        let fetch = |index: usize| {
+            let block = blocks[index].clone();
+            let fetched = &fetched;
+            move || async move {
+                fetched.fetch_add(1, Ordering::SeqCst);
+                Ok(block)
+            }
+        };
+
+        let mut cache = BlockCache::new(2);
+        for index in [0, 1, 0, 1] {
+            let block = cache
+                .get_or_fetch(hashes[index], fetch(index))
+                .await
+                .unwrap();
+            assert_eq!(block, blocks[index]);
+        }
+        assert_eq!(fetched.load(Ordering::SeqCst), 2);
+
+        // Failures aren't cached
+        assert!(cache
+            .get_or_fetch(hashes[2], || async { Err(eyre::eyre!("Block not found")) })
+            .await
+            .is_err());
+        assert_eq!(fetched.load(Ordering::SeqCst), 2);
+
+        // Block 0 was used less recently than block 1, so it is evicted for block 2
+        cache.get_or_fetch(hashes[2], fetch(2)).await.unwrap();
+        cache.get_or_fetch(hashes[1], fetch(1)).await.unwrap();
+        assert_eq!(fetched.load(Ordering::SeqCst), 3);
+        cache.get_or_fetch(hashes[0], fetch(0)).await.unwrap();
+        assert_eq!(fetched.load(Ordering::SeqCst), 4);
+
+        // A cache of size 0 fetches every time
+        let mut cache = BlockCache::new(0);
+        for _ in 0..2 {
+            cache.get_or_fetch(hashes[0], fetch(0)).await.unwrap();
+        }
+        assert_eq!(fetched.load(Ordering::SeqCst), 6);
     }
 }
@@ -1,41 +1,145 @@
-use std::{
-    process::exit,
-    sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc,
-    },
-};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use types::{BlockHeaderWithTransaction, Bloom, TransactionReceipt, TxType, H160, H256, U256};
 
 use crate::config::Config;
+use crate::network::EthNetwork;
+
+/// Why a block or receipt from the execution RPC couldn't be converted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConversionError {
+    /// The block lacks a field its header hash depends on. `block` is `None` if the number
+    /// itself is missing.
+    MissingBlockField {
+        block: Option<u64>,
+        field: &'static str,
+    },
+    /// A field of the block that isn't part of the RPC types couldn't be parsed.
+    InvalidBlockField {
+        block: Option<u64>,
+        field: &'static str,
+    },
+    /// The receipt has a transaction type the relayer can't encode.
+    InvalidTxType {
+        block: Option<u64>,
+        transaction_index: u64,
+        tx_type: u64,
+    },
+    /// The receipt has no status (pre-Byzantium, it has a state root instead) or a status other
+    /// than 0 and 1, neither of which the relayer can encode.
+    InvalidStatus {
+        block: Option<u64>,
+        transaction_index: u64,
+        status: Option<u64>,
+    },
+}
+
+impl std::fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConversionError::MissingBlockField {
+                block: Some(block),
+                field,
+            } => write!(f, "Failed to parse block {block}: missing {field}"),
+            ConversionError::MissingBlockField { block: None, field } => {
+                write!(f, "Failed to parse block: missing {field}")
+            }
+            ConversionError::InvalidBlockField {
+                block: Some(block),
+                field,
+            } => write!(f, "Failed to parse block {block}: invalid {field}"),
+            ConversionError::InvalidBlockField { block: None, field } => {
+                write!(f, "Failed to parse block: invalid {field}")
+            }
+            ConversionError::InvalidTxType {
+                block: Some(block),
+                transaction_index,
+                tx_type,
+            } => write!(
+                f,
+                "Failed to parse receipt {transaction_index} of block {block}: invalid tx type {tx_type}"
+            ),
+            ConversionError::InvalidTxType {
+                block: None,
+                transaction_index,
+                tx_type,
+            } => write!(
+                f,
+                "Failed to parse receipt {transaction_index}: invalid tx type {tx_type}"
+            ),
+            ConversionError::InvalidStatus {
+                block,
+                transaction_index,
+                status,
+            } => {
+                write!(f, "Failed to parse receipt {transaction_index}")?;
+                if let Some(block) = block {
+                    write!(f, " of block {block}")?;
+                }
+                match status {
+                    Some(status) => write!(f, ": invalid status {status}"),
+                    None => write!(f, ": missing status"),
+                }
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConversionError {}
 
 pub fn convert_ethers_receipt(
     receipt: ethers::types::TransactionReceipt,
-) -> eyre::Result<TransactionReceipt> {
+) -> Result<TransactionReceipt, ConversionError> {
     let mut bloom = [0u8; 256];
     bloom.copy_from_slice(&receipt.logs_bloom.0);
 
+    let block = receipt.block_number.map(|number| number.as_u64());
+    let transaction_index = receipt.transaction_index.as_u64();
+    let tx_type = receipt.transaction_type.unwrap_or_default().as_u64();
+    // A missing status is not a failure, the receipt can't be encoded at all
+    let status = receipt.status.map(|status| status.as_u64());
+    let success = match status {
+        Some(0) => false,
+        Some(1) => true,
+        _ => {
+            return Err(ConversionError::InvalidStatus {
+                block,
+                transaction_index,
+                status,
+            })
+        }
+    };
     let transaction_receipt = TransactionReceipt {
         bloom: types::Bloom::new(bloom),
         receipt: types::Receipt {
-            tx_type: TxType::from_u64(receipt.transaction_type.unwrap_or_default().as_u64())
-                .ok_or_else(|| eyre::eyre!("invalid tx type"))?,
-            success: receipt.status.map(|e| e.as_u64() == 1).unwrap_or_default(),
+            tx_type: TxType::from_u64(tx_type).ok_or(ConversionError::InvalidTxType {
+                block,
+                transaction_index,
+                tx_type,
+            })?,
+            success,
             cumulative_gas_used: receipt.cumulative_gas_used.as_u64(),
-            logs: receipt
-                .logs
-                .into_iter()
-                .map(convert_ethers_log)
-                .collect::<eyre::Result<Vec<_>>>()?,
+            logs: receipt.logs.into_iter().map(convert_ethers_log).collect(),
         },
     };
 
     Ok(transaction_receipt)
 }
 
-pub fn convert_ethers_log(log: ethers::types::Log) -> eyre::Result<types::Log> {
-    let log = types::Log {
+/// Parses the `result` of an `eth_getBlockReceipts` call, i.e. all receipts of a block, in
+/// transaction order.
+pub fn convert_block_receipts(result: serde_json::Value) -> eyre::Result<Vec<TransactionReceipt>> {
+    let mut receipts: Vec<ethers::types::TransactionReceipt> = serde_json::from_value(result)?;
+    receipts.sort_by_key(|receipt| receipt.transaction_index);
+    Ok(receipts
+        .into_iter()
+        .map(convert_ethers_receipt)
+        .collect::<Result<_, _>>()?)
+}
+
+pub fn convert_ethers_log(log: ethers::types::Log) -> types::Log {
+    types::Log {
         address: H160(log.address.0),
         topics: log
             .topics
@@ -43,41 +147,73 @@ pub fn convert_ethers_log(log: ethers::types::Log) -> eyre::Result<types::Log> {
             .map(|e| H256(e.0))
             .collect::<Vec<_>>(),
         data: log.data.0.to_vec(),
-    };
-
-    Ok(log)
+    }
 }
 
+/// JSON name of the header field of EIP-7685, which the RPC block type doesn't have.
+const REQUESTS_HASH_FIELD: &str = "requestsHash";
+
+/// Converts a block from the execution RPC.
+///
+/// The header is hashed and compared against the light client, so a made up field would only
+/// fail later with a hash mismatch. Hence `author`, `mix_hash`, `number` and `logs_bloom` are
+/// required and the block is rejected (and refetched by the caller) if the RPC omits them. Only
+/// `nonce` falls back to zero: it is always zero since the merge and only finalized blocks are
+/// relayed.
 pub fn convert_ethers_block(
     execution_block: ethers::types::Block<ethers::types::H256>,
-) -> eyre::Result<BlockHeaderWithTransaction> {
+) -> Result<BlockHeaderWithTransaction, ConversionError> {
+    let block = execution_block.number.map(|number| number.as_u64());
+    let missing = |field| ConversionError::MissingBlockField { block, field };
+    // Added by Prague, the RPC types don't know it yet
+    let requests_hash = execution_block
+        .other
+        .get_deserialized::<ethers::types::H256>(REQUESTS_HASH_FIELD)
+        .transpose()
+        .map_err(|_| ConversionError::InvalidBlockField {
+            block,
+            field: "requests_hash",
+        })?;
     let mut bloom = [0u8; 256];
-    let err = || eyre::eyre!("Failed to parse block");
-    bloom.copy_from_slice(&execution_block.logs_bloom.ok_or_else(err)?.0);
+    bloom.copy_from_slice(
+        &execution_block
+            .logs_bloom
+            .ok_or_else(|| missing("logs_bloom"))?
+            .0,
+    );
     let header = types::BlockHeader {
         parent_hash: H256(execution_block.parent_hash.0),
-        beneficiary: H160(execution_block.author.ok_or_else(err)?.0),
+        beneficiary: H160(execution_block.author.ok_or_else(|| missing("author"))?.0),
         state_root: H256(execution_block.state_root.0),
         transactions_root: H256(execution_block.transactions_root.0),
         receipts_root: H256(execution_block.receipts_root.0),
         withdrawals_root: execution_block.withdrawals_root.map(|r| H256(r.0)),
         logs_bloom: Bloom::new(bloom),
-        number: execution_block.number.ok_or_else(err)?.as_u64(),
+        number: block.ok_or_else(|| missing("number"))?,
         gas_limit: execution_block.gas_limit.as_u64(),
         gas_used: execution_block.gas_used.as_u64(),
         timestamp: execution_block.timestamp.as_u64(),
-        mix_hash: H256(execution_block.mix_hash.ok_or_else(err)?.0),
-        base_fee_per_gas: Some(execution_block.base_fee_per_gas.ok_or_else(err)?.as_u64()),
+        mix_hash: H256(
+            execution_block
+                .mix_hash
+                .ok_or_else(|| missing("mix_hash"))?
+                .0,
+        ),
+        base_fee_per_gas: execution_block.base_fee_per_gas.map(|a| a.as_u64()),
         extra_data: execution_block.extra_data.0.to_vec(),
 
         // Defaults
         ommers_hash: H256(execution_block.uncles_hash.0),
         difficulty: U256(execution_block.difficulty.into()),
-        nonce: execution_block.nonce.ok_or_else(err)?.to_low_u64_be(),
+        nonce: execution_block
+            .nonce
+            .map(|nonce| nonce.to_low_u64_be())
+            .unwrap_or_default(),
 
         blob_gas_used: execution_block.blob_gas_used.map(|a| a.as_u64()),
         excess_blob_gas: execution_block.excess_blob_gas.map(|a| a.as_u64()),
         parent_beacon_block_root: execution_block.parent_beacon_block_root.map(|a| H256(a.0)),
+        requests_hash: requests_hash.map(|a| H256(a.0)),
     };
 
     Ok(BlockHeaderWithTransaction {
@@ -90,19 +226,366 @@ pub fn convert_ethers_block(
     })
 }
 
-pub fn prepare_config(config: &Config) -> helios::config::Config {
-    let helios_config: helios::config::Config = helios::config::Config::from_file(
-        &config.helios_config_path,
-        &config.network,
+/// Startup self-test: checks that `block` from the execution RPC converts to a header that hashes
+/// to the hash the node reports. If it doesn't, the relayer can't encode the headers of this chain
+/// (e.g. a hardfork added a field) and every fetched block would fail the hash check.
+pub fn check_header_encoding(block: ethers::types::Block<ethers::types::H256>) -> eyre::Result<()> {
+    let expected = block
+        .hash
+        .ok_or_else(|| eyre::eyre!("Header encoding self-test: the block has no hash"))?;
+    // Fields the conversion doesn't know of, the likely culprits of a mismatch
+    let unknown_fields = block
+        .other
+        .keys()
+        .filter(|field| *field != REQUESTS_HASH_FIELD)
+        .cloned()
+        .collect::<Vec<_>>();
+    let header = convert_ethers_block(block)
+        .map_err(|e| eyre::eyre!("Header encoding self-test: {e}"))?
+        .header;
+
+    let hash = header.hash();
+    if hash.0 != expected.0 {
+        eyre::bail!(
+            "Header encoding self-test: block {} hashes to {:?}, but the node reports {:?}. \
+             The relayer can't encode the headers of this chain (unknown header fields: {:?})",
+            header.number,
+            ethers::types::H256(hash.0),
+            expected,
+            unknown_fields
+        );
+    }
+    Ok(())
+}
+
+/// Loads the helios config of the network. An explicit `checkpoint` replaces the one from the
+/// file and turns off the external checkpoint fallback, so a fresh sync only trusts the operator.
+pub fn prepare_config(config: &Config) -> eyre::Result<helios::config::Config> {
+    let mut helios_config = load_helios_config(&config.network, &config.helios_config_path)?;
+    if let Some(checkpoint) = &config.checkpoint {
+        helios_config.checkpoint = Some(parse_checkpoint(checkpoint)?);
+        helios_config.load_external_fallback = false;
+    }
+
+    Ok(helios_config)
+}
+
+/// Reads the section of the `network` named by `--network` from the helios config at `path`.
+pub fn load_helios_config(network: &str, path: &Path) -> eyre::Result<helios::config::Config> {
+    let network = EthNetwork::try_from(network)?;
+    Ok(helios::config::Config::from_file(
+        &path.to_path_buf(),
+        &network.helios.to_string(),
         &Default::default(),
-    );
+    ))
+}
+
+fn parse_checkpoint(checkpoint: &str) -> eyre::Result<Vec<u8>> {
+    let bytes = hex::decode(checkpoint.trim_start_matches("0x"))
+        .map_err(|err| eyre::eyre!("invalid checkpoint {checkpoint}: {err}"))?;
+    if bytes.len() != 32 {
+        eyre::bail!("invalid checkpoint {checkpoint}: expected 32 bytes");
+    }
+    Ok(bytes)
+}
 
-    helios_config
+/// Returns `true` once shutdown has been requested. Loops check it before starting a new
+/// iteration and return, so that an iteration is never cut off halfway.
+pub fn is_terminated(term: &AtomicBool) -> bool {
+    let terminated = term.load(Ordering::Relaxed);
+    if terminated {
+        log::info!(target: "relayer::is_terminated", "shutdown requested");
+    }
+    terminated
 }
 
-pub fn exit_if_term(term: Arc<AtomicBool>) {
-    if term.load(Ordering::Relaxed) {
-        log::info!(target: "relayer::exit_if_term","caught SIGTERM");
-        exit(0);
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use types::{EventProof, H256};
+
+    use clap::Parser;
+
+    use super::{
+        check_header_encoding, convert_block_receipts, convert_ethers_block,
+        convert_ethers_receipt, is_terminated, prepare_config, ConversionError,
+    };
+    use crate::config::Config;
+
+    fn load_block(json: &str) -> ethers::types::Block<ethers::types::H256> {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn convert_block_without_base_fee() {
+        // Mainnet genesis, long before London
+        let block = load_block(include_str!("../../merkle/tests/suits/block_0.json"));
+        let hash = H256(block.hash.unwrap().0);
+
+        let block = convert_ethers_block(block).unwrap();
+        assert_eq!(block.header.base_fee_per_gas, None);
+        assert_eq!(block.header.hash(), hash);
+    }
+
+    fn block_without(field: &str) -> ethers::types::Block<ethers::types::H256> {
+        let mut block: serde_json::Value =
+            serde_json::from_str(include_str!("../../merkle/tests/suits/block_17819525.json"))
+                .unwrap();
+        block.as_object_mut().unwrap().remove(field).unwrap();
+        serde_json::from_value(block).unwrap()
+    }
+
+    #[test]
+    fn convert_block_without_mix_hash() {
+        let err = convert_ethers_block(block_without("mixHash")).unwrap_err();
+        assert!(err.to_string().contains("mix_hash"), "{err}");
+    }
+
+    #[test]
+    fn missing_block_fields_are_named() {
+        for (json_field, field) in [
+            ("logsBloom", "logs_bloom"),
+            ("miner", "author"),
+            ("mixHash", "mix_hash"),
+        ] {
+            assert_eq!(
+                convert_ethers_block(block_without(json_field)).unwrap_err(),
+                ConversionError::MissingBlockField {
+                    block: Some(17819525),
+                    field
+                }
+            );
+        }
+        assert_eq!(
+            convert_ethers_block(block_without("number")).unwrap_err(),
+            ConversionError::MissingBlockField {
+                block: None,
+                field: "number"
+            }
+        );
+    }
+
+    #[test]
+    fn invalid_tx_type_is_named() {
+        let response: serde_json::Value = serde_json::from_str(include_str!(
+            "../tests/suits/eth_getBlockReceipts_8652100.json"
+        ))
+        .unwrap();
+        let mut receipt = response["result"][0].clone();
+        receipt["type"] = "0x7f".into();
+        let receipt: ethers::types::TransactionReceipt = serde_json::from_value(receipt).unwrap();
+
+        let err = convert_ethers_receipt(receipt).unwrap_err();
+        assert_eq!(
+            err,
+            ConversionError::InvalidTxType {
+                block: Some(8652100),
+                transaction_index: 0,
+                tx_type: 0x7f
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "Failed to parse receipt 0 of block 8652100: invalid tx type 127"
+        );
+    }
+
+    #[test]
+    fn receipt_without_status_is_not_a_failure() {
+        let response: serde_json::Value = serde_json::from_str(include_str!(
+            "../tests/suits/eth_getBlockReceipts_8652100.json"
+        ))
+        .unwrap();
+        let receipt = |status: Option<&str>| {
+            let mut receipt = response["result"][1].clone();
+            match status {
+                Some(status) => receipt["status"] = status.into(),
+                None => {
+                    receipt.as_object_mut().unwrap().remove("status");
+                    // Pre-Byzantium receipts carry the state root instead
+                    receipt["root"] = format!("0x{}", "11".repeat(32)).into();
+                }
+            }
+            serde_json::from_value::<ethers::types::TransactionReceipt>(receipt).unwrap()
+        };
+
+        assert!(
+            convert_ethers_receipt(receipt(Some("0x1")))
+                .unwrap()
+                .receipt
+                .success
+        );
+        assert!(
+            !convert_ethers_receipt(receipt(Some("0x0")))
+                .unwrap()
+                .receipt
+                .success
+        );
+
+        let err = convert_ethers_receipt(receipt(None)).unwrap_err();
+        assert_eq!(
+            err,
+            ConversionError::InvalidStatus {
+                block: Some(8652100),
+                transaction_index: 1,
+                status: None
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "Failed to parse receipt 1 of block 8652100: missing status"
+        );
+
+        assert_eq!(
+            convert_ethers_receipt(receipt(Some("0x2")))
+                .unwrap_err()
+                .to_string(),
+            "Failed to parse receipt 1 of block 8652100: invalid status 2"
+        );
+    }
+
+    #[test]
+    fn convert_block_with_requests_hash() {
+        let requests_hash = "0xe3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+        let mut block: serde_json::Value =
+            serde_json::from_str(include_str!("../../merkle/tests/suits/block_17819525.json"))
+                .unwrap();
+        block["requestsHash"] = requests_hash.into();
+
+        let header = convert_ethers_block(serde_json::from_value(block.clone()).unwrap())
+            .unwrap()
+            .header;
+        assert_eq!(
+            header.requests_hash,
+            Some(H256(
+                hex::decode(&requests_hash[2..])
+                    .unwrap()
+                    .try_into()
+                    .unwrap()
+            ))
+        );
+
+        block["requestsHash"] = "0x1234".into();
+        assert_eq!(
+            convert_ethers_block(serde_json::from_value(block).unwrap()).unwrap_err(),
+            ConversionError::InvalidBlockField {
+                block: Some(17819525),
+                field: "requests_hash"
+            }
+        );
+        // Blocks before Prague have none
+        assert_eq!(
+            convert_ethers_block(load_block(include_str!(
+                "../../merkle/tests/suits/block_17819525.json"
+            )))
+            .unwrap()
+            .header
+            .requests_hash,
+            None
+        );
+    }
+
+    #[test]
+    fn convert_block_without_nonce() {
+        let block = block_without("nonce");
+        let hash = H256(block.hash.unwrap().0);
+
+        let block = convert_ethers_block(block).unwrap();
+        assert_eq!(block.header.nonce, 0);
+        assert_eq!(block.header.hash(), hash);
+    }
+
+    #[test]
+    fn header_encoding_self_test() {
+        for json in [
+            include_str!("../../merkle/tests/suits/block_0.json"),
+            include_str!("../../merkle/tests/suits/block_17819525.json"),
+            include_str!("../../merkle/tests/suits/block_18027905.json"),
+        ] {
+            check_header_encoding(load_block(json)).unwrap();
+        }
+    }
+
+    #[test]
+    fn header_encoding_self_test_flags_unknown_field() {
+        // A node of a later hardfork hashes a field the relayer doesn't encode
+        let mut block: serde_json::Value =
+            serde_json::from_str(include_str!("../../merkle/tests/suits/block_17819525.json"))
+                .unwrap();
+        block["blockAccessListHash"] =
+            "0xe3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855".into();
+        block["hash"] = "0x1111111111111111111111111111111111111111111111111111111111111111".into();
+
+        let err = check_header_encoding(load_block(&block.to_string())).unwrap_err();
+        let err = err.to_string();
+        assert!(err.contains("block 17819525"), "{err}");
+        assert!(err.contains("0x1111"), "{err}");
+        assert!(err.contains("\"blockAccessListHash\""), "{err}");
+    }
+
+    #[test]
+    fn convert_block_receipts_sample() {
+        let response: serde_json::Value = serde_json::from_str(include_str!(
+            "../tests/suits/eth_getBlockReceipts_8652100.json"
+        ))
+        .unwrap();
+        let block = load_block(include_str!("../../merkle/tests/suits/block_8652100.json"));
+        let hash = H256(block.hash.unwrap().0);
+        let block = convert_ethers_block(block).unwrap();
+
+        let receipts = convert_block_receipts(response["result"].clone()).unwrap();
+        assert_eq!(receipts.len(), block.transactions.len());
+        // The receipts only prove against the header if all of them are there, in order
+        for index in [0, receipts.len() - 1] {
+            EventProof::build(block.header.clone(), hash, &receipts, index).unwrap();
+        }
+
+        assert!(convert_block_receipts(serde_json::json!({ "code": -32601 })).is_err());
+    }
+
+    #[test]
+    fn is_terminated_follows_flag() {
+        let term = AtomicBool::new(false);
+        assert!(!is_terminated(&term));
+        term.store(true, Ordering::Relaxed);
+        // Used to call `exit(0)`, which would have killed the test runner here.
+        assert!(is_terminated(&term));
+    }
+
+    fn config(extra: &[&str]) -> Config {
+        let helios_config = concat!(env!("CARGO_MANIFEST_DIR"), "/../helios.toml");
+        let args = [
+            "relayer",
+            "--network",
+            "sepolia",
+            "--database",
+            "db",
+            "--substrate-config-path",
+            "ggxchain-config.toml",
+            "--helios-config-path",
+            helios_config,
+        ];
+        Config::try_parse_from(args.iter().chain(extra)).unwrap()
+    }
+
+    #[test]
+    fn checkpoint_overrides_helios_config() {
+        let helios_config = prepare_config(&config(&[])).unwrap();
+        assert_eq!(
+            helios_config.checkpoint,
+            Some(
+                hex::decode("c6203ac1c054548242b7710b1ef67f1b912e52cd34afe133865f07ce1218811a")
+                    .unwrap()
+            )
+        );
+        assert!(helios_config.load_external_fallback);
+
+        let checkpoint = "0x".to_string() + &"ab".repeat(32);
+        let helios_config = prepare_config(&config(&["--checkpoint", &checkpoint])).unwrap();
+        assert_eq!(helios_config.checkpoint, Some(vec![0xab; 32]));
+        assert!(!helios_config.load_external_fallback);
+
+        assert!(prepare_config(&config(&["--checkpoint", "0xabcd"])).is_err());
     }
 }
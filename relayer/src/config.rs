@@ -2,7 +2,76 @@ use std::path::PathBuf;
 
 use serde::Deserialize;
 
-use clap::Parser;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use types::{H160, H256};
+
+#[derive(Debug, Parser)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    #[command(flatten)]
+    pub config: Option<Config>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Build and validate the `EventProof` for a receipt of a block and print it as JSON.
+    BuildProof(BuildProofArgs),
+    /// Print the watched contracts, proof deposit and reward, and finalized height of a chain.
+    Status(StatusArgs),
+    /// Mark stored blocks as unprocessed, so the relayer builds proofs for them again.
+    ResetProcessed(ResetProcessedArgs),
+    /// Compare a stored block with the block the execution node reports at its height.
+    VerifyBlock(VerifyBlockArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct BuildProofArgs {
+    /// Block JSON, as returned by `eth_getBlockByHash`.
+    #[arg(long)]
+    pub block: PathBuf,
+    /// JSON array with all receipts of the block, in transaction order.
+    #[arg(long)]
+    pub receipts: PathBuf,
+    /// Index of the receipt to prove.
+    #[arg(long)]
+    pub index: usize,
+}
+
+#[derive(Debug, Args)]
+pub struct StatusArgs {
+    #[arg(long, visible_alias = "chain")]
+    pub network: String,
+    #[arg(long)]
+    pub substrate_config_path: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct ResetProcessedArgs {
+    #[arg(long)]
+    pub database: PathBuf,
+    /// First block to reset.
+    #[arg(long = "from-block")]
+    pub from_block: u64,
+    /// Last block to reset, inclusive.
+    #[arg(long = "to-block")]
+    pub to_block: u64,
+}
+
+#[derive(Debug, Args)]
+pub struct VerifyBlockArgs {
+    #[arg(long)]
+    pub database: PathBuf,
+    #[arg(long, visible_alias = "chain")]
+    pub network: String,
+    /// Helios config with the execution RPC of the network.
+    #[arg(long)]
+    pub helios_config_path: PathBuf,
+    /// Height of the stored block to verify.
+    #[arg(long)]
+    pub height: u64,
+}
 
 #[derive(Deserialize, Debug, Clone, Parser)]
 pub struct Config {
@@ -14,12 +83,142 @@ pub struct Config {
     pub substrate_config_path: PathBuf,
     #[arg(long)]
     pub helios_config_path: PathBuf,
+    /// Trusted beacon block root to sync helios from, instead of the `checkpoint` in the helios
+    /// config. Disables `load_external_fallback`.
+    #[arg(long)]
+    pub checkpoint: Option<String>,
+    /// Directory helios keeps its checkpoint in, `<database>/helios` by default.
+    #[arg(long)]
+    pub helios_data_dir: Option<PathBuf>,
+    /// Host to serve metrics on, `127.0.0.1` by default.
     #[arg(long)]
     pub server_host: Option<String>,
+    /// Port to serve metrics on. Metrics are not served if unset.
     #[arg(long)]
     pub server_port: Option<u64>,
     #[arg(long)]
     pub blocks_to_store: Option<u64>,
     #[arg(long)]
     pub bloom_processor_limit_per_block: Option<u64>,
+    /// Which bloom positive blocks to build proofs for first.
+    #[arg(long, value_enum, default_value_t = ProcessingOrder::OldestFirst)]
+    #[serde(default)]
+    pub processing_order: ProcessingOrder,
+    /// Block to start fetching from when the database is empty. By default the last
+    /// `blocks_to_store` blocks are fetched. Blocks from it up to the lowest stored block are
+    /// fetched too, so an interrupted first run is resumed.
+    #[arg(long = "from-block")]
+    pub start_block: Option<u64>,
+    /// Number of blocks below the finalized head to wait before relaying a block.
+    #[arg(long)]
+    pub confirmation_depth: Option<u64>,
+    /// Number of missing blocks above which they are fetched by number concurrently instead of
+    /// walking back parent hashes one block at a time.
+    #[arg(long)]
+    pub parallel_fetch_threshold: Option<u64>,
+    /// Number of recently fetched blocks kept in memory, so walking back parent hashes again
+    /// after a failed attempt doesn't refetch them. 256 by default, 0 turns the cache off.
+    #[arg(long)]
+    pub block_cache_size: Option<usize>,
+    /// First delay between RPC retries, doubled on every retry.
+    #[arg(long)]
+    pub retry_base_delay_ms: Option<u64>,
+    /// Upper bound of the delay between RPC retries.
+    #[arg(long)]
+    pub retry_max_delay_ms: Option<u64>,
+    /// Most a proof submission may cost in fees, as a multiple of the proof reward. Proofs
+    /// costing more are deferred until fees drop. Fees are not checked if unset.
+    #[arg(long)]
+    pub max_fee_reward_ratio: Option<f64>,
+    /// Largest serialized proof to submit. Larger proofs would exceed the extrinsic length limit
+    /// of the chain and are skipped. 3932160 bytes by default.
+    #[arg(long)]
+    pub max_proof_bytes: Option<usize>,
+    /// Most proof submissions watched at a time, later proofs of a batch wait for a slot so the
+    /// tx pool of the node isn't flooded. 8 by default.
+    #[arg(long)]
+    pub max_in_flight_submissions: Option<usize>,
+    /// Seconds a block whose proofs were sent is not selected again while it is unprocessed, so
+    /// a restart after a crash between sending and marking it doesn't send the proofs right
+    /// away. Blocks are retried on the next iteration if unset.
+    #[arg(long)]
+    pub submission_cooldown_secs: Option<u64>,
+    /// Seconds the watched addresses of the registry are cached before being fetched again. A
+    /// shorter TTL picks up changes to the watched contracts sooner at the cost of more queries
+    /// to the node. 300 seconds by default.
+    #[arg(long)]
+    pub watched_addresses_ttl_secs: Option<u64>,
+    /// Build and validate proofs without submitting them or marking blocks as processed.
+    #[arg(long)]
+    #[serde(default)]
+    pub dry_run: bool,
+    /// Fetch the blocks up to the finalized head, prove and submit their events once, and exit.
+    #[arg(long)]
+    #[serde(default)]
+    pub once: bool,
+    /// `ADDRESS:TOPIC` pair; blocks are only relayed for `ADDRESS` if their bloom also contains
+    /// one of its topics, and only its events with one of them as signature are proven.
+    /// Addresses without topics are relayed for any event.
+    #[arg(long = "watch-topic", value_parser = parse_watched_topic)]
+    #[serde(default)]
+    pub watched_topics: Vec<(H160, H256)>,
+    /// Format of the log lines written to stderr.
+    #[arg(long, value_enum, env = "LOG_FORMAT", default_value_t = LogFormat::Text)]
+    #[serde(default)]
+    pub log_format: LogFormat,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProcessingOrder {
+    /// Lowest heights first, in chain order.
+    #[default]
+    OldestFirst,
+    /// Highest heights first, so fresh events are relayed while a backfill catches up.
+    NewestFirst,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human readable lines.
+    #[default]
+    Text,
+    /// One JSON object per line with the timestamp, level, target and message.
+    Json,
+}
+
+fn parse_watched_topic(value: &str) -> Result<(H160, H256), String> {
+    let (address, topic) = value
+        .split_once(':')
+        .ok_or_else(|| "expected ADDRESS:TOPIC".to_string())?;
+    let decode = |value: &str| {
+        hex::decode(value.trim_start_matches("0x")).map_err(|err| format!("{value}: {err}"))
+    };
+    let address: [u8; 20] = decode(address)?
+        .try_into()
+        .map_err(|_| "address must be 20 bytes".to_string())?;
+    let topic: [u8; 32] = decode(topic)?
+        .try_into()
+        .map_err(|_| "topic must be 32 bytes".to_string())?;
+    Ok((H160(address), H256(topic)))
+}
+
+#[cfg(test)]
+mod tests {
+    use types::{H160, H256};
+
+    use super::parse_watched_topic;
+
+    #[test]
+    fn parse_watch_topic() {
+        let address = "0x".to_string() + &"11".repeat(20);
+        let topic = "22".repeat(32);
+        assert_eq!(
+            parse_watched_topic(&format!("{address}:{topic}")),
+            Ok((H160([0x11; 20]), H256([0x22; 32])))
+        );
+        assert!(parse_watched_topic(&address).is_err());
+        assert!(parse_watched_topic(&format!("{topic}:{address}")).is_err());
+    }
 }
@@ -1,7 +1,30 @@
 use std::time::Duration;
 
 pub const BLOCK_AMOUNT_TO_STORE: u64 = 100;
-pub const UPDATE_WATCHED_ADDRESSES_INTERVAL: Duration = Duration::from_secs(5 * 60);
+// How long the watched addresses of a chain are used before being fetched again
+pub const DEFAULT_WATCHED_ADDRESSES_TTL: Duration = Duration::from_secs(5 * 60);
+// How long a receipt found not to be proven yet is not checked again, proven ones are never rechecked
+pub const UNPROVED_RECEIPT_CACHE_TTL: Duration = Duration::from_secs(30);
 pub const SLEEP_DURATION: Duration = Duration::from_secs(60);
 // It will be used to limit the amount of blocks that will be processed in one parallel iteration
 pub const DEFAULT_LIMIT_PROCESSING_BLOCKS_PER_ITERATION: u64 = 5;
+// Largest serialized proof submitted by default, the normal dispatch share of a 5 MiB block
+pub const DEFAULT_MAX_PROOF_BYTES: usize = 3_932_160;
+// Number of missing blocks above which they are fetched by number instead of walking parent hashes
+pub const DEFAULT_PARALLEL_FETCH_THRESHOLD: u64 = 256;
+// Number of recently fetched blocks kept in memory, enough for a walk back below the threshold
+pub const DEFAULT_BLOCK_CACHE_SIZE: usize = 256;
+// Maximum number of blocks fetched concurrently when fetching by number
+pub const PARALLEL_FETCH_CONCURRENCY: usize = 16;
+// Number of blocks fetched by number, verified and stored at a time
+pub const FETCH_CHUNK_SIZE: u64 = 1024;
+// Backoff of the RPC retries, see `client::Backoff`
+pub const RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+pub const RETRY_MAX_DELAY: Duration = Duration::from_secs(30);
+pub const RETRY_BUDGET: Duration = Duration::from_secs(2 * 60);
+// Environment variable with the password of the signer keystore, unless configured otherwise
+pub const KEYSTORE_PASSWORD_ENV: &str = "RELAYER_KEYSTORE_PASSWORD";
+// How many times a proof rejected by the tx pool is resubmitted with a fresh nonce
+pub const MAX_RESUBMISSIONS: u32 = 3;
+// Maximum number of proof submissions watched at a time
+pub const DEFAULT_MAX_IN_FLIGHT_SUBMISSIONS: usize = 8;
@@ -1,34 +1,75 @@
 use std::{
+    ops::RangeInclusive,
     path::Path,
-    sync::{Arc, Mutex},
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
 };
 
 use eyre::Result;
-use rusqlite::Connection;
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::{Connection, OptionalExtension};
 use types::{BlockHeaderWithTransaction, H256};
 
+/// Schema migrations by version, applied in order. A released migration is never changed, schema
+/// changes are added as a new migration.
+const MIGRATIONS: &[(u32, &str)] = &[
+    (1, include_str!("./sql/migrations/0001_blocks.sql")),
+    (
+        2,
+        include_str!("./sql/migrations/0002_blocks_is_processed_index.sql"),
+    ),
+    (
+        3,
+        include_str!("./sql/migrations/0003_blocks_submission_attempted_at.sql"),
+    ),
+];
+
 #[derive(Clone)]
 pub struct DB {
-    conn: Arc<Mutex<Connection>>,
+    pool: Arc<Pool<SqliteConnectionManager>>,
 }
 
 impl DB {
+    /// Creates the database directory and its missing parents, and checks that it is writable,
+    /// as the database and the helios data directory are kept in it.
+    pub fn create_dir(db_dir: &Path) -> Result<()> {
+        std::fs::create_dir_all(db_dir).map_err(|err| {
+            eyre::eyre!(
+                "Failed to create database directory {}: {err}",
+                db_dir.display()
+            )
+        })?;
+        let probe = db_dir.join(".write-check");
+        std::fs::write(&probe, b"")
+            .and_then(|()| std::fs::remove_file(&probe))
+            .map_err(|err| {
+                eyre::eyre!(
+                    "Database directory {} is not writable: {err}",
+                    db_dir.display()
+                )
+            })
+    }
+
     pub fn new(db_dir: &Path) -> Result<Self> {
-        let conn = Connection::open(db_dir.join("db.sqlite"))?;
+        let manager = SqliteConnectionManager::file(db_dir.join("db.sqlite"));
+        let pool = Pool::new(manager)?;
 
         Ok(DB {
-            conn: Arc::new(Mutex::new(conn)),
+            pool: Arc::new(pool),
         })
     }
 
+    /// Creates or migrates the tables and switches the database to WAL journaling, so the bloom
+    /// processor can read while the client is inserting blocks.
     pub fn create_tables(&self) -> Result<()> {
-        let conn = self.conn.lock().expect("acquire mutex");
-        let sql = include_str!("./sql/schema.sql");
-        Ok(conn.execute_batch(sql)?)
+        let mut conn = self.pool.get()?;
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
+        migrate(&mut conn)
     }
 
     pub fn select_latest_fetched_block_height(&self) -> Result<Option<u64>> {
-        let conn = self.conn.lock().expect("acquire mutex");
+        let conn = self.pool.get()?;
         let mut stmt =
             conn.prepare("SELECT block_height FROM blocks ORDER BY block_height DESC LIMIT 1;")?;
         let block_height_iter = stmt.query_map([], |row| row.get::<_, u64>(0))?;
@@ -40,18 +81,28 @@ impl DB {
             .cloned())
     }
 
-    pub fn select_latest_fetched_block_hash(&self) -> Result<Option<H256>> {
-        let conn = self.conn.lock().expect("acquire mutex");
-        let mut stmt =
-            conn.prepare("SELECT block_hash FROM blocks ORDER BY block_height DESC LIMIT 1;")?;
-        let block_hash_iter = stmt.query_map([], |row| row.get::<_, [u8; 32]>(0))?;
+    /// The lowest heights missing below a stored block, from `floor` up to the lowest stored
+    /// block or between stored blocks. Fetching by number stores the newest blocks first, so
+    /// stopping midway leaves such a gap below them.
+    pub fn select_first_gap(&self, floor: Option<u64>) -> Result<Option<RangeInclusive<u64>>> {
+        let conn = self.pool.get()?;
+        let lowest = conn.query_row("SELECT MIN(block_height) FROM blocks", [], |row| {
+            row.get::<_, Option<u64>>(0)
+        })?;
+        if let (Some(floor), Some(lowest)) = (floor, lowest) {
+            if floor < lowest {
+                return Ok(Some(floor..=lowest - 1));
+            }
+        }
 
-        Ok(block_hash_iter
-            .flatten()
-            .flat_map(|hash| Ok::<H256, eyre::Report>(H256(hash)))
-            .collect::<Vec<_>>()
-            .first()
-            .cloned())
+        let gap = conn
+            .query_row(
+                "SELECT b.block_height + 1, (SELECT MIN(a.block_height) FROM blocks a WHERE a.block_height > b.block_height) - 1 FROM blocks b LEFT JOIN blocks n ON n.block_height = b.block_height + 1 WHERE n.block_height IS NULL AND b.block_height < (SELECT MAX(block_height) FROM blocks) ORDER BY b.block_height LIMIT 1",
+                [],
+                |row| Ok(row.get::<_, u64>(0)?..=row.get::<_, u64>(1)?),
+            )
+            .optional()?;
+        Ok(gap)
     }
 
     pub fn insert_block(
@@ -61,7 +112,7 @@ impl DB {
         block_header: BlockHeaderWithTransaction,
         bloom_positive: bool,
     ) -> Result<()> {
-        let conn = self.conn.lock().expect("acquire mutex");
+        let conn = self.pool.get()?;
         let is_processed = !bloom_positive; // We need to process only bloom positive blocks
         conn.execute(
             "INSERT INTO blocks(block_height, block_hash, block_header, is_processed) values (?1, ?2, ?3, ?4)",
@@ -76,15 +127,38 @@ impl DB {
         Ok(())
     }
 
+    /// Selects up to `limit` unprocessed blocks below `max_block`, lowest first. Blocks whose
+    /// proofs were sent at or after `attempted_before` are skipped, none are if it is `None`.
     pub fn select_blocks_to_process(
         &self,
         max_block: u64,
         limit: u64,
+        attempted_before: Option<SystemTime>,
     ) -> Result<Vec<(u64, H256, BlockHeaderWithTransaction)>> {
-        let conn = self.conn.lock().expect("acquire mutex");
-        let mut stmt =
-            conn.prepare("SELECT block_height, block_hash, block_Header FROM blocks WHERE is_processed = 0 AND block_height < ?1 ORDER BY block_height LIMIT ?2")?;
-        let blocks_iter = stmt.query_map((max_block, limit), |row| {
+        self.select_unprocessed_blocks(max_block, limit, attempted_before, "ASC")
+    }
+
+    /// Like [`DB::select_blocks_to_process`], but the newest blocks below `max_block` first.
+    pub fn select_blocks_to_process_desc(
+        &self,
+        max_block: u64,
+        limit: u64,
+        attempted_before: Option<SystemTime>,
+    ) -> Result<Vec<(u64, H256, BlockHeaderWithTransaction)>> {
+        self.select_unprocessed_blocks(max_block, limit, attempted_before, "DESC")
+    }
+
+    fn select_unprocessed_blocks(
+        &self,
+        max_block: u64,
+        limit: u64,
+        attempted_before: Option<SystemTime>,
+        order: &str,
+    ) -> Result<Vec<(u64, H256, BlockHeaderWithTransaction)>> {
+        let attempted_before = attempted_before.map_or(i64::MAX as u64, unix_secs);
+        let conn = self.pool.get()?;
+        let mut stmt = conn.prepare(&unprocessed_blocks_query(order))?;
+        let blocks_iter = stmt.query_map((max_block, limit, attempted_before), |row| {
             let block_height = row.get::<_, u64>(0)?;
             let block_hash = row.get::<_, [u8; 32]>(1)?;
             let block_header = row.get::<_, String>(2)?;
@@ -101,8 +175,29 @@ impl DB {
         Ok(blocks_iter.flatten().collect::<Vec<_>>())
     }
 
+    /// The hash and header of the stored block at `block_height`, `None` if it isn't stored.
+    pub fn select_block_by_height(
+        &self,
+        block_height: u64,
+    ) -> Result<Option<(H256, BlockHeaderWithTransaction)>> {
+        let conn = self.pool.get()?;
+        let block = conn
+            .query_row(
+                "SELECT block_hash, block_header FROM blocks WHERE block_height = ?1",
+                (block_height,),
+                |row| Ok((row.get::<_, [u8; 32]>(0)?, row.get::<_, String>(1)?)),
+            )
+            .optional()?;
+
+        block
+            .map(|(block_hash, block_header)| -> Result<_> {
+                Ok((H256(block_hash), serde_json::from_str(&block_header)?))
+            })
+            .transpose()
+    }
+
     pub fn mark_block_processed(&self, block_number: u64) -> Result<()> {
-        let conn = self.conn.lock().expect("acquire mutex");
+        let conn = self.pool.get()?;
         conn.execute(
             "UPDATE blocks SET is_processed = 1 WHERE block_height = ?1",
             (block_number,),
@@ -110,15 +205,96 @@ impl DB {
 
         Ok(())
     }
+
+    /// Records that proofs of the blocks were sent at `at`, so a restart before they are marked
+    /// processed can leave them alone for a while instead of sending the proofs again right away.
+    pub fn mark_submission_attempted(&self, block_numbers: &[u64], at: SystemTime) -> Result<()> {
+        let mut conn = self.pool.get()?;
+        let tx = conn.transaction()?;
+        for block_number in block_numbers {
+            tx.execute(
+                "UPDATE blocks SET submission_attempted_at = ?1 WHERE block_height = ?2",
+                (unix_secs(at), block_number),
+            )?;
+        }
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    /// Marks the stored blocks `from_block..=to_block` as unprocessed, so the bloom processor
+    /// checks them again. Blocks that aren't stored are not fetched. Returns how many blocks
+    /// were reset.
+    pub fn reset_processed(&self, from_block: u64, to_block: u64) -> Result<usize> {
+        if from_block > to_block {
+            return Err(eyre::eyre!(
+                "invalid range: {from_block} is above {to_block}"
+            ));
+        }
+        let conn = self.pool.get()?;
+        Ok(conn.execute(
+            "UPDATE blocks SET is_processed = 0 WHERE block_height >= ?1 AND block_height <= ?2",
+            (from_block, to_block),
+        )?)
+    }
+
+    /// Closes the pooled connections. Fails if another handle to the database is still alive.
+    pub fn close(self) -> Result<()> {
+        let pool =
+            Arc::try_unwrap(self.pool).map_err(|_| eyre::eyre!("database is still in use"))?;
+        drop(pool);
+        Ok(())
+    }
+}
+
+/// Applies the [`MIGRATIONS`] above the latest version recorded in `schema_migrations`, each in its
+/// own transaction together with recording its version.
+fn migrate(conn: &mut Connection) -> Result<()> {
+    const TARGET: &str = "relayer::db::migrate";
+
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS schema_migrations (version INTEGER NOT NULL PRIMARY KEY);",
+    )?;
+    let current = conn.query_row(
+        "SELECT COALESCE(MAX(version), 0) FROM schema_migrations",
+        [],
+        |row| row.get::<_, u32>(0),
+    )?;
+
+    for (version, sql) in MIGRATIONS.iter().filter(|(version, _)| *version > current) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(sql)
+            .map_err(|err| eyre::eyre!("database migration {version} failed: {err}"))?;
+        tx.execute(
+            "INSERT INTO schema_migrations (version) VALUES (?1)",
+            (version,),
+        )?;
+        tx.commit()?;
+        log::info!(target: TARGET, "Applied database migration {}", version);
+    }
+    Ok(())
+}
+
+fn unprocessed_blocks_query(order: &str) -> String {
+    format!("SELECT block_height, block_hash, block_Header FROM blocks WHERE is_processed = 0 AND block_height < ?1 AND (submission_attempted_at IS NULL OR submission_attempted_at < ?3) ORDER BY block_height {order} LIMIT ?2")
+}
+
+/// Seconds since the Unix epoch, as submission attempts are stored.
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
 }
 
 #[cfg(test)]
 mod tests {
+    use std::time::{Duration, SystemTime};
+
     use proptest::{prelude::any, proptest, strategy::Strategy};
     use tempfile::{tempdir, TempDir};
     use types::{BlockHeader, BlockHeaderWithTransaction, Bloom, H160, H256, U256};
 
-    use super::DB;
+    use super::{unprocessed_blocks_query, DB, MIGRATIONS};
 
     fn db() -> (TempDir, DB) {
         let dir = tempdir().unwrap();
@@ -177,6 +353,7 @@ mod tests {
                 blob_gas_used,
                 excess_blob_gas,
                 parent_beacon_block_root,
+                requests_hash,
                 extra_data,
             ),
         ): (
@@ -202,6 +379,7 @@ mod tests {
                 Option<u64>,
                 Option<u64>,
                 Option<H256>,
+                Option<H256>,
                 Vec<u8>,
             ),
         ),
@@ -226,6 +404,7 @@ mod tests {
             blob_gas_used,
             excess_blob_gas,
             parent_beacon_block_root,
+            requests_hash,
             extra_data,
         }
     }
@@ -255,6 +434,7 @@ mod tests {
                 any::<Option<u64>>(),
                 any::<Option<u64>>(),
                 h256_option_strat(),
+                h256_option_strat(),
                 any::<Vec<u8>>(),
             ),
         )
@@ -270,6 +450,77 @@ mod tests {
         })
     }
 
+    fn block_hash(height: u64) -> H256 {
+        let mut hash = [0; 32];
+        hash[..8].copy_from_slice(&height.to_be_bytes());
+        H256(hash)
+    }
+
+    fn block(height: u64) -> BlockHeaderWithTransaction {
+        let header = block_header_new((
+            (
+                H256([1; 32]),
+                H256([2; 32]),
+                H160([3; 20]),
+                H256([4; 32]),
+                H256([5; 32]),
+                H256([6; 32]),
+                None,
+                Bloom::new([0; 256]),
+                U256([0; 32]),
+            ),
+            (
+                height,
+                0,
+                0,
+                0,
+                H256([7; 32]),
+                0,
+                None,
+                None,
+                None,
+                None,
+                None,
+                vec![],
+            ),
+        ));
+        BlockHeaderWithTransaction {
+            header,
+            transactions: vec![],
+        }
+    }
+
+    #[test]
+    fn create_nested_dir() {
+        let dir = tempdir().unwrap();
+        let db_dir = dir.path().join("relayer").join("sepolia");
+        DB::create_dir(&db_dir).unwrap();
+        // Existing directories are fine
+        DB::create_dir(&db_dir).unwrap();
+        assert_eq!(std::fs::read_dir(&db_dir).unwrap().count(), 0);
+
+        DB::new(&db_dir).unwrap().create_tables().unwrap();
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn create_dir_below_file() {
+        let dir = tempdir().unwrap();
+        let file = dir.path().join("file");
+        std::fs::write(&file, "").unwrap();
+
+        let db_dir = file.join("db");
+        let err = DB::create_dir(&db_dir).unwrap_err().to_string();
+        assert!(
+            err.starts_with(&format!(
+                "Failed to create database directory {}: ",
+                db_dir.display()
+            )),
+            "{err}"
+        );
+        dir.close().unwrap();
+    }
+
     #[test]
     fn create_tables() {
         let (dir, db) = db();
@@ -277,6 +528,308 @@ mod tests {
         dir.close().unwrap();
     }
 
+    fn applied_migrations(db: &DB) -> Vec<u32> {
+        let conn = db.pool.get().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT version FROM schema_migrations ORDER BY version")
+            .unwrap();
+        let versions = stmt
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        versions
+    }
+
+    #[test]
+    fn migration_versions_are_sequential() {
+        for (i, (version, _)) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(*version as usize, i + 1);
+        }
+    }
+
+    #[test]
+    fn old_schema_is_migrated() {
+        let (dir, db) = db();
+        // A database of a relayer without versioned migrations, before the blocks were indexed
+        db.pool
+            .get()
+            .unwrap()
+            .execute_batch(MIGRATIONS[0].1)
+            .unwrap();
+        db.insert_block(100, block_hash(100), block(100), true)
+            .unwrap();
+
+        db.create_tables().unwrap();
+
+        let latest = MIGRATIONS.last().unwrap().0;
+        assert_eq!(applied_migrations(&db), (1..=latest).collect::<Vec<_>>());
+        let index: String = db
+            .pool
+            .get()
+            .unwrap()
+            .query_row(
+                "SELECT name FROM sqlite_master WHERE type = 'index' AND tbl_name = 'blocks' AND name NOT LIKE 'sqlite_%'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(index, "blocks_is_processed_block_height");
+        let blocks = db
+            .select_blocks_to_process(u32::MAX.into(), 10, None)
+            .unwrap();
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].0, 100);
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn migrations_are_idempotent() {
+        let (dir, db) = db();
+        db.create_tables().unwrap();
+        let applied = applied_migrations(&db);
+        assert_eq!(applied.len(), MIGRATIONS.len());
+        db.insert_block(100, block_hash(100), block(100), true)
+            .unwrap();
+
+        // Restarting the relayer applies nothing again and keeps the data
+        db.create_tables().unwrap();
+        db.create_tables().unwrap();
+        assert_eq!(applied_migrations(&db), applied);
+        assert_eq!(db.select_latest_fetched_block_height().unwrap(), Some(100));
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn close_waits_for_last_handle() {
+        let (dir, db) = db();
+        let handle = db.clone();
+        assert!(db.close().is_err());
+        handle.close().unwrap();
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn concurrent_inserts_and_selects() {
+        let (dir, db) = db();
+        db.create_tables().unwrap();
+        let journal_mode: String = db
+            .pool
+            .get()
+            .unwrap()
+            .pragma_query_value(None, "journal_mode", |row| row.get(0))
+            .unwrap();
+        assert_eq!(journal_mode, "wal");
+
+        let writers = (0..4u64)
+            .map(|thread| {
+                let db = db.clone();
+                std::thread::spawn(move || {
+                    for i in 0..25 {
+                        let height = thread * 25 + i;
+                        db.insert_block(height, block_hash(height), block(height), true)
+                            .unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        let readers = (0..4)
+            .map(|_| {
+                let db = db.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..25 {
+                        db.select_blocks_to_process(u32::MAX.into(), 10, None)
+                            .unwrap();
+                        db.select_latest_fetched_block_height().unwrap();
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in writers.into_iter().chain(readers) {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(db.select_latest_fetched_block_height().unwrap(), Some(99));
+        assert_eq!(
+            db.select_blocks_to_process(u32::MAX.into(), 1000, None)
+                .unwrap()
+                .len(),
+            100
+        );
+        db.close().unwrap();
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn select_blocks_uses_index() {
+        let (dir, db) = db();
+        db.create_tables().unwrap();
+        let conn = db.pool.get().unwrap();
+        for order in ["ASC", "DESC"] {
+            let mut stmt = conn
+                .prepare(&format!(
+                    "EXPLAIN QUERY PLAN {}",
+                    unprocessed_blocks_query(order)
+                ))
+                .unwrap();
+            let plan = stmt
+                .query_map((100, 10, 0), |row| row.get::<_, String>(3))
+                .unwrap()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            assert_eq!(plan.len(), 1, "{plan:?}");
+            assert!(
+                plan[0].contains("USING INDEX blocks_is_processed_block_height"),
+                "{plan:?}"
+            );
+        }
+        drop(conn);
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn select_blocks_newest_first() {
+        let (dir, db) = db();
+        db.create_tables().unwrap();
+        for height in 100..120 {
+            // Every third block is bloom negative
+            db.insert_block(height, block_hash(height), block(height), height % 3 != 0)
+                .unwrap();
+        }
+
+        let heights = |blocks: Vec<(u64, H256, BlockHeaderWithTransaction)>| {
+            blocks
+                .into_iter()
+                .map(|(height, _, _)| height)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            heights(db.select_blocks_to_process_desc(119, 4, None).unwrap()),
+            [118, 116, 115, 113]
+        );
+        assert_eq!(
+            heights(db.select_blocks_to_process(119, 4, None).unwrap()),
+            [100, 101, 103, 104]
+        );
+        assert_eq!(
+            heights(db.select_blocks_to_process_desc(103, 10, None).unwrap()),
+            [101, 100]
+        );
+        db.mark_block_processed(118).unwrap();
+        assert_eq!(
+            heights(
+                db.select_blocks_to_process_desc(u32::MAX.into(), 2, None)
+                    .unwrap()
+            ),
+            [119, 116]
+        );
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn recently_attempted_blocks_are_skipped() {
+        let (dir, db) = db();
+        db.create_tables().unwrap();
+        for height in 100..104 {
+            db.insert_block(height, block_hash(height), block(height), true)
+                .unwrap();
+        }
+        let cooldown = Duration::from_secs(10 * 60);
+        let now = SystemTime::now();
+        db.mark_submission_attempted(&[101, 102], now).unwrap();
+
+        let heights = |attempted_before| {
+            db.select_blocks_to_process(u32::MAX.into(), 10, attempted_before)
+                .unwrap()
+                .into_iter()
+                .map(|(height, _, _)| height)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(heights(Some(now - cooldown)), [100, 103]);
+        assert_eq!(heights(None), [100, 101, 102, 103]);
+
+        // Once the cooldown has elapsed the blocks are selected again
+        assert_eq!(
+            heights(Some(now + Duration::from_secs(1))),
+            [100, 101, 102, 103]
+        );
+        // An attempt before the cooldown doesn't hold the block back
+        db.mark_submission_attempted(&[102], now - cooldown - Duration::from_secs(1))
+            .unwrap();
+        assert_eq!(heights(Some(now - cooldown)), [100, 102, 103]);
+        assert_eq!(
+            db.select_blocks_to_process_desc(u32::MAX.into(), 10, Some(now - cooldown))
+                .unwrap()
+                .len(),
+            3
+        );
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn reset_processed_range() {
+        let (dir, db) = db();
+        db.create_tables().unwrap();
+        for height in 100..110 {
+            db.insert_block(height, block_hash(height), block(height), height % 2 == 0)
+                .unwrap();
+        }
+        for height in (100..110).step_by(2) {
+            db.mark_block_processed(height).unwrap();
+        }
+        assert!(db
+            .select_blocks_to_process(u32::MAX.into(), 20, None)
+            .unwrap()
+            .is_empty());
+
+        // Only the stored blocks of the range are reset
+        assert_eq!(db.reset_processed(105, 120).unwrap(), 5);
+        assert_eq!(db.reset_processed(200, 300).unwrap(), 0);
+        assert!(db.reset_processed(120, 105).is_err());
+
+        let heights = db
+            .select_blocks_to_process(u32::MAX.into(), 20, None)
+            .unwrap()
+            .into_iter()
+            .map(|(height, hash, block)| {
+                assert_eq!(hash, block_hash(height));
+                assert_eq!(block.header.number, height);
+                height
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(heights, [105, 106, 107, 108, 109]);
+        assert_eq!(db.select_latest_fetched_block_height().unwrap(), Some(109));
+        dir.close().unwrap();
+    }
+
+    #[test]
+    fn gaps_between_stored_blocks() {
+        let (dir, db) = db();
+        db.create_tables().unwrap();
+        assert_eq!(db.select_first_gap(None).unwrap(), None);
+        assert_eq!(db.select_first_gap(Some(90)).unwrap(), None);
+
+        for height in (100..=102).chain(106..=108).chain([110]) {
+            db.insert_block(height, block_hash(height), block(height), true)
+                .unwrap();
+        }
+        assert_eq!(db.select_first_gap(None).unwrap(), Some(103..=105));
+        // Blocks from the floor up to the lowest stored block come first
+        assert_eq!(db.select_first_gap(Some(90)).unwrap(), Some(90..=99));
+        assert_eq!(db.select_first_gap(Some(100)).unwrap(), Some(103..=105));
+
+        for height in 103..=105 {
+            db.insert_block(height, block_hash(height), block(height), true)
+                .unwrap();
+        }
+        assert_eq!(db.select_first_gap(None).unwrap(), Some(109..=109));
+
+        db.insert_block(109, block_hash(109), block(109), true)
+            .unwrap();
+        assert_eq!(db.select_first_gap(None).unwrap(), None);
+        dir.close().unwrap();
+    }
+
     proptest! {
         #[test]
         fn insert(
@@ -302,7 +855,7 @@ mod tests {
             db.create_tables().unwrap();
             db.insert_block(block_number, block_hash, block_header.clone(), true)
                 .unwrap();
-            let blocks = db.select_blocks_to_process(block_number + 1, 1).unwrap();
+            let blocks = db.select_blocks_to_process(block_number + 1, 1, None).unwrap();
             assert_eq!(blocks.len(), 1);
             let (block_numb, hash, block) = blocks[0].clone();
             assert_eq!(block_numb, block_number);
@@ -310,12 +863,12 @@ mod tests {
             assert_eq!(block, block_header);
 
             // Check if specify less max block we receive nothing
-            let blocks = db.select_blocks_to_process(block_number - 1,1).unwrap();
+            let blocks = db.select_blocks_to_process(block_number - 1, 1, None).unwrap();
             assert_eq!(blocks.len(), 0);
 
             // Check that block is not received after processing
             db.mark_block_processed(block_number).unwrap();
-            let blocks = db.select_blocks_to_process(block_number + 1, 1).unwrap();
+            let blocks = db.select_blocks_to_process(block_number + 1, 1, None).unwrap();
             assert_eq!(blocks.len(), 0);
             dir.close().unwrap();
         }
@@ -0,0 +1,56 @@
+use std::{fmt::Display, io::Write};
+
+use crate::config::LogFormat;
+
+/// Initializes the logger. Lines are filtered by `RUST_LOG` in either format.
+pub fn init(format: LogFormat) {
+    let mut builder = env_logger::Builder::from_default_env();
+    if format == LogFormat::Json {
+        builder.format(|buf, record| writeln!(buf, "{}", json_line(buf.timestamp(), record)));
+    }
+    builder.init();
+}
+
+/// A log line for aggregators. Keeps the `target` so lines can still be told apart by module,
+/// e.g. `relayer::client::start`.
+fn json_line(timestamp: impl Display, record: &log::Record) -> String {
+    serde_json::json!({
+        "timestamp": timestamp.to_string(),
+        "level": record.level().as_str(),
+        "target": record.target(),
+        "message": record.args().to_string(),
+    })
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use log::{Level, Record};
+
+    use super::json_line;
+
+    #[test]
+    fn json_line_is_parseable() {
+        let line = json_line(
+            "2023-08-01T00:00:00Z",
+            &Record::builder()
+                .args(format_args!(
+                    "Processing \"finality\" update\nfor block {}",
+                    1
+                ))
+                .level(Level::Info)
+                .target("relayer::client::start")
+                .build(),
+        );
+        assert!(!line.contains('\n'));
+
+        let value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(value["level"], "INFO");
+        assert_eq!(value["target"], "relayer::client::start");
+        assert_eq!(
+            value["message"],
+            "Processing \"finality\" update\nfor block 1"
+        );
+        assert_eq!(value["timestamp"], "2023-08-01T00:00:00Z");
+    }
+}
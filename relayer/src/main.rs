@@ -1,39 +1,96 @@
-use std::sync::{atomic::AtomicBool, Arc};
+use std::{
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
-use clap::Parser;
+use bloom_processor::BloomProcessor;
+use clap::{CommandFactory, Parser};
 use client::Client;
 use eyre::Result;
-use tokio::fs;
 
 mod bloom_processor;
+mod build_proof;
 mod client;
 pub(crate) mod common;
 mod config;
 pub(crate) mod consts;
 mod db;
+mod logger;
+mod metrics;
+mod network;
+mod reset_processed;
+mod status;
 mod substrate_client;
+mod verify_block;
 
-use config::Config;
+use config::{Cli, Command};
 use db::DB;
+use network::EthNetwork;
 use substrate_client::SubstrateClient;
 
+/// Exit code of a `--once` run that left proofs to retry.
+const EXIT_PROOFS_FAILED: i32 = 2;
+
 #[tokio::main]
 async fn main() -> Result<()> {
-    env_logger::init();
+    let cli = Cli::parse();
+    logger::init(
+        cli.config
+            .as_ref()
+            .map(|config| config.log_format)
+            .unwrap_or_default(),
+    );
+
+    match &cli.command {
+        Some(Command::BuildProof(args)) => return build_proof::run(args),
+        Some(Command::Status(args)) => return status::run(args).await,
+        Some(Command::ResetProcessed(args)) => return reset_processed::run(args),
+        Some(Command::VerifyBlock(args)) => return verify_block::run(args).await,
+        None => {}
+    }
+    let Some(config) = cli.config else {
+        Cli::command()
+            .error(
+                clap::error::ErrorKind::MissingRequiredArgument,
+                "the relayer requires --network, --database, --substrate-config-path and --helios-config-path",
+            )
+            .exit()
+    };
 
-    let config = Config::parse();
     let term = Arc::new(AtomicBool::new(false));
     signal_hook::flag::register(signal_hook::consts::SIGTERM, Arc::clone(&term))?;
 
-    if !fs::try_exists(&config.database).await? {
-        fs::create_dir(&config.database).await?
-    }
-
+    DB::create_dir(&config.database)?;
     let db = DB::new(&config.database)?;
     db.create_tables()?;
 
-    let chain_id: u32 = network_name_to_id(&config.network)?;
-    let substrate_client = SubstrateClient::new(&config.substrate_config_path, chain_id).await?;
+    if let Some(port) = config.server_port {
+        let host = config.server_host.as_deref().unwrap_or("127.0.0.1");
+        let addr: SocketAddr = format!("{host}:{port}").parse()?;
+        tokio::spawn(async move {
+            if let Err(e) = metrics::serve(addr).await {
+                log::error!("metrics server was stopped because of {e:?}");
+            }
+        });
+    }
+
+    let chain_id = EthNetwork::try_from(config.network.as_str())?.chain_id;
+    let once = config.once;
+    // The client and the bloom processor each cache the watched addresses for this long
+    let watched_addresses_ttl = config
+        .watched_addresses_ttl_secs
+        .map(Duration::from_secs)
+        .unwrap_or(consts::DEFAULT_WATCHED_ADDRESSES_TTL);
+    let substrate_client = SubstrateClient::new(
+        &config.substrate_config_path,
+        chain_id,
+        watched_addresses_ttl,
+    )
+    .await?;
 
     let mut client = Client::new(
         config.clone(),
@@ -42,29 +99,66 @@ async fn main() -> Result<()> {
         substrate_client.clone(),
     )?;
     let mut bloom_processor =
-        bloom_processor::BloomProcessor::new(db.clone(), config, term, substrate_client, chain_id)?;
+        BloomProcessor::new(db.clone(), config, term.clone(), substrate_client, chain_id)?;
 
+    if once {
+        return run_once(client, bloom_processor, db).await;
+    }
+
+    let mut client_task = tokio::spawn(async move { client.start().await });
+    let mut bloom_processor_task = tokio::spawn(async move { bloom_processor.run().await });
+
+    let mut client_stopped = false;
+    let mut bloom_processor_stopped = false;
     tokio::select! {
             _ = tokio::signal::ctrl_c() => {
                 log::info!("ctrl-c received, shutting down");
             }
 
-            err = tokio::spawn(async move { client.start().await } ) => {
-                log::error!("client was stopped because of {err:?}");
+            res = &mut client_task => {
+                client_stopped = true;
+                log::error!("client was stopped because of {res:?}");
             }
 
-            err = tokio::spawn(async move { bloom_processor.run().await }) => {
-                log::error!("bloom processor was stopped because of {err:?}");
+            res = &mut bloom_processor_task => {
+                bloom_processor_stopped = true;
+                log::error!("bloom processor was stopped because of {res:?}");
             }
     }
+
+    // Let both loops finish their current iteration.
+    term.store(true, Ordering::Relaxed);
+    if !client_stopped {
+        if let Err(e) = client_task.await? {
+            log::error!("client stopped with error: {e:?}");
+        }
+    }
+    if !bloom_processor_stopped {
+        bloom_processor_task.await?;
+    }
+
+    db.close()?;
+    log::info!("relayer stopped");
     Ok(())
 }
 
-fn network_name_to_id(network_name: &str) -> Result<u32> {
-    match network_name {
-        "mainnet" => Ok(1),
-        "goerli" => Ok(5),
-        "sepolia" => Ok(11155111),
-        _ => Err(eyre::eyre!("Unknown network name {}", network_name)),
+/// Fetches the blocks up to the finalized head and proves their events once. Exits with
+/// [`EXIT_PROOFS_FAILED`] if proofs of some blocks weren't submitted, they are retried on the next
+/// run.
+async fn run_once(mut client: Client, mut bloom_processor: BloomProcessor, db: DB) -> Result<()> {
+    client.start().await?;
+    let pass = bloom_processor.process_blocks().await?;
+    log::info!(
+        "single pass finished: {} blocks processed, {} submitted, {} failed",
+        pass.blocks,
+        pass.submitted,
+        pass.failed
+    );
+
+    drop((client, bloom_processor));
+    db.close()?;
+    if pass.failed > 0 {
+        std::process::exit(EXIT_PROOFS_FAILED);
     }
+    Ok(())
 }
@@ -0,0 +1,120 @@
+use std::{net::SocketAddr, sync::OnceLock};
+
+use prometheus::{
+    exponential_buckets, Encoder, Histogram, HistogramOpts, IntCounter, IntCounterVec, Opts,
+    Registry, TextEncoder,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+
+/// Relayer metrics, served in the Prometheus text format.
+pub struct Metrics {
+    registry: Registry,
+    /// Proofs built and validated, whether submitted or not.
+    pub proofs_built: IntCounter,
+    /// Proofs that failed validation, by reason.
+    pub proof_rejections: IntCounterVec,
+    /// Proofs not submitted because the fee exceeded the allowed share of the reward.
+    pub proofs_deferred_for_fee: IntCounter,
+    /// Proofs not submitted because they are larger than the chain accepts.
+    pub proofs_oversized: IntCounter,
+    /// Time to build the receipt trie of a block and the proof of one receipt in it.
+    pub proof_build_seconds: Histogram,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry =
+            Registry::new_custom(Some("relayer".into()), None).expect("valid metrics prefix");
+        let proofs_built = IntCounter::new("proofs_built_total", "Proofs built and validated")
+            .expect("valid metric");
+        registry
+            .register(Box::new(proofs_built.clone()))
+            .expect("metric registered once");
+        let proof_rejections = IntCounterVec::new(
+            Opts::new("proof_rejections_total", "Proofs that failed validation"),
+            &["reason"],
+        )
+        .expect("valid metric");
+        registry
+            .register(Box::new(proof_rejections.clone()))
+            .expect("metric registered once");
+        let proofs_deferred_for_fee = IntCounter::new(
+            "proofs_deferred_for_fee_total",
+            "Proofs not submitted because the fee exceeded the allowed share of the reward",
+        )
+        .expect("valid metric");
+        registry
+            .register(Box::new(proofs_deferred_for_fee.clone()))
+            .expect("metric registered once");
+        let proofs_oversized = IntCounter::new(
+            "proofs_oversized_total",
+            "Proofs not submitted because they are larger than the chain accepts",
+        )
+        .expect("valid metric");
+        registry
+            .register(Box::new(proofs_oversized.clone()))
+            .expect("metric registered once");
+        // 1 ms up to about 16 s
+        let proof_build_seconds = Histogram::with_opts(
+            HistogramOpts::new(
+                "proof_build_seconds",
+                "Time to build the receipt trie of a block and the proof of a receipt",
+            )
+            .buckets(exponential_buckets(0.001, 2.0, 15).expect("valid buckets")),
+        )
+        .expect("valid metric");
+        registry
+            .register(Box::new(proof_build_seconds.clone()))
+            .expect("metric registered once");
+
+        Self {
+            registry,
+            proofs_built,
+            proof_rejections,
+            proofs_deferred_for_fee,
+            proofs_oversized,
+            proof_build_seconds,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        let mut buffer = vec![];
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .expect("metrics are valid utf-8");
+        String::from_utf8(buffer).expect("metrics are valid utf-8")
+    }
+}
+
+pub fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(Metrics::new)
+}
+
+/// Answers every connection on `addr` with the current metrics.
+pub async fn serve(addr: SocketAddr) -> eyre::Result<()> {
+    const TARGET: &str = "relayer::metrics::serve";
+
+    let listener = TcpListener::bind(addr).await?;
+    log::info!(target: TARGET, "serving metrics on {addr}");
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+        tokio::spawn(async move {
+            // The request itself doesn't matter, there is only one page.
+            let mut request = [0u8; 1024];
+            let _ = stream.read(&mut request).await;
+
+            let body = metrics().encode();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\n\r\n{body}",
+                body.len()
+            );
+            if let Err(e) = stream.write_all(response.as_bytes()).await {
+                log::debug!(target: TARGET, "failed to write metrics: {e}");
+            }
+        });
+    }
+}
@@ -0,0 +1,51 @@
+use helios::config::networks::Network as HeliosNetwork;
+
+/// An Ethereum network the relayer follows, as named by `--network`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EthNetwork {
+    /// Chain id, the key of the chain in the receipt registry.
+    pub chain_id: u32,
+    /// Network of the light client, naming the section of the helios config.
+    pub helios: HeliosNetwork,
+}
+
+impl TryFrom<&str> for EthNetwork {
+    type Error = eyre::Report;
+
+    fn try_from(network_name: &str) -> eyre::Result<Self> {
+        let (chain_id, helios) = match network_name {
+            "mainnet" => (1, HeliosNetwork::MAINNET),
+            "goerli" => (5, HeliosNetwork::GOERLI),
+            "sepolia" => (11155111, HeliosNetwork::SEPOLIA),
+            _ => return Err(eyre::eyre!("Unknown network name {}", network_name)),
+        };
+        Ok(Self { chain_id, helios })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EthNetwork, HeliosNetwork};
+
+    #[test]
+    fn supported_networks() {
+        for (name, chain_id, helios) in [
+            ("mainnet", 1, HeliosNetwork::MAINNET),
+            ("goerli", 5, HeliosNetwork::GOERLI),
+            ("sepolia", 11155111, HeliosNetwork::SEPOLIA),
+        ] {
+            let network = EthNetwork::try_from(name).unwrap();
+            assert_eq!(network, EthNetwork { chain_id, helios });
+            // The helios config has a section per network
+            assert_eq!(network.helios.to_string(), name);
+        }
+    }
+
+    #[test]
+    fn unknown_network() {
+        for name in ["", "Mainnet", "holesky", "ropsten"] {
+            let err = EthNetwork::try_from(name).unwrap_err();
+            assert_eq!(err.to_string(), format!("Unknown network name {name}"));
+        }
+    }
+}
@@ -0,0 +1,28 @@
+use crate::config::ResetProcessedArgs;
+use crate::db::DB;
+
+/// Marks the stored blocks of `args.from_block..=args.to_block` as unprocessed, so the next run
+/// rebuilds their proofs, e.g. after a contract was watched retroactively.
+pub fn run(args: &ResetProcessedArgs) -> eyre::Result<()> {
+    // Opening a missing database would create an empty one
+    if !args.database.join("db.sqlite").try_exists()? {
+        return Err(eyre::eyre!(
+            "no relayer database in {}",
+            args.database.display()
+        ));
+    }
+    let db = DB::new(&args.database)?;
+    let reset = db.reset_processed(args.from_block, args.to_block)?;
+    if reset == 0 {
+        return Err(eyre::eyre!(
+            "no stored blocks between {} and {}",
+            args.from_block,
+            args.to_block
+        ));
+    }
+    println!(
+        "marked {reset} blocks between {} and {} as unprocessed",
+        args.from_block, args.to_block
+    );
+    db.close()
+}
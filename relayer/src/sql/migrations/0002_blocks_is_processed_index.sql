@@ -0,0 +1 @@
+CREATE INDEX IF NOT EXISTS blocks_is_processed_block_height ON blocks (is_processed, block_height);
@@ -0,0 +1,2 @@
+-- Unix time in seconds proofs of the block were last sent at, NULL if they never were
+ALTER TABLE blocks ADD COLUMN submission_attempted_at INTEGER;
@@ -0,0 +1,94 @@
+use std::fmt;
+
+use types::H160;
+
+use crate::config::StatusArgs;
+use crate::consts::DEFAULT_WATCHED_ADDRESSES_TTL;
+use crate::network::EthNetwork;
+use crate::substrate_client::{ProofEconomics, SubstrateClient};
+
+/// What the registry currently knows about a chain.
+#[derive(Debug)]
+pub struct Status {
+    pub network: String,
+    pub chain_id: u32,
+    pub finalized_height: u64,
+    pub economics: ProofEconomics,
+    pub watched_addresses: Vec<H160>,
+}
+
+/// Queries the GGX node for the status of `args.network` and prints it to stdout.
+pub async fn run(args: &StatusArgs) -> eyre::Result<()> {
+    let chain_id = EthNetwork::try_from(args.network.as_str())?.chain_id;
+    let mut substrate_client = SubstrateClient::new(
+        &args.substrate_config_path,
+        chain_id,
+        DEFAULT_WATCHED_ADDRESSES_TTL,
+    )
+    .await?;
+
+    let status = Status {
+        network: args.network.clone(),
+        chain_id,
+        finalized_height: substrate_client
+            .last_known_block_block_number(chain_id)
+            .await?,
+        economics: substrate_client.proof_economics(chain_id).await?,
+        watched_addresses: substrate_client.watched_addresses(chain_id).await?,
+    };
+    print!("{status}");
+    Ok(())
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{:<18} {} ({})", "chain", self.network, self.chain_id)?;
+        writeln!(f, "{:<18} {}", "finalized height", self.finalized_height)?;
+        writeln!(f, "{:<18} {}", "proof deposit", self.economics.deposit)?;
+        writeln!(f, "{:<18} {}", "proof reward", self.economics.reward)?;
+        writeln!(
+            f,
+            "{:<18} {}",
+            "watched contracts",
+            self.watched_addresses.len()
+        )?;
+        for address in &self.watched_addresses {
+            writeln!(f, "  0x{}", hex::encode(address.0))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use types::H160;
+
+    use super::Status;
+    use crate::substrate_client::ProofEconomics;
+
+    #[test]
+    fn status_table() {
+        let status = Status {
+            network: "sepolia".into(),
+            chain_id: 11155111,
+            finalized_height: 4_500_000,
+            economics: ProofEconomics {
+                deposit: 10,
+                reward: 25,
+            },
+            watched_addresses: vec![H160([0x11; 20]), H160([0xab; 20])],
+        };
+        assert_eq!(
+            status.to_string(),
+            "\
+chain              sepolia (11155111)
+finalized height   4500000
+proof deposit      10
+proof reward       25
+watched contracts  2
+  0x1111111111111111111111111111111111111111
+  0xabababababababababababababababababababab
+"
+        );
+    }
+}
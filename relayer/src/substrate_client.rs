@@ -1,14 +1,21 @@
-use std::{collections::HashMap, path::Path, time::Duration};
+use std::{
+    collections::HashMap,
+    future::Future,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use eyre::Result;
+use futures::StreamExt;
 use subxt::{error::DispatchError, tx::TxStatus, OnlineClient, PolkadotConfig};
 use subxt_signer::{
     bip39::Mnemonic,
     sr25519::{dev, Keypair},
+    SecretUri,
 };
 use types::H160;
 
-use crate::consts::UPDATE_WATCHED_ADDRESSES_INTERVAL;
+use crate::consts::{KEYSTORE_PASSWORD_ENV, MAX_RESUBMISSIONS, UNPROVED_RECEIPT_CACHE_TTL};
 
 use self::ggxchain::runtime_types::webb_proposals::header::TypedChainId;
 
@@ -18,11 +25,17 @@ pub struct SubstrateClient {
     keypair: Keypair,
     chain_id: u32,
 
-    watched_addresses: HashMap<u32, (Duration, Vec<H160>)>,
+    watched_addresses: WatchedAddressesCache,
+    proved_receipts: ProvedCache,
 }
 
 impl SubstrateClient {
-    pub async fn new(substrate_config_path: &Path, chain_id: u32) -> Result<Self> {
+    /// `watched_addresses_ttl` is how long the answers of `watched_addresses` are reused.
+    pub async fn new(
+        substrate_config_path: &Path,
+        chain_id: u32,
+        watched_addresses_ttl: Duration,
+    ) -> Result<Self> {
         let file_content = std::fs::read_to_string(substrate_config_path)?;
         let config: SubstrateConfig = toml::from_str(&file_content)?;
         let api = OnlineClient::<PolkadotConfig>::from_url(&config.ws_url)
@@ -34,27 +47,35 @@ impl SubstrateClient {
                     err
                 )
             })?;
-        let keypair = if config.is_dev {
-            dev::alice()
-        } else {
-            Keypair::from_phrase(&config.phrase, config.password.as_deref())?
-        };
+        let keypair = config.keypair()?;
         Ok(Self {
             api,
             keypair,
             chain_id,
-            watched_addresses: HashMap::new(),
+            watched_addresses: WatchedAddressesCache::new(watched_addresses_ttl),
+            proved_receipts: ProvedCache::default(),
         })
     }
 
-    pub async fn send_event_proof(&self, event_proof: types::EventProof, nonce: u64) -> Result<()> {
-        // TODO: Ideally we should check if the proof isn't already submitted
-        // but let's skip this for now
+    pub async fn send_event_proof(
+        &self,
+        event_proof: types::EventProof,
+        nonce: u64,
+    ) -> Result<(), SubmitError> {
+        self.submit_encoded_proof(encode_proof(&event_proof), nonce)
+            .await
+    }
 
-        let tx = ggxchain::tx().eth_receipt_registry().submit_proof(
-            TypedChainId::Evm(self.chain_id),
-            serde_json::to_vec(&event_proof)?,
-        );
+    /// submits an already serialized proof; fails with [`SubmitError::Rejected`] if the pool
+    /// drops the tx
+    async fn submit_encoded_proof(
+        &self,
+        encoded_proof: Vec<u8>,
+        nonce: u64,
+    ) -> Result<(), SubmitError> {
+        let tx = ggxchain::tx()
+            .eth_receipt_registry()
+            .submit_proof(typed_chain_id(self.chain_id), encoded_proof);
         let mut tx_progress = self
             .api
             .tx()
@@ -67,11 +88,9 @@ impl SubstrateClient {
                 Ok(e) => e,
                 Err(err) => {
                     log::error!("failed to watch for tx events {err:?}");
-                    return Err(std::io::Error::new(
-                        std::io::ErrorKind::Other,
-                        format!("Failed to get hash storage value: {err:?}"),
-                    )
-                    .into());
+                    return Err(SubmitError::Transport(format!(
+                        "failed to watch for tx events: {err}"
+                    )));
                 }
             };
             match e {
@@ -88,54 +107,68 @@ impl SubstrateClient {
                 }
                 TxStatus::FinalityTimeout(_) => {
                     log::warn!("tx timeout");
+                    return Err(SubmitError::Timeout);
                 }
                 TxStatus::Finalized(v) => {
-                    let maybe_success = v.wait_for_success().await;
-                    match maybe_success {
+                    return match v.wait_for_success().await {
                         Ok(_) => {
                             log::debug!("tx finalized");
-                            return Ok(());
+                            Ok(())
                         }
-                        Err(err) => {
-                            let error_msg = match err {
-                                subxt::Error::Runtime(DispatchError::Module(error)) => {
-                                    let details = error.details()?;
-                                    let pallet = details.pallet.name();
-                                    let error = &details.variant;
-                                    format!("Extrinsic failed with an error: {pallet}::{error:?}")
-                                }
-                                _ => {
-                                    format!("Extrinsic failed with an error: {}", err)
-                                }
-                            };
-
-                            return Err(std::io::Error::new(
-                                std::io::ErrorKind::Other,
-                                format!("Tx failed : {error_msg}"),
-                            )
-                            .into());
+                        Err(subxt::Error::Runtime(DispatchError::Module(error))) => {
+                            let details = error.details().map_err(subxt::Error::from)?;
+                            Err(SubmitError::DispatchModule {
+                                pallet: details.pallet.name().to_string(),
+                                error: details.variant.name.clone(),
+                            })
                         }
-                    }
+                        Err(err) => Err(SubmitError::Transport(format!(
+                            "extrinsic failed with an error: {err}"
+                        ))),
+                    };
+                }
+                TxStatus::Usurped(_) => {
+                    log::warn!("tx usurped");
+                    return Err(SubmitError::Rejected("usurped"));
                 }
-                TxStatus::Usurped(_) => {}
                 TxStatus::Dropped => {
                     log::warn!("tx dropped");
+                    return Err(SubmitError::Rejected("dropped"));
                 }
                 TxStatus::Invalid => {
                     log::warn!("tx invalid");
+                    return Err(SubmitError::Rejected("invalid"));
                 }
             }
         }
 
-        Err(std::io::Error::new(std::io::ErrorKind::Other, "Transaction stream ended").into())
+        Err(SubmitError::Transport("transaction stream ended".into()))
+    }
+
+    /// estimated fee of submitting an already serialized proof
+    pub async fn estimate_proof_fee(&self, encoded_proof: Vec<u8>) -> Result<u128> {
+        let tx = ggxchain::tx()
+            .eth_receipt_registry()
+            .submit_proof(typed_chain_id(self.chain_id), encoded_proof);
+        // The nonce doesn't change the fee
+        let fee = self
+            .api
+            .tx()
+            .create_signed_with_nonce(&tx, &self.keypair, 0, Default::default())?
+            .partial_fee_estimate()
+            .await?;
+        Ok(fee)
     }
 
     // TODO: Re-make it using utility pallet to submit a batch of proofs in single tx, but for now we keep it simple
-    /// sends a batch of proofs to the chain and returns a vector of results with a block_height
+    /// sends a batch of proofs to the chain, at most `max_in_flight` at a time, and returns a
+    /// vector of results with a block_height; proofs of receipts that are already proven are not
+    /// sent, as they would only cost a deposit
     pub async fn send_event_proofs(
-        &self,
+        &mut self,
         event_proofs: Vec<types::EventProof>,
-    ) -> Vec<(u64, Result<()>)> {
+        max_in_flight: usize,
+    ) -> Vec<(u64, Result<(), SubmitError>)> {
         const TARGET: &str = "relayer::substrate_client::send_event_proofs";
         log::debug!(target: TARGET, "sending event {} proofs", event_proofs.len());
 
@@ -143,87 +176,423 @@ impl SubstrateClient {
             .iter()
             .map(|event_proof| event_proof.block_header.number)
             .collect::<Vec<_>>();
-        let nonce = self
-            .api
-            .tx()
-            .account_nonce(&self.keypair.public_key().into())
-            .await;
-
-        if let Err(err) = nonce {
-            log::error!("failed to get nonce: {err:?}");
-            return vec![];
+        let mut encoded_proofs = Vec::with_capacity(event_proofs.len());
+        for event_proof in &event_proofs {
+            let proved = self
+                .is_item_proved(self.chain_id, event_proof.transaction_receipt_hash)
+                .await;
+            encoded_proofs.push(match proved {
+                Ok(true) => Err(SubmitError::AlreadyProcessed),
+                // Submitting is still safe, the pallet doesn't reward a receipt twice
+                Ok(false) | Err(_) => Ok(encode_proof(event_proof)),
+            });
         }
-        let nonce = nonce.unwrap();
-
-        let events_len = event_proofs.len() as u64;
-        let event_proofs_future = event_proofs
-            .into_iter()
-            .zip(nonce..nonce + events_len)
-            .map(|(event_proof, nonce)| self.send_event_proof(event_proof, nonce))
-            .collect::<Vec<_>>();
 
-        let results = futures::future::join_all(event_proofs_future).await;
+        let results = submit_with_resubmission(
+            encoded_proofs,
+            MAX_RESUBMISSIONS,
+            max_in_flight,
+            || self.account_nonce(),
+            |encoded_proof, nonce| self.submit_encoded_proof(encoded_proof, nonce),
+        )
+        .await;
         block_heights.into_iter().zip(results.into_iter()).collect()
     }
 
+    async fn account_nonce(&self) -> Result<u64> {
+        Ok(self
+            .api
+            .tx()
+            .account_nonce(&self.keypair.public_key().into())
+            .await?)
+    }
+
     pub async fn watched_addresses(&mut self, chain_id: u32) -> Result<Vec<types::H160>> {
         let current_time = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?;
-        if let Some((last_update, data)) = self.watched_addresses.get(&chain_id) {
-            if current_time - *last_update < UPDATE_WATCHED_ADDRESSES_INTERVAL {
-                return Ok(data.clone());
-            }
-        }
-
-        let query = ggxchain::storage()
-            .eth_receipt_registry()
-            .watched_contracts(TypedChainId::Evm(chain_id));
-        let result: Vec<H160> = self
-            .api
-            .storage()
-            .at_latest()
-            .await?
-            .fetch(&query)
-            .await?
-            .map(|vec| vec.0)
-            .ok_or_else(|| eyre::eyre!("Empty watched contracts list"))?
-            .into_iter()
-            .map(|addr| types::H160(addr.0))
-            .collect();
+        let api = &self.api;
         self.watched_addresses
-            .insert(chain_id, (current_time, result.clone()));
-        Ok(result)
+            .get(chain_id, current_time, || async move {
+                let query = ggxchain::storage()
+                    .eth_receipt_registry()
+                    .watched_contracts(typed_chain_id(chain_id));
+                let result: Vec<H160> = api
+                    .storage()
+                    .at_latest()
+                    .await?
+                    .fetch(&query)
+                    .await?
+                    .map(|vec| vec.0)
+                    .ok_or_else(|| eyre::eyre!("Empty watched contracts list"))?
+                    .into_iter()
+                    .map(|addr| types::H160(addr.0))
+                    .collect();
+                Ok::<_, eyre::Report>(result)
+            })
+            .await
     }
 
     pub async fn last_known_block_block_number(&self, chain_id: u32) -> Result<u64> {
         let query = ggxchain::storage()
             .eth2_client()
-            .finalized_execution_header(TypedChainId::Evm(chain_id));
+            .finalized_execution_header(typed_chain_id(chain_id));
 
         let result = self.api.storage().at_latest().await?.fetch(&query).await?;
         result
-            .map(|header| header.block_number - 1) // -1 because it might not have all details on chain yet
+            .map(|header| header.block_number)
             .ok_or_else(|| eyre::eyre!("No finalized header"))
     }
 
-    pub async fn is_item_proved(&self, chain_id: u32, receipt_hash: types::H256) -> Result<bool> {
+    /// hash of block `number` as stored by the light client, which `submit_proof` checks the
+    /// proof against; `None` if the block isn't stored (yet or anymore)
+    pub async fn finalized_block_hash(
+        &self,
+        chain_id: u32,
+        number: u64,
+    ) -> Result<Option<types::H256>> {
         let query = ggxchain::storage()
-            .eth_receipt_registry()
-            .processed_receipts_hash(
-                TypedChainId::Evm(chain_id),
-                subxt::utils::Static(receipt_hash),
-            );
+            .eth2_client()
+            .finalized_execution_blocks(typed_chain_id(chain_id), number);
 
         let result = self.api.storage().at_latest().await?.fetch(&query).await?;
-        Ok(result.is_some())
+        Ok(result.map(|hash| types::H256(hash.0 .0)))
+    }
+
+    /// deposit taken for and reward paid for a proof on `chain_id`
+    pub async fn proof_economics(&self, chain_id: u32) -> Result<ProofEconomics> {
+        let storage = ggxchain::storage().eth_receipt_registry();
+        let at = self.api.storage().at_latest().await?;
+        let deposit = at
+            .fetch_or_default(&storage.proof_deposit(typed_chain_id(chain_id)))
+            .await?;
+        let reward = at
+            .fetch_or_default(&storage.proof_reward(typed_chain_id(chain_id)))
+            .await?;
+        Ok(ProofEconomics { deposit, reward })
+    }
+
+    /// whether the receipt is already proven on `chain_id`, answered from the cache if it was
+    /// checked recently
+    pub async fn is_item_proved(
+        &mut self,
+        chain_id: u32,
+        receipt_hash: types::H256,
+    ) -> Result<bool> {
+        let current_time = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?;
+        let api = &self.api;
+        self.proved_receipts
+            .check((chain_id, receipt_hash), current_time, || async move {
+                let query = ggxchain::storage()
+                    .eth_receipt_registry()
+                    .processed_receipts_hash(
+                        typed_chain_id(chain_id),
+                        subxt::utils::Static(receipt_hash),
+                    );
+
+                let result = api.storage().at_latest().await?.fetch(&query).await?;
+                Ok::<_, eyre::Report>(result.is_some())
+            })
+            .await
+    }
+}
+
+/// Answers of `is_item_proved` by chain and receipt hash. A proven receipt stays proven, so it is
+/// kept for good; one that isn't proven yet is checked again after
+/// [`UNPROVED_RECEIPT_CACHE_TTL`].
+#[derive(Debug, Clone, Default)]
+struct ProvedCache {
+    // `None` for proven receipts, otherwise when the receipt was found unproven
+    entries: HashMap<(u32, types::H256), Option<Duration>>,
+}
+
+impl ProvedCache {
+    async fn check<F, Fut>(
+        &mut self,
+        key: (u32, types::H256),
+        current_time: Duration,
+        fetch: F,
+    ) -> Result<bool>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<bool>>,
+    {
+        let is_fresh = |checked_at: Duration| {
+            current_time.saturating_sub(checked_at) < UNPROVED_RECEIPT_CACHE_TTL
+        };
+        match self.entries.get(&key) {
+            Some(None) => return Ok(true),
+            Some(Some(checked_at)) if is_fresh(*checked_at) => return Ok(false),
+            _ => {}
+        }
+
+        let proved = fetch().await?;
+        // Unproven receipts are only asked about once or twice, drop the stale ones
+        self.entries
+            .retain(|_, checked_at| checked_at.map_or(true, is_fresh));
+        self.entries.insert(key, (!proved).then_some(current_time));
+        Ok(proved)
+    }
+}
+
+/// Watched addresses by chain, fetched again once they are `ttl` old.
+#[derive(Debug, Clone)]
+struct WatchedAddressesCache {
+    ttl: Duration,
+    // Addresses of each chain with when they were fetched
+    entries: HashMap<u32, (Duration, Vec<H160>)>,
+}
+
+impl WatchedAddressesCache {
+    fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: HashMap::new(),
+        }
+    }
+
+    async fn get<F, Fut>(
+        &mut self,
+        chain_id: u32,
+        current_time: Duration,
+        fetch: F,
+    ) -> Result<Vec<H160>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Vec<H160>>>,
+    {
+        if let Some((fetched_at, addresses)) = self.entries.get(&chain_id) {
+            if current_time.saturating_sub(*fetched_at) < self.ttl {
+                return Ok(addresses.clone());
+            }
+        }
+
+        let addresses = fetch().await?;
+        self.entries
+            .insert(chain_id, (current_time, addresses.clone()));
+        Ok(addresses)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProofEconomics {
+    pub deposit: u128,
+    pub reward: u128,
+}
+
+/// The registry key of the chain `chain_id`. The relayer only follows EVM chains; this mirrors
+/// `pallet_receipt_registry::typed_chain_id`.
+fn typed_chain_id(chain_id: u32) -> TypedChainId {
+    TypedChainId::Evm(chain_id)
+}
+
+/// Name of the receipt registry pallet in the GGX runtime.
+const RECEIPT_REGISTRY_PALLET: &str = "EthReceiptRegistry";
+
+/// Why a proof submission failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubmitError {
+    /// Talking to the node failed.
+    Transport(String),
+    /// `submit_proof` was dispatched and failed with `pallet::error`.
+    DispatchModule { pallet: String, error: String },
+    /// The receipt is already proven, so the proof wasn't sent.
+    AlreadyProcessed,
+    /// The tx wasn't finalized in time.
+    Timeout,
+    /// The transaction pool rejected the tx (invalid, usurped or dropped), usually because its
+    /// nonce was taken by another transaction from the same account.
+    Rejected(&'static str),
+}
+
+impl SubmitError {
+    /// Whether submitting the same proof again can't succeed, so its block needs no retry. The
+    /// registry errors that clear up on their own (a block the light client doesn't have yet, a
+    /// paused pallet, a chain that isn't watched yet, a relayer that isn't allowed yet) and
+    /// errors of other pallets, like a balance too low for the deposit, are retried.
+    pub fn is_permanent(&self) -> bool {
+        match self {
+            SubmitError::AlreadyProcessed => true,
+            SubmitError::DispatchModule { pallet, error } => {
+                pallet == RECEIPT_REGISTRY_PALLET
+                    && !matches!(
+                        error.as_str(),
+                        "HeaderHashDoesNotExist"
+                            | "Paused"
+                            | "NoMonitoredAddressesForChain"
+                            | "RelayerNotAllowed"
+                    )
+            }
+            SubmitError::Transport(_) | SubmitError::Timeout | SubmitError::Rejected(_) => false,
+        }
+    }
+}
+
+impl std::fmt::Display for SubmitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SubmitError::Transport(err) => write!(f, "{err}"),
+            SubmitError::DispatchModule { pallet, error } => {
+                write!(f, "extrinsic failed with an error: {pallet}::{error}")
+            }
+            SubmitError::AlreadyProcessed => write!(f, "receipt is already proven"),
+            SubmitError::Timeout => write!(f, "tx finality timeout"),
+            SubmitError::Rejected(reason) => write!(f, "tx {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for SubmitError {}
+
+impl From<subxt::Error> for SubmitError {
+    fn from(err: subxt::Error) -> Self {
+        SubmitError::Transport(err.to_string())
+    }
+}
+
+pub(crate) fn encode_proof(event_proof: &types::EventProof) -> Vec<u8> {
+    serde_json::to_vec(event_proof).expect("proofs have no maps with non-string keys")
+}
+
+/// Submits `items` with consecutive nonces starting at `next_nonce()`, in nonce order and with at
+/// most `max_in_flight` submissions watched at a time. Items the pool rejected are resubmitted with
+/// a freshly fetched nonce, at most `max_resubmissions` times. Returns the final result of every
+/// item, in order.
+async fn submit_with_resubmission<T, N, NF, S, SF>(
+    items: Vec<Result<T, SubmitError>>,
+    max_resubmissions: u32,
+    max_in_flight: usize,
+    next_nonce: N,
+    submit: S,
+) -> Vec<Result<(), SubmitError>>
+where
+    T: Clone,
+    N: Fn() -> NF,
+    NF: Future<Output = Result<u64>>,
+    S: Fn(T, u64) -> SF,
+    SF: Future<Output = Result<(), SubmitError>>,
+{
+    const TARGET: &str = "relayer::substrate_client::submit_with_resubmission";
+
+    let mut results = Vec::with_capacity(items.len());
+    let mut pending = vec![];
+    for (i, item) in items.into_iter().enumerate() {
+        match item {
+            Ok(item) => {
+                results.push(None);
+                pending.push((i, item));
+            }
+            Err(err) => results.push(Some(Err(err))),
+        }
     }
+
+    let mut round = 0;
+    while !pending.is_empty() {
+        let nonce = match next_nonce().await {
+            Ok(nonce) => nonce,
+            Err(err) => {
+                log::error!(target: TARGET, "failed to get nonce: {err:?}");
+                for (i, _) in pending {
+                    results[i] = Some(Err(SubmitError::Transport(format!(
+                        "failed to get nonce: {err}"
+                    ))));
+                }
+                break;
+            }
+        };
+
+        // Submissions are started in nonce order, but may finish in any order
+        let mut outcomes = futures::stream::iter(pending.iter().zip(nonce..).enumerate().map(
+            |(position, ((_, item), nonce))| {
+                let submission = submit(item.clone(), nonce);
+                async move { (position, submission.await) }
+            },
+        ))
+        .buffer_unordered(max_in_flight.max(1))
+        .collect::<Vec<_>>()
+        .await;
+        outcomes.sort_by_key(|(position, _)| *position);
+        let outcomes = outcomes.into_iter().map(|(_, outcome)| outcome);
+
+        let mut rejected = vec![];
+        for ((i, item), outcome) in pending.into_iter().zip(outcomes) {
+            match outcome {
+                Err(err @ SubmitError::Rejected(_)) if round < max_resubmissions => {
+                    log::warn!(target: TARGET, "{err}, resubmitting with a fresh nonce");
+                    rejected.push((i, item));
+                }
+                outcome => results[i] = Some(outcome),
+            }
+        }
+        pending = rejected;
+        round += 1;
+    }
+
+    results
+        .into_iter()
+        .map(|result| result.expect("every item is either submitted or failed"))
+        .collect()
 }
 
 #[derive(Debug, Clone, serde::Deserialize)]
 struct SubstrateConfig {
     ws_url: String,
     is_dev: bool,
-    phrase: Mnemonic,
+    phrase: Option<Mnemonic>,
     password: Option<String>,
+    // Substrate keystore file to load the signer from instead of `phrase`
+    keystore_path: Option<PathBuf>,
+    // Environment variable holding the keystore password
+    keystore_password_env: Option<String>,
+}
+
+impl SubstrateConfig {
+    fn keypair(&self) -> Result<Keypair> {
+        if self.is_dev {
+            return Ok(dev::alice());
+        }
+        match (&self.keystore_path, &self.phrase) {
+            (Some(keystore_path), _) => {
+                let password_env = self
+                    .keystore_password_env
+                    .as_deref()
+                    .unwrap_or(KEYSTORE_PASSWORD_ENV);
+                // Without the password another key would be derived, so it has to be set, empty
+                // for a keystore without one
+                let password = std::env::var(password_env).map_err(|err| {
+                    eyre::eyre!("Keystore password variable {password_env} is not usable: {err}")
+                })?;
+                keypair_from_keystore(
+                    keystore_path,
+                    Some(password.as_str()).filter(|password| !password.is_empty()),
+                )
+            }
+            (None, Some(phrase)) => Ok(Keypair::from_phrase(phrase, self.password.as_deref())?),
+            (None, None) => Err(eyre::eyre!(
+                "Either `phrase` or `keystore_path` has to be set in the substrate config"
+            )),
+        }
+    }
+}
+
+/// Loads a keypair from a file of a Substrate keystore, which holds the secret URI (a mnemonic or
+/// a `0x` prefixed seed) as a JSON string. Like the node keystore, `password` is applied to the
+/// secret, so the key can't be derived from the file alone.
+fn keypair_from_keystore(path: &Path, password: Option<&str>) -> Result<Keypair> {
+    let secret: String = serde_json::from_str(&std::fs::read_to_string(path)?)
+        .map_err(|err| eyre::eyre!("Invalid keystore file {}: {err}", path.display()))?;
+    let uri = match password {
+        Some(_) if secret.contains("///") => {
+            return Err(eyre::eyre!(
+                "Keystore file {} already contains a password",
+                path.display()
+            ))
+        }
+        Some(password) => format!("{secret}///{password}"),
+        None => secret,
+    };
+    let uri: SecretUri = uri
+        .parse()
+        .map_err(|err| eyre::eyre!("Invalid secret in keystore file {}: {err}", path.display()))?;
+    Ok(Keypair::from_uri(&uri)?)
 }
 
 #[subxt::subxt(
@@ -234,3 +603,285 @@ struct SubstrateConfig {
     )
 )]
 mod ggxchain {}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::Cell, sync::Mutex};
+
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::consts::{DEFAULT_MAX_IN_FLIGHT_SUBMISSIONS, DEFAULT_WATCHED_ADDRESSES_TTL};
+
+    const ALICE_SEED: &str = "0xe5be9a5092b81bca64be81d212e7f2f9eba183bb7a90954f7b76361f6edb5c0a";
+
+    fn keystore_file(secret: &str) -> (tempfile::TempDir, PathBuf) {
+        let dir = tempdir().unwrap();
+        let keystore_path = dir.path().join("keystore.json");
+        std::fs::write(&keystore_path, serde_json::to_string(secret).unwrap()).unwrap();
+        (dir, keystore_path)
+    }
+
+    #[test]
+    fn keypair_from_keystore_file() {
+        let alice = dev::alice().public_key().0;
+
+        let (_dir, path) = keystore_file(ALICE_SEED);
+        assert_eq!(
+            keypair_from_keystore(&path, None).unwrap().public_key().0,
+            alice
+        );
+
+        let phrase = "bottom drive obey lake curtain smoke basket hold race lonely fit walk//Alice";
+        let (_dir, path) = keystore_file(phrase);
+        assert_eq!(
+            keypair_from_keystore(&path, None).unwrap().public_key().0,
+            alice
+        );
+
+        // The password is part of the key
+        assert_ne!(
+            keypair_from_keystore(&path, Some("secret"))
+                .unwrap()
+                .public_key()
+                .0,
+            alice
+        );
+    }
+
+    #[test]
+    fn missing_keystore_password_is_an_error() {
+        let (_dir, path) = keystore_file(ALICE_SEED);
+        let config = SubstrateConfig {
+            ws_url: "ws://localhost:9944".into(),
+            is_dev: false,
+            phrase: None,
+            password: None,
+            keystore_path: Some(path),
+            keystore_password_env: Some("RELAYER_TEST_UNSET_KEYSTORE_PASSWORD".into()),
+        };
+        assert!(config.keypair().is_err());
+    }
+
+    #[tokio::test]
+    async fn resubmits_rejected_tx_with_fresh_nonce() {
+        // another sender takes nonce 0, so the pool reports our first tx as invalid
+        let chain_nonce = Cell::new(0);
+        let submitted = Mutex::new(vec![]);
+
+        let results = submit_with_resubmission(
+            vec![Ok("proof")],
+            MAX_RESUBMISSIONS,
+            DEFAULT_MAX_IN_FLIGHT_SUBMISSIONS,
+            || {
+                let nonce = chain_nonce.get();
+                async move { Ok(nonce) }
+            },
+            |item, nonce| {
+                submitted.lock().unwrap().push((item, nonce));
+                let result = if nonce == 0 {
+                    chain_nonce.set(1);
+                    Err(SubmitError::Rejected("invalid"))
+                } else {
+                    Ok(())
+                };
+                async move { result }
+            },
+        )
+        .await;
+
+        assert_eq!(results.len(), 1);
+        assert!(results[0].is_ok());
+        assert_eq!(*submitted.lock().unwrap(), [("proof", 0), ("proof", 1)]);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_resubmissions() {
+        let attempts = Cell::new(0);
+
+        let results = submit_with_resubmission(
+            vec![Ok(()), Err(SubmitError::AlreadyProcessed)],
+            2,
+            DEFAULT_MAX_IN_FLIGHT_SUBMISSIONS,
+            || async { Ok(0) },
+            |_, _| {
+                attempts.set(attempts.get() + 1);
+                async { Err(SubmitError::Rejected("usurped")) }
+            },
+        )
+        .await;
+
+        assert_eq!(attempts.get(), 3);
+        assert_eq!(results[0], Err(SubmitError::Rejected("usurped")));
+        assert_eq!(results[1], Err(SubmitError::AlreadyProcessed));
+    }
+
+    #[tokio::test]
+    async fn limits_submissions_in_flight() {
+        const MAX_IN_FLIGHT: usize = 3;
+        let in_flight = Cell::new(0);
+        let most_in_flight = Cell::new(0);
+        let started = Mutex::new(vec![]);
+
+        let results = submit_with_resubmission(
+            (0..10).map(Ok).collect(),
+            MAX_RESUBMISSIONS,
+            MAX_IN_FLIGHT,
+            || async { Ok(100) },
+            |item, nonce| {
+                started.lock().unwrap().push((item, nonce));
+                let (in_flight, most_in_flight) = (&in_flight, &most_in_flight);
+                async move {
+                    in_flight.set(in_flight.get() + 1);
+                    most_in_flight.set(most_in_flight.get().max(in_flight.get()));
+                    // Later submissions finish first
+                    tokio::time::sleep(Duration::from_millis(10 - item)).await;
+                    in_flight.set(in_flight.get() - 1);
+                    if item == 4 {
+                        Err(SubmitError::Timeout)
+                    } else {
+                        Ok(())
+                    }
+                }
+            },
+        )
+        .await;
+
+        assert_eq!(most_in_flight.get(), MAX_IN_FLIGHT);
+        // Nonces are assigned in item order, and results are returned in item order
+        assert_eq!(
+            *started.lock().unwrap(),
+            (0..10).zip(100..).collect::<Vec<_>>()
+        );
+        for (item, result) in results.iter().enumerate() {
+            assert_eq!(result.is_err(), item == 4);
+        }
+    }
+
+    fn module_error(pallet: &str, error: &str) -> SubmitError {
+        SubmitError::DispatchModule {
+            pallet: pallet.into(),
+            error: error.into(),
+        }
+    }
+
+    #[test]
+    fn permanent_submit_errors() {
+        for error in [
+            "ConvertToStringFailed",
+            "DeserializeFail",
+            "BlockHashesDoNotMatch",
+            "VerifyProofFail",
+            "BloomMismatch",
+            "ProofTooOld",
+        ] {
+            assert!(module_error("EthReceiptRegistry", error).is_permanent());
+        }
+        assert!(SubmitError::AlreadyProcessed.is_permanent());
+
+        for error in [
+            "HeaderHashDoesNotExist",
+            "Paused",
+            "NoMonitoredAddressesForChain",
+            "RelayerNotAllowed",
+        ] {
+            assert!(!module_error("EthReceiptRegistry", error).is_permanent());
+        }
+        assert!(!module_error("Balances", "InsufficientBalance").is_permanent());
+        assert!(!SubmitError::Transport("connection closed".into()).is_permanent());
+        assert!(!SubmitError::Timeout.is_permanent());
+        assert!(!SubmitError::Rejected("invalid").is_permanent());
+
+        assert_eq!(
+            module_error("EthReceiptRegistry", "VerifyProofFail").to_string(),
+            "extrinsic failed with an error: EthReceiptRegistry::VerifyProofFail"
+        );
+    }
+
+    #[tokio::test]
+    async fn recently_checked_receipts_are_not_fetched() {
+        let mut cache = ProvedCache::default();
+        let fetches = Cell::new(0);
+        let fetch = |proved: bool| {
+            let fetches = &fetches;
+            move || {
+                fetches.set(fetches.get() + 1);
+                async move { Ok(proved) }
+            }
+        };
+        let key = (5, types::H256([1; 32]));
+        let now = Duration::from_secs(1_000);
+
+        assert!(!cache.check(key, now, fetch(false)).await.unwrap());
+        // Answered locally within the TTL, even though the receipt got proven meanwhile
+        let later = now + UNPROVED_RECEIPT_CACHE_TTL / 2;
+        assert!(!cache.check(key, later, fetch(true)).await.unwrap());
+        assert_eq!(fetches.get(), 1);
+
+        // Checked again once the TTL is over, and kept for good once proven
+        let expired = now + UNPROVED_RECEIPT_CACHE_TTL;
+        assert!(cache.check(key, expired, fetch(true)).await.unwrap());
+        assert!(cache
+            .check(
+                key,
+                expired + UNPROVED_RECEIPT_CACHE_TTL * 100,
+                fetch(false)
+            )
+            .await
+            .unwrap());
+        assert_eq!(fetches.get(), 2);
+
+        // Failed checks are not cached
+        let other = (5, types::H256([2; 32]));
+        assert!(cache
+            .check(other, now, || async { Err(eyre::eyre!("offline")) })
+            .await
+            .is_err());
+        assert!(!cache.check(other, now, fetch(false)).await.unwrap());
+        assert_eq!(fetches.get(), 3);
+    }
+
+    #[tokio::test]
+    async fn watched_addresses_are_fetched_again_after_ttl() {
+        let mut default = WatchedAddressesCache::new(DEFAULT_WATCHED_ADDRESSES_TTL);
+        let mut short = WatchedAddressesCache::new(Duration::from_secs(10));
+        let fetches = Cell::new(0);
+        let fetch = |address: u8| {
+            let fetches = &fetches;
+            move || {
+                fetches.set(fetches.get() + 1);
+                async move { Ok(vec![H160([address; 20])]) }
+            }
+        };
+        let now = Duration::from_secs(1_000);
+        let later = now + Duration::from_secs(30);
+
+        assert_eq!(
+            default.get(5, now, fetch(1)).await.unwrap(),
+            vec![H160([1; 20])]
+        );
+        assert_eq!(
+            default.get(5, later, fetch(2)).await.unwrap(),
+            vec![H160([1; 20])]
+        );
+        assert_eq!(fetches.get(), 1);
+
+        // The same 30 seconds are past a short TTL, picking up the changed list
+        assert_eq!(
+            short.get(5, now, fetch(1)).await.unwrap(),
+            vec![H160([1; 20])]
+        );
+        assert_eq!(
+            short.get(5, later, fetch(2)).await.unwrap(),
+            vec![H160([2; 20])]
+        );
+        assert_eq!(fetches.get(), 3);
+
+        // Other chains are fetched on their own
+        assert_eq!(
+            short.get(1, later, fetch(3)).await.unwrap(),
+            vec![H160([3; 20])]
+        );
+        assert_eq!(fetches.get(), 4);
+    }
+}
@@ -0,0 +1,223 @@
+use std::fmt;
+
+use ethers::providers::{Http, JsonRpcClient, Middleware, Provider};
+use types::{BlockHeaderWithTransaction, H256};
+
+use crate::common::{convert_ethers_block, load_helios_config};
+use crate::config::VerifyBlockArgs;
+use crate::db::DB;
+
+/// A field of a stored block that differs from the block the node reports.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Divergence {
+    pub field: &'static str,
+    pub stored: String,
+    pub live: String,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:<18} stored {}, live {}",
+            self.field, self.stored, self.live
+        )
+    }
+}
+
+/// Compares the block stored at `args.height` with the live block of the execution node and
+/// prints the fields that differ. Fails if any does.
+pub async fn run(args: &VerifyBlockArgs) -> eyre::Result<()> {
+    // Opening a missing database would create an empty one
+    if !args.database.join("db.sqlite").try_exists()? {
+        return Err(eyre::eyre!(
+            "no relayer database in {}",
+            args.database.display()
+        ));
+    }
+    let helios_config = load_helios_config(&args.network, &args.helios_config_path)?;
+    let provider =
+        Provider::<Http>::try_from(helios_config.execution_rpc.as_str()).map_err(|err| {
+            eyre::eyre!(
+                "Failed to connect to execution RPC at {} with error: {}",
+                helios_config.execution_rpc,
+                err
+            )
+        })?;
+
+    let db = DB::new(&args.database)?;
+    let divergences = verify_block(&db, &provider, args.height).await;
+    db.close()?;
+    let divergences = divergences?;
+
+    if divergences.is_empty() {
+        println!("block {} matches the node", args.height);
+        return Ok(());
+    }
+    println!("block {} diverges from the node:", args.height);
+    for divergence in &divergences {
+        println!("  {divergence}");
+    }
+    Err(eyre::eyre!(
+        "block {} diverges from the node in {} fields",
+        args.height,
+        divergences.len()
+    ))
+}
+
+/// Fetches the block at `height` from `provider` and compares it with the stored one.
+pub(crate) async fn verify_block<P: JsonRpcClient>(
+    db: &DB,
+    provider: &Provider<P>,
+    height: u64,
+) -> eyre::Result<Vec<Divergence>> {
+    let (stored_hash, stored) = db
+        .select_block_by_height(height)?
+        .ok_or_else(|| eyre::eyre!("no stored block at height {height}"))?;
+    let live = provider
+        .get_block(height)
+        .await?
+        .ok_or_else(|| eyre::eyre!("the node has no block at height {height}"))?;
+    let live_hash = H256(
+        live.hash
+            .ok_or_else(|| eyre::eyre!("block {height} from the node has no hash"))?
+            .0,
+    );
+    let live = convert_ethers_block(live)?;
+
+    Ok(divergences(stored_hash, &stored, live_hash, &live))
+}
+
+/// The hash and the header fields a reorg or a corrupt row changes, where they differ.
+fn divergences(
+    stored_hash: H256,
+    stored: &BlockHeaderWithTransaction,
+    live_hash: H256,
+    live: &BlockHeaderWithTransaction,
+) -> Vec<Divergence> {
+    let hash = |hash: &H256| format!("0x{}", hex::encode(hash.0));
+    let (stored, live) = (&stored.header, &live.header);
+    [
+        ("block_hash", hash(&stored_hash), hash(&live_hash)),
+        // The stored header has to hash to the stored hash as well
+        ("header_hash", hash(&stored.hash()), hash(&live_hash)),
+        ("number", stored.number.to_string(), live.number.to_string()),
+        (
+            "parent_hash",
+            hash(&stored.parent_hash),
+            hash(&live.parent_hash),
+        ),
+        (
+            "state_root",
+            hash(&stored.state_root),
+            hash(&live.state_root),
+        ),
+        (
+            "transactions_root",
+            hash(&stored.transactions_root),
+            hash(&live.transactions_root),
+        ),
+        (
+            "receipts_root",
+            hash(&stored.receipts_root),
+            hash(&live.receipts_root),
+        ),
+        (
+            "logs_bloom",
+            format!("0x{}", hex::encode(stored.logs_bloom.as_bytes())),
+            format!("0x{}", hex::encode(live.logs_bloom.as_bytes())),
+        ),
+        (
+            "timestamp",
+            stored.timestamp.to_string(),
+            live.timestamp.to_string(),
+        ),
+    ]
+    .into_iter()
+    .filter(|(_, stored, live)| stored != live)
+    .map(|(field, stored, live)| Divergence {
+        field,
+        stored,
+        live,
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::providers::Provider;
+    use tempfile::tempdir;
+    use types::H256;
+
+    use super::{verify_block, Divergence};
+    use crate::common::convert_ethers_block;
+    use crate::db::DB;
+
+    fn live_block() -> ethers::types::Block<ethers::types::H256> {
+        serde_json::from_str(include_str!("../../merkle/tests/suits/block_17819525.json")).unwrap()
+    }
+
+    #[tokio::test]
+    async fn stored_block_matches_node() {
+        let dir = tempdir().unwrap();
+        let db = DB::new(dir.path()).unwrap();
+        db.create_tables().unwrap();
+        let live = live_block();
+        let hash = H256(live.hash.unwrap().0);
+        let height = live.number.unwrap().as_u64();
+        db.insert_block(
+            height,
+            hash,
+            convert_ethers_block(live.clone()).unwrap(),
+            true,
+        )
+        .unwrap();
+
+        let (provider, mock) = Provider::mocked();
+        mock.push(live).unwrap();
+        assert_eq!(verify_block(&db, &provider, height).await.unwrap(), []);
+
+        // Only stored blocks can be verified
+        assert!(verify_block(&db, &provider, height + 1).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn stored_block_of_another_fork_diverges() {
+        let dir = tempdir().unwrap();
+        let db = DB::new(dir.path()).unwrap();
+        db.create_tables().unwrap();
+        let live = live_block();
+        let height = live.number.unwrap().as_u64();
+        // The relayer stored a sibling of the block, which was reorged out
+        let mut stored = convert_ethers_block(live.clone()).unwrap();
+        stored.header.state_root = H256([0x11; 32]);
+        let stored_hash = stored.header.hash();
+        db.insert_block(height, stored_hash, stored.clone(), true)
+            .unwrap();
+
+        let (provider, mock) = Provider::mocked();
+        mock.push(live.clone()).unwrap();
+        let live_hash = format!("0x{}", hex::encode(live.hash.unwrap().0));
+        let stored_hash = format!("0x{}", hex::encode(stored_hash.0));
+        assert_eq!(
+            verify_block(&db, &provider, height).await.unwrap(),
+            [
+                Divergence {
+                    field: "block_hash",
+                    stored: stored_hash.clone(),
+                    live: live_hash.clone(),
+                },
+                Divergence {
+                    field: "header_hash",
+                    stored: stored_hash,
+                    live: live_hash,
+                },
+                Divergence {
+                    field: "state_root",
+                    stored: format!("0x{}", "11".repeat(32)),
+                    live: format!("0x{}", hex::encode(live.state_root.0)),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,62 @@
+use std::process::{Command, Output};
+
+use types::EventProof;
+
+const SUITS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../merkle/tests/suits");
+
+fn build_proof(index: &str) -> Output {
+    build_block_proof(17819525, index)
+}
+
+fn build_block_proof(block: u64, index: &str) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_eth-transaction-receipt-relayer"))
+        .arg("build-proof")
+        .args(["--block", &format!("{SUITS}/block_{block}.json")])
+        .args([
+            "--receipts",
+            &format!("{SUITS}/block_{block}_receipts.json"),
+        ])
+        .args(["--index", index])
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn build_proof_prints_valid_proof() {
+    let output = build_proof("3");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let proof: EventProof = serde_json::from_slice(&output.stdout).unwrap();
+    proof.validate().unwrap();
+    assert_eq!(proof.block_header.number, 17819525);
+    assert_eq!(proof.merkle_proof_of_receipt.transaction_index(), Some(3));
+}
+
+#[test]
+fn build_proof_fails_for_missing_receipt() {
+    let output = build_proof("100000");
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+}
+
+/// The pallet tests submit this proof, so it has to stay what the relayer builds for the block.
+#[test]
+fn build_proof_matches_pallet_fixture() {
+    let output = build_block_proof(8652100, "0");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let built: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let fixture: serde_json::Value = serde_json::from_str(include_str!(
+        "../../pallet/tests/data/goerli/event_proof_8652100.json"
+    ))
+    .unwrap();
+    assert_eq!(built, fixture);
+}
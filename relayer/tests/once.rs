@@ -0,0 +1,44 @@
+use std::process::{Command, Stdio};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+const HELIOS_CONFIG: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../helios.toml");
+
+/// A `--once` run has to exit instead of polling, even if it can't reach any node.
+#[test]
+fn once_terminates_after_a_pass() {
+    let dir = tempfile::tempdir().unwrap();
+    let substrate_config = dir.path().join("ggxchain-config.toml");
+    std::fs::write(
+        &substrate_config,
+        "is_dev = true\nws_url = \"ws://127.0.0.1:1\"\n",
+    )
+    .unwrap();
+
+    let mut relayer = Command::new(env!("CARGO_BIN_EXE_eth-transaction-receipt-relayer"))
+        .arg("--once")
+        .args(["--network", "goerli"])
+        .arg("--database")
+        .arg(dir.path().join("db"))
+        .arg("--substrate-config-path")
+        .arg(&substrate_config)
+        .args(["--helios-config-path", HELIOS_CONFIG])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = relayer.try_wait().unwrap() {
+            break status;
+        }
+        if started.elapsed() > Duration::from_secs(60) {
+            relayer.kill().unwrap();
+            panic!("the relayer is still running after a single pass");
+        }
+        sleep(Duration::from_millis(100));
+    };
+    // The pass failed, which is not the exit code of proofs left to retry
+    assert_eq!(status.code(), Some(1));
+}
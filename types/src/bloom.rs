@@ -1,4 +1,4 @@
-use crate::H160;
+use crate::{Log, H160, H256};
 use alloy_rlp::Encodable;
 
 #[derive(Debug, PartialEq, Clone)]
@@ -9,9 +9,30 @@ impl Bloom {
         Self(ethbloom::Bloom(bytes))
     }
 
+    /// The 2048 bits of the bloom.
+    pub fn as_bytes(&self) -> &[u8; 256] {
+        &self.0 .0
+    }
+
+    /// The bloom of the addresses and topics of `logs`, as computed for a receipt.
+    pub fn from_logs(logs: &[Log]) -> Self {
+        let mut bloom = ethbloom::Bloom::default();
+        for log in logs {
+            bloom.accrue(ethbloom::Input::Raw(&log.address.0));
+            for topic in &log.topics {
+                bloom.accrue(ethbloom::Input::Raw(&topic.0));
+            }
+        }
+        Self(bloom)
+    }
+
     pub fn check_address(&self, address: &H160) -> bool {
         self.0.contains_input(ethbloom::Input::Raw(&address.0))
     }
+
+    pub fn check_topic(&self, topic: &H256) -> bool {
+        self.0.contains_input(ethbloom::Input::Raw(&topic.0))
+    }
 }
 
 impl Encodable for Bloom {
@@ -19,3 +40,24 @@ impl Encodable for Bloom {
         self.0 .0.encode(out)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Bloom;
+    use crate::{H160, H256};
+
+    #[test]
+    fn check_address_and_topic() {
+        let address = H160([1; 20]);
+        let topic = H256([2; 32]);
+        let mut bloom = ethbloom::Bloom::default();
+        bloom.accrue(ethbloom::Input::Raw(&address.0));
+        bloom.accrue(ethbloom::Input::Raw(&topic.0));
+        let bloom = Bloom(bloom);
+
+        assert!(bloom.check_address(&address));
+        assert!(bloom.check_topic(&topic));
+        assert!(!bloom.check_topic(&H256([3; 32])));
+        assert!(!bloom.check_address(&H160([3; 20])));
+    }
+}
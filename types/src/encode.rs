@@ -1,5 +1,7 @@
+#[cfg(feature = "proofs")]
 use alloy_rlp::BufMut;
 
+#[cfg(feature = "proofs")]
 use crate::H256;
 
 #[macro_export]
@@ -23,6 +25,7 @@ macro_rules! encode {
 }
 
 /// Given an RLP encoded node, returns either RLP(node) or RLP(keccak(RLP(node)))
+#[cfg(feature = "proofs")]
 pub fn rlp_node(rlp: &[u8], out: &mut dyn BufMut) {
     if rlp.len() < 32 {
         out.put_slice(rlp);
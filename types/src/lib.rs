@@ -1,13 +1,14 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
-#[macro_use]
+#[cfg_attr(any(feature = "serde", feature = "proofs"), macro_use)]
 extern crate alloc;
 
 mod receipt;
+#[cfg(feature = "proofs")]
 pub use receipt::{
-    BranchNode, ExtensionNode, Leaf, Log, MerkleProof, MerkleProofNode, Nibbles, Receipt,
-    TransactionReceipt, TxType,
+    BranchNode, ExtensionNode, InvalidNibble, Leaf, MerkleProof, MerkleProofNode, Nibbles,
 };
+pub use receipt::{Log, Receipt, TransactionReceipt, TxType, UnknownTxType};
 
 mod primitives;
 pub use primitives::{H160, H256, H64, U256};
@@ -20,10 +21,15 @@ pub use bloom::Bloom;
 
 pub(crate) mod encode;
 
+#[cfg(feature = "proofs")]
 pub mod encoding {
     pub use crate::receipt::LeafEncoder;
 }
 
+#[cfg(feature = "merkle-proof")]
+pub mod merkle;
+
+#[cfg(feature = "proofs")]
 #[derive(Debug)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EventProof {
@@ -56,28 +62,67 @@ pub struct EventProof {
 }
 
 /// Error type for validating `EventProofTransaction`s.
+#[cfg(feature = "proofs")]
 #[derive(Debug)]
 pub enum ValidationError {
-    IncorrectBodyHash { expected: H256, actual: H256 },
-    IncorrectReceiptHash { expected: H256, actual: H256 },
-    IncorrectReceiptRoot { expected: H256, actual: H256 },
+    IncorrectBodyHash {
+        expected: H256,
+        actual: H256,
+    },
+    IncorrectReceiptHash {
+        expected: H256,
+        actual: H256,
+    },
+    IncorrectReceiptRoot {
+        expected: H256,
+        actual: H256,
+        transaction_index: usize,
+    },
+    InvalidProofKey,
+    ProofPathMismatch {
+        transaction_index: usize,
+    },
+    /// The bloom of the receipt isn't the bloom of its logs. The bloom is hashed into the receipt,
+    /// so such a proof wouldn't reconstruct the receipts root either, this names the cause.
+    BloomMismatch {
+        transaction_index: usize,
+    },
+    TransactionIndexOutOfBounds {
+        index: usize,
+        len: usize,
+    },
 }
 
+#[cfg(feature = "proofs")]
 impl EventProof {
     /// Check that the `EventProofTransaction` is valid.
     pub fn validate(&self) -> Result<(), ValidationError> {
-        if self.block_hash != H256::hash(&self.block_header) {
+        let mut buf = alloc::vec::Vec::new();
+        let block_hash = H256::hash_with_buffer(&self.block_header, &mut buf);
+        if self.block_hash != block_hash {
             return Err(ValidationError::IncorrectBodyHash {
                 expected: self.block_hash,
-                actual: H256::hash(&self.block_header),
+                actual: block_hash,
             });
         }
-        if self.transaction_receipt_hash != H256::hash(&self.transaction_receipt) {
+        let receipt_hash = H256::hash_with_buffer(&self.transaction_receipt, &mut buf);
+        if self.transaction_receipt_hash != receipt_hash {
             return Err(ValidationError::IncorrectReceiptHash {
                 expected: self.transaction_receipt_hash,
-                actual: H256::hash(&self.transaction_receipt),
+                actual: receipt_hash,
             });
         }
+        let transaction_index = self
+            .merkle_proof_of_receipt
+            .transaction_index()
+            .ok_or(ValidationError::InvalidProofKey)?;
+        if !self.merkle_proof_of_receipt.follows_key_path() {
+            return Err(ValidationError::ProofPathMismatch { transaction_index });
+        }
+        // Much cheaper than reconstructing the root
+        if !self.transaction_receipt.verify_bloom() {
+            return Err(ValidationError::BloomMismatch { transaction_index });
+        }
         if self.block_header.receipts_root
             != self
                 .merkle_proof_of_receipt
@@ -88,8 +133,191 @@ impl EventProof {
                 actual: self
                     .merkle_proof_of_receipt
                     .merkle_root(&self.transaction_receipt),
+                transaction_index,
             });
         }
         Ok(())
     }
+
+    /// Builds a validated proof that `receipts[index]` is included in the block.
+    ///
+    /// `receipts` must contain every receipt of the block, in transaction order.
+    #[cfg(feature = "merkle-proof")]
+    pub fn build(
+        block_header: BlockHeader,
+        block_hash: H256,
+        receipts: &[TransactionReceipt],
+        index: usize,
+    ) -> Result<Self, ValidationError> {
+        let merkle_proof_of_receipt = MerkleProof::from_transactions(receipts, index)?;
+        let transaction_receipt = receipts[index].clone();
+        let event_proof = EventProof {
+            block_header,
+            block_hash,
+            transaction_receipt_hash: H256::hash(&transaction_receipt),
+            transaction_receipt,
+            merkle_proof_of_receipt,
+        };
+        event_proof.validate()?;
+        Ok(event_proof)
+    }
+}
+
+#[cfg(all(test, feature = "serde", feature = "proofs"))]
+mod tests {
+    use crate::{
+        BlockHeader, Bloom, EventProof, MerkleProof, Receipt, TransactionReceipt, TxType, H160,
+        H256, U256,
+    };
+
+    fn event_proof() -> EventProof {
+        EventProof {
+            block_header: BlockHeader {
+                parent_hash: H256([1; 32]),
+                ommers_hash: H256([2; 32]),
+                beneficiary: H160([3; 20]),
+                state_root: H256([4; 32]),
+                transactions_root: H256([5; 32]),
+                receipts_root: H256([6; 32]),
+                withdrawals_root: Some(H256([7; 32])),
+                logs_bloom: Bloom::new([0; 256]),
+                difficulty: U256::from(0x020000),
+                number: 1,
+                gas_limit: 2,
+                gas_used: 3,
+                timestamp: 4,
+                mix_hash: H256([8; 32]),
+                nonce: 0,
+                base_fee_per_gas: Some(5),
+                blob_gas_used: None,
+                excess_blob_gas: None,
+                parent_beacon_block_root: None,
+                requests_hash: None,
+                extra_data: vec![],
+            },
+            block_hash: H256([0xab; 32]),
+            transaction_receipt: TransactionReceipt {
+                bloom: Bloom::new([0; 256]),
+                receipt: Receipt {
+                    tx_type: TxType::EIP1559,
+                    success: true,
+                    cumulative_gas_used: 21000,
+                    logs: vec![],
+                },
+            },
+            transaction_receipt_hash: H256([10; 32]),
+            merkle_proof_of_receipt: MerkleProof::default(),
+        }
+    }
+
+    #[test]
+    fn event_proof_serde_uses_hex_hashes() {
+        let proof = event_proof();
+        let encoded = serde_json::to_string(&proof).unwrap();
+        assert!(encoded.contains(&format!("\"block_hash\":\"0x{}\"", "ab".repeat(32))));
+
+        // Hashes used to be encoded as arrays of integers.
+        assert!(!encoded.contains("[171,171,"));
+        assert!(
+            serde_json::to_string(&proof.block_hash).unwrap().len()
+                < serde_json::to_string(&proof.block_hash.0).unwrap().len()
+        );
+
+        let decoded: EventProof = serde_json::from_str(&encoded).unwrap();
+        assert_eq!(decoded.block_header, proof.block_header);
+        assert_eq!(decoded.block_hash, proof.block_hash);
+        assert_eq!(decoded.transaction_receipt, proof.transaction_receipt);
+        assert_eq!(
+            decoded.transaction_receipt_hash,
+            proof.transaction_receipt_hash
+        );
+        assert_eq!(
+            decoded.merkle_proof_of_receipt,
+            proof.merkle_proof_of_receipt
+        );
+    }
+
+    #[cfg(feature = "merkle-proof")]
+    #[test]
+    fn receipt_root_mismatch_carries_index() {
+        use crate::ValidationError;
+
+        let receipts: Vec<_> = (0..5)
+            .map(|i| TransactionReceipt {
+                bloom: Bloom::new([0; 256]),
+                receipt: Receipt {
+                    tx_type: TxType::EIP1559,
+                    success: true,
+                    cumulative_gas_used: 21000 * (i + 1),
+                    logs: vec![],
+                },
+            })
+            .collect();
+        let mut block_header = event_proof().block_header;
+        block_header.receipts_root = MerkleProof::from_transactions(&receipts, 0)
+            .unwrap()
+            .merkle_root(&receipts[0]);
+        let block_hash = block_header.hash();
+
+        let mut proof = EventProof::build(block_header, block_hash, &receipts, 3).unwrap();
+        proof.transaction_receipt.receipt.cumulative_gas_used += 1;
+        proof.transaction_receipt_hash = H256::hash(&proof.transaction_receipt);
+
+        assert!(matches!(
+            proof.validate(),
+            Err(ValidationError::IncorrectReceiptRoot {
+                transaction_index: 3,
+                ..
+            })
+        ));
+    }
+
+    #[cfg(feature = "merkle-proof")]
+    #[test]
+    fn bloom_mismatch_is_rejected_before_root() {
+        use crate::{Log, ValidationError};
+
+        let receipts: Vec<_> = (0..3u8)
+            .map(|i| {
+                let logs = vec![Log {
+                    address: H160([i; 20]),
+                    topics: vec![H256([i; 32])],
+                    data: vec![i],
+                }];
+                TransactionReceipt {
+                    bloom: Bloom::from_logs(&logs),
+                    receipt: Receipt {
+                        tx_type: TxType::EIP1559,
+                        success: true,
+                        cumulative_gas_used: 21000 * (i as u64 + 1),
+                        logs,
+                    },
+                }
+            })
+            .collect();
+        let mut block_header = event_proof().block_header;
+        block_header.receipts_root = MerkleProof::from_transactions(&receipts, 0)
+            .unwrap()
+            .merkle_root(&receipts[0]);
+        let block_hash = block_header.hash();
+
+        let mut proof = EventProof::build(block_header, block_hash, &receipts, 1).unwrap();
+        // A bloom hiding the log, consistent with the receipt hash
+        proof.transaction_receipt.bloom = Bloom::new([0; 256]);
+        proof.transaction_receipt_hash = H256::hash(&proof.transaction_receipt);
+
+        // The bloom is part of the proven receipt, the root check alone would reject it
+        assert_ne!(
+            proof
+                .merkle_proof_of_receipt
+                .merkle_root(&proof.transaction_receipt),
+            proof.block_header.receipts_root
+        );
+        assert!(matches!(
+            proof.validate(),
+            Err(ValidationError::BloomMismatch {
+                transaction_index: 1
+            })
+        ));
+    }
 }
@@ -0,0 +1,8 @@
+//! Generation of receipt Merkle proofs from the full list of a block's receipts.
+//!
+//! Only needs `alloc`, so proofs can be built in `no_std` environments as well.
+pub mod node;
+
+mod trie;
+
+pub use trie::{IterativeTrie, PatriciaTrie};
@@ -1,7 +1,7 @@
-use std::cell::RefCell;
-use std::rc::Rc;
+use alloc::{rc::Rc, vec::Vec};
+use core::cell::RefCell;
 
-use types::Nibbles;
+use crate::Nibbles;
 
 #[derive(Debug, Clone, Default)]
 pub enum Node {
@@ -1,11 +1,11 @@
-use std::cell::RefCell;
-use std::convert::TryInto;
-use std::rc::Rc;
+use alloc::{boxed::Box, rc::Rc, vec::Vec};
+use core::cell::RefCell;
+use core::convert::TryInto;
 
+use crate::{MerkleProof, MerkleProofNode, Nibbles, H256};
 use alloy_rlp::EMPTY_STRING_CODE;
-use types::{MerkleProof, MerkleProofNode, Nibbles, H256};
 
-use crate::node::{empty_children, BranchNode, Node};
+use super::node::{empty_children, BranchNode, Node};
 
 pub trait IterativeTrie {
     fn insert(&mut self, key: Vec<u8>, value: Vec<u8>);
@@ -149,6 +149,23 @@ impl PatriciaTrie {
         self.root.clone()
     }
 
+    /// Whether nothing was inserted into the trie.
+    pub fn is_empty(&self) -> bool {
+        matches!(self.root, Node::Empty)
+    }
+
+    /// Root hash of the trie, as in the `receipts_root` of a block. The root of an empty trie is
+    /// the hash of the empty string, and a root node encoded in less than 32 bytes is hashed as
+    /// well.
+    pub fn root_hash(&self) -> H256 {
+        let encoded = self.encode_node(self.root_node());
+        if encoded.len() == 32 {
+            H256::from_slice(&encoded)
+        } else {
+            H256::keccak(&encoded)
+        }
+    }
+
     fn insert_at_iterative(n: Node, partial_key: Nibbles, value: Vec<u8>) -> Node {
         let mut queue = vec![n];
         let mut partial = Clone::clone(&partial_key);
@@ -167,12 +184,12 @@ impl PatriciaTrie {
                 }
                 borrow_node if matches!(borrow_node, Node::Leaf(_)) => {
                     // We will replace the leaf with a branch or extension most likely.
-                    let leaf = std::mem::take(borrow_node);
+                    let leaf = core::mem::take(borrow_node);
                     let leaf = leaf.into_leaf().expect("checked above;");
 
                     let mut borrow_leaf = leaf.borrow_mut();
 
-                    let old_partial = std::mem::take(&mut borrow_leaf.key);
+                    let old_partial = core::mem::take(&mut borrow_leaf.key);
                     let match_index = partial.common_prefix(&old_partial);
 
                     // Key is the same, replace value. But we need to reconstruct it as we took it out.
@@ -193,7 +210,7 @@ impl PatriciaTrie {
                     // Insert old leaf.
                     let n = Node::from_leaf(
                         old_partial.offset(match_index + 1),
-                        std::mem::take(&mut borrow_leaf.value),
+                        core::mem::take(&mut borrow_leaf.value),
                     );
 
                     branch.insert(old_partial.at(match_index), n);
@@ -230,12 +247,12 @@ impl PatriciaTrie {
                     Some(child)
                 }
                 borrow_node if matches!(borrow_node, Node::Extension(_)) => {
-                    let ext = std::mem::take(borrow_node);
+                    let ext = core::mem::take(borrow_node);
                     let ext = ext.into_extension().expect("checked above;");
 
                     let mut borrow_ext = ext.borrow_mut();
 
-                    let prefix = std::mem::take(&mut borrow_ext.prefix);
+                    let prefix = core::mem::take(&mut borrow_ext.prefix);
                     let sub_node = borrow_ext.node.clone();
                     let match_index = partial.common_prefix(&prefix);
 
@@ -352,7 +369,7 @@ impl PatriciaTrie {
                 // Hash leaf node and replace it with hash
                 Node::Leaf(leaf) => {
                     let borrow_leaf = leaf.borrow();
-                    let leaf = types::encoding::LeafEncoder {
+                    let leaf = crate::encoding::LeafEncoder {
                         key: &borrow_leaf.key.encode_compact(),
                         value: &borrow_leaf.value,
                     };
@@ -364,7 +381,7 @@ impl PatriciaTrie {
                 // It means we haven't processed all the children yet.
                 // We push the child to the stack and increase the depth counter.
                 Node::Branch(branch) if depth < 16 => {
-                    let borrow_branch: std::cell::Ref<'_, BranchNode> = branch.borrow();
+                    let borrow_branch: core::cell::Ref<'_, BranchNode> = branch.borrow();
                     stack.push((
                         NodeOrHash::Node {
                             node: borrow_branch.children[depth].clone(),
@@ -378,7 +395,7 @@ impl PatriciaTrie {
                 // We have processed all the children, so we can combine and hash them.
                 Node::Branch(branch) => {
                     let borrow_branch = branch.borrow();
-                    let branch = types::BranchNode {
+                    let branch = crate::BranchNode {
                         branches: stack
                             .drain(counter + 1..counter + 17)
                             .map(|(n, _, _)| match n {
@@ -415,7 +432,7 @@ impl PatriciaTrie {
                 // We have processed the child, so we can hash it.
                 Node::Extension(ext) => {
                     let borrow_ext = ext.borrow();
-                    let extension = types::ExtensionNode::new(
+                    let extension = crate::ExtensionNode::new(
                         borrow_ext.prefix.clone(),
                         H256::from_slice(&match &stack[counter + 1].0 {
                             NodeOrHash::Node { .. } => unreachable!(),
@@ -522,14 +539,44 @@ impl IterativeTrie for PatriciaTrie {
 #[cfg(test)]
 mod tests {
     use cita_trie::Trie;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+    use proptest::sample::select;
     use rand::distributions::Alphanumeric;
     use rand::{thread_rng, Rng};
     use std::collections::HashMap;
     use std::sync::Arc;
 
     use hasher::HasherKeccak;
+    use test_strategy::proptest;
 
     use super::{IterativeTrie, PatriciaTrie};
+    use crate::H256;
+
+    #[test]
+    fn empty_trie_root_hash() {
+        let trie = PatriciaTrie::new();
+        assert!(trie.is_empty());
+        assert_eq!(
+            trie.root_hash(),
+            H256(hex_literal::hex!(
+                "56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421"
+            ))
+        );
+    }
+
+    #[test]
+    fn root_hash_of_populated_trie() {
+        let mut trie = PatriciaTrie::new();
+        for i in 0..100u8 {
+            trie.insert(alloy_rlp::encode(i), vec![i; 40]);
+        }
+        assert!(!trie.is_empty());
+        assert_eq!(
+            trie.root_hash(),
+            H256::from_slice(&trie.encode_node(trie.root_node()))
+        );
+    }
 
     #[test]
     fn recursive_crash_test() {
@@ -569,6 +616,42 @@ mod tests {
         assert_eq!(trie.iter().count(), 1000);
     }
 
+    /// Entries whose keys share a random prefix and then differ in a few bytes drawn from a
+    /// small alphabet, so neighbours often collide on all but the last nibble. All keys have the
+    /// same length and values are at least 32 bytes, as for receipts: no key is a prefix of
+    /// another and no node is small enough to be inlined.
+    fn colliding_entries() -> impl Strategy<Value = Vec<(Vec<u8>, Vec<u8>)>> {
+        const BYTES: [u8; 6] = [0x00, 0x01, 0x0f, 0x10, 0xf0, 0xff];
+
+        (vec(any::<u8>(), 0..32), 1usize..4).prop_flat_map(|(prefix, suffix_len)| {
+            let entry = (
+                vec(select(&BYTES[..]), suffix_len),
+                vec(any::<u8>(), 32..64),
+            )
+                .prop_map(move |(suffix, value)| ([prefix.clone(), suffix].concat(), value));
+            vec(entry, 1..64)
+        })
+    }
+
+    #[proptest]
+    fn test_trie_colliding_keys(#[strategy(colliding_entries())] entries: Vec<(Vec<u8>, Vec<u8>)>) {
+        let mut trie = PatriciaTrie::new();
+        let mut cita_trie = cita_trie::PatriciaTrie::new(
+            Arc::new(cita_trie::MemoryDB::new(true)),
+            Arc::new(HasherKeccak::new()),
+        );
+
+        for (key, value) in entries {
+            trie.insert(key.clone(), value.clone());
+            cita_trie.insert(key, value).unwrap();
+        }
+        prop_assert_eq!(
+            trie.root_hash(),
+            H256::from_slice(&cita_trie.root().unwrap())
+        );
+        prop_assert!(trie.iter().eq(cita_trie.iter()));
+    }
+
     #[test]
     fn iterator_trie() {
         let mut kv = HashMap::new();
@@ -598,13 +681,12 @@ mod tests {
 mod merkle_proof {
     use std::sync::Arc;
 
-    use alloy_rlp::Encodable;
     use cita_trie::{MemoryDB, PatriciaTrie, Trie};
     use hasher::HasherKeccak;
 
-    use types::{Bloom, Receipt, TransactionReceipt, H256};
+    use crate::{Bloom, Receipt, TransactionReceipt, H256};
 
-    use crate::IterativeTrie;
+    use crate::merkle::IterativeTrie;
 
     fn trie_root(iter: impl Iterator<Item = (Vec<u8>, Vec<u8>)>) -> H256 {
         let mut trie =
@@ -619,9 +701,7 @@ mod merkle_proof {
     fn transaction_to_key_value(
         (index, transaction): (usize, TransactionReceipt),
     ) -> (Vec<u8>, Vec<u8>) {
-        let mut vec = vec![];
-        transaction.encode(&mut vec);
-        (alloy_rlp::encode(index), vec)
+        (alloy_rlp::encode(index), transaction.encode_to_vec())
     }
 
     #[test]
@@ -630,7 +710,7 @@ mod merkle_proof {
             .map(|e| TransactionReceipt {
                 bloom: Bloom::new([e; 256]),
                 receipt: Receipt {
-                    tx_type: types::TxType::EIP1559,
+                    tx_type: crate::TxType::EIP1559,
                     logs: vec![],
                     cumulative_gas_used: e as u64,
                     success: true,
@@ -639,7 +719,7 @@ mod merkle_proof {
             .collect();
         const SEARCHIN_INDEX: usize = 55;
         let searching_for = transactions[SEARCHIN_INDEX].clone();
-        let mut trie = crate::PatriciaTrie::new();
+        let mut trie = crate::merkle::PatriciaTrie::new();
         for (k, v) in transactions
             .clone()
             .into_iter()
@@ -661,4 +741,28 @@ mod merkle_proof {
         );
         assert_eq!(root, restored_root);
     }
+
+    #[test]
+    fn single_receipt_merkle_proof() {
+        let transaction = TransactionReceipt {
+            bloom: Bloom::new([1; 256]),
+            receipt: Receipt {
+                tx_type: crate::TxType::EIP1559,
+                logs: vec![],
+                cumulative_gas_used: 21000,
+                success: true,
+            },
+        };
+        let (key, value) = transaction_to_key_value((0, transaction.clone()));
+        let mut trie = crate::merkle::PatriciaTrie::new();
+        trie.insert(key.clone(), value.clone());
+
+        // The leaf is the root, there is no node left to prove it with
+        let proof = trie.merkle_proof(key.clone());
+        assert!(proof.proof.is_empty());
+        assert_eq!(
+            proof.merkle_root(&transaction),
+            trie_root(core::iter::once((key, value)))
+        );
+    }
 }
@@ -3,11 +3,21 @@ use alloy_rlp::{Encodable, RlpEncodableWrapper};
 use keccak_hash::keccak;
 use parity_scale_codec::{Decode, Encode, MaxEncodedLen};
 use scale_info::TypeInfo;
+use subtle::ConstantTimeEq;
 
 #[derive(
-    Debug, RlpEncodableWrapper, PartialEq, Clone, Encode, Decode, TypeInfo, Copy, MaxEncodedLen,
+    Debug,
+    RlpEncodableWrapper,
+    PartialEq,
+    Eq,
+    Hash,
+    Clone,
+    Encode,
+    Decode,
+    TypeInfo,
+    Copy,
+    MaxEncodedLen,
 )]
-#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct H256(pub [u8; 32]);
 
 impl H256 {
@@ -25,11 +35,10 @@ impl H256 {
 #[derive(
     Debug, RlpEncodableWrapper, PartialEq, Clone, Encode, Decode, TypeInfo, Copy, MaxEncodedLen,
 )]
-#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct H64(pub [u8; 8]);
 
-#[derive(Debug, PartialEq, Clone, Encode, Decode, TypeInfo, Copy, MaxEncodedLen)]
-#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+/// 256-bit unsigned integer stored as big-endian bytes.
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode, TypeInfo, Copy, MaxEncodedLen)]
 pub struct U256(pub [u8; 32]);
 
 impl U256 {
@@ -42,6 +51,52 @@ impl U256 {
     pub fn zero() -> Self {
         Self([0u8; 32])
     }
+
+    /// Adds two values, returning `None` if the result doesn't fit into 256 bits.
+    pub fn checked_add(&self, other: &Self) -> Option<Self> {
+        let mut result = [0u8; 32];
+        let mut carry = 0u16;
+        for i in (0..32).rev() {
+            let sum = self.0[i] as u16 + other.0[i] as u16 + carry;
+            result[i] = sum as u8;
+            carry = sum >> 8;
+        }
+        if carry == 0 {
+            Some(Self(result))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the value as `u64`, or `None` if it doesn't fit.
+    pub fn to_u64(&self) -> Option<u64> {
+        if self.0[..24].iter().any(|&b| b != 0) {
+            return None;
+        }
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&self.0[24..]);
+        Some(u64::from_be_bytes(bytes))
+    }
+}
+
+// Bytes are stored big-endian, so lexicographic order is numeric order.
+impl PartialOrd for U256 {
+    fn partial_cmp(&self, other: &Self) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for U256 {
+    fn cmp(&self, other: &Self) -> core::cmp::Ordering {
+        self.0.cmp(&other.0)
+    }
+}
+
+#[cfg(test)]
+impl From<U256> for ethers::types::U256 {
+    fn from(x: U256) -> Self {
+        ethers::types::U256::from_big_endian(&x.0)
+    }
 }
 
 impl Encodable for U256 {
@@ -66,7 +121,6 @@ impl From<u64> for U256 {
 #[derive(
     Debug, RlpEncodableWrapper, PartialEq, Clone, Encode, Decode, TypeInfo, Copy, MaxEncodedLen,
 )]
-#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct H160(pub [u8; 20]);
 
 impl H160 {
@@ -79,6 +133,12 @@ impl H160 {
         bytes[..slice.len()].copy_from_slice(slice);
         Self(bytes)
     }
+
+    /// Equality in constant time, unlike `==` which stops at the first differing byte. Used to
+    /// match watched addresses against the logs of submitted receipts.
+    pub fn ct_eq(&self, other: &Self) -> bool {
+        self.0[..].ct_eq(&other.0[..]).into()
+    }
 }
 
 impl H256 {
@@ -86,16 +146,150 @@ impl H256 {
     where
         T: Encodable,
     {
-        let mut rlp = Vec::new();
-        x.encode(&mut rlp);
-        Self(keccak(&rlp).into())
+        Self::hash_with_buffer(x, &mut Vec::new())
+    }
+
+    /// Like [`H256::hash`], but encodes into `buf` (cleared first), so hashing in a loop
+    /// allocates only until the buffer fits the largest value.
+    pub fn hash_with_buffer<T>(x: T, buf: &mut Vec<u8>) -> Self
+    where
+        T: Encodable,
+    {
+        buf.clear();
+        x.encode(buf);
+        Self::keccak(buf)
+    }
+
+    /// Keccak-256 of `bytes`.
+    pub fn keccak(bytes: &[u8]) -> Self {
+        Self(keccak(bytes).into())
+    }
+
+    /// Equality in constant time, unlike `==` which stops at the first differing byte.
+    pub fn ct_eq(&self, other: &Self) -> bool {
+        self.0[..].ct_eq(&other.0[..]).into()
+    }
+}
+
+/// Serde support encoding hashes and integers as `0x`-prefixed hex strings, as in Ethereum JSON-RPC.
+///
+/// Deserialization also accepts the plain byte-array form the derived impls used to produce, so
+/// previously stored JSON keeps loading.
+#[cfg(feature = "serde")]
+mod serde_hex {
+    use alloc::string::String;
+    use core::fmt;
+
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::{H160, H256, H64, U256};
+
+    const HEX_CHARS: &[u8; 16] = b"0123456789abcdef";
+
+    fn to_hex(bytes: &[u8], skip_leading_zeros: bool) -> String {
+        let mut hex = String::with_capacity(2 + bytes.len() * 2);
+        hex.push_str("0x");
+        for byte in bytes {
+            hex.push(HEX_CHARS[(byte >> 4) as usize] as char);
+            hex.push(HEX_CHARS[(byte & 0x0f) as usize] as char);
+        }
+        if skip_leading_zeros {
+            let digits = hex[2..].trim_start_matches('0');
+            let digits = if digits.is_empty() { "0" } else { digits };
+            return format!("0x{digits}");
+        }
+        hex
+    }
+
+    fn nibble(c: u8) -> Option<u8> {
+        match c {
+            b'0'..=b'9' => Some(c - b'0'),
+            b'a'..=b'f' => Some(c - b'a' + 10),
+            b'A'..=b'F' => Some(c - b'A' + 10),
+            _ => None,
+        }
+    }
+
+    /// Decodes a hex string into `N` bytes. Shorter inputs are only allowed for quantities and
+    /// are right-aligned.
+    fn from_hex<const N: usize>(value: &str, allow_short: bool) -> Result<[u8; N], &'static str> {
+        let digits = value.strip_prefix("0x").unwrap_or(value).as_bytes();
+        if digits.len() > N * 2 || (!allow_short && digits.len() != N * 2) {
+            return Err("invalid hex length");
+        }
+
+        let mut bytes = [0u8; N];
+        let offset = N * 2 - digits.len();
+        for (i, &c) in digits.iter().enumerate() {
+            let nibble = nibble(c).ok_or("invalid hex character")?;
+            let position = offset + i;
+            bytes[position / 2] |= if position % 2 == 0 {
+                nibble << 4
+            } else {
+                nibble
+            };
+        }
+        Ok(bytes)
+    }
+
+    struct HexVisitor<const N: usize> {
+        quantity: bool,
+    }
+
+    impl<'de, const N: usize> de::Visitor<'de> for HexVisitor<N> {
+        type Value = [u8; N];
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            write!(formatter, "a 0x-prefixed hex string of up to {N} bytes")
+        }
+
+        fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+            from_hex(value, self.quantity).map_err(E::custom)
+        }
+
+        fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut bytes = [0u8; N];
+            for (i, byte) in bytes.iter_mut().enumerate() {
+                *byte = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(i, &self))?;
+            }
+            Ok(bytes)
+        }
     }
+
+    macro_rules! impl_hex_serde {
+        ($name:ident, $len:expr, $quantity:expr) => {
+            impl Serialize for $name {
+                fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                    serializer.serialize_str(&to_hex(&self.0, $quantity))
+                }
+            }
+
+            impl<'de> Deserialize<'de> for $name {
+                fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                    deserializer
+                        .deserialize_any(HexVisitor::<$len> {
+                            quantity: $quantity,
+                        })
+                        .map($name)
+                }
+            }
+        };
+    }
+
+    impl_hex_serde!(H256, 32, false);
+    impl_hex_serde!(H160, 20, false);
+    impl_hex_serde!(H64, 8, false);
+    impl_hex_serde!(U256, 32, true);
 }
 
 #[cfg(test)]
 mod tests {
     use test_strategy::proptest;
 
+    use super::{H256, U256};
+
     #[proptest]
     fn test_from_u64(a: u64) {
         let u256 = super::U256::from(a);
@@ -104,4 +298,120 @@ mod tests {
 
         assert_eq!(u256.0, ethers_u256);
     }
+
+    #[proptest]
+    fn test_ordering(a: [u8; 32], b: [u8; 32]) {
+        let (a, b) = (U256(a), U256(b));
+        let (ethers_a, ethers_b): (ethers::types::U256, ethers::types::U256) = (a.into(), b.into());
+
+        assert_eq!(a.cmp(&b), ethers_a.cmp(&ethers_b));
+    }
+
+    #[proptest]
+    fn test_checked_add(a: [u8; 32], b: [u8; 32]) {
+        let (a, b) = (U256(a), U256(b));
+        let (ethers_a, ethers_b): (ethers::types::U256, ethers::types::U256) = (a.into(), b.into());
+
+        let sum = a.checked_add(&b).map(ethers::types::U256::from);
+        assert_eq!(sum, ethers_a.checked_add(ethers_b));
+    }
+
+    #[proptest]
+    fn test_ct_eq(a: [u8; 32], b: [u8; 32], flipped: usize) {
+        use super::H160;
+
+        assert_eq!(H256(a).ct_eq(&H256(b)), H256(a) == H256(b));
+        assert!(H256(a).ct_eq(&H256(a)));
+        let mut near = a;
+        near[flipped % 32] ^= 1;
+        assert!(!H256(a).ct_eq(&H256(near)));
+
+        let (a, b) = (H160::from_slice(&a[..20]), H160::from_slice(&b[..20]));
+        assert_eq!(a.ct_eq(&b), a == b);
+        assert!(a.ct_eq(&a));
+    }
+
+    #[proptest]
+    fn test_to_u64(a: u64, high: [u8; 24]) {
+        assert_eq!(U256::from(a).to_u64(), Some(a));
+
+        let mut bytes = U256::from(a).0;
+        bytes[..24].copy_from_slice(&high);
+        let expected = high.iter().all(|&b| b == 0).then_some(a);
+        assert_eq!(U256(bytes).to_u64(), expected);
+    }
+
+    #[test]
+    fn serde_hex_strings() {
+        use super::{H160, H256};
+
+        let hash = H256([0xab; 32]);
+        let encoded = serde_json::to_string(&hash).unwrap();
+        assert_eq!(encoded, format!("\"0x{}\"", "ab".repeat(32)));
+        assert!(encoded.len() < serde_json::to_string(&hash.0).unwrap().len());
+        assert_eq!(serde_json::from_str::<H256>(&encoded).unwrap(), hash);
+
+        let address = H160([0x01; 20]);
+        let encoded = serde_json::to_string(&address).unwrap();
+        assert_eq!(serde_json::from_str::<H160>(&encoded).unwrap(), address);
+
+        assert_eq!(serde_json::to_string(&U256::zero()).unwrap(), "\"0x0\"");
+        assert_eq!(
+            serde_json::to_string(&U256::from(0x1234)).unwrap(),
+            "\"0x1234\""
+        );
+        assert_eq!(
+            serde_json::from_str::<U256>("\"0x1234\"").unwrap(),
+            U256::from(0x1234)
+        );
+
+        // Wrong length and non-hex input are rejected.
+        assert!(serde_json::from_str::<H160>("\"0x1234\"").is_err());
+        assert!(serde_json::from_str::<H256>(&format!("\"0x{}\"", "zz".repeat(32))).is_err());
+    }
+
+    #[test]
+    fn serde_accepts_legacy_byte_arrays() {
+        use super::H256;
+
+        let hash = H256([7; 32]);
+        let legacy = serde_json::to_string(&hash.0).unwrap();
+        assert_eq!(serde_json::from_str::<H256>(&legacy).unwrap(), hash);
+    }
+
+    #[proptest]
+    fn serde_round_trip(a: [u8; 32]) {
+        let hash = super::H256(a);
+        let encoded = serde_json::to_string(&hash).unwrap();
+        assert_eq!(serde_json::from_str::<super::H256>(&encoded).unwrap(), hash);
+
+        let value = U256(a);
+        let encoded = serde_json::to_string(&value).unwrap();
+        assert_eq!(serde_json::from_str::<U256>(&encoded).unwrap(), value);
+    }
+
+    #[proptest]
+    fn test_hash_with_buffer(values: Vec<Vec<u8>>) {
+        let mut buf = Vec::new();
+        for value in &values {
+            assert_eq!(H256::hash_with_buffer(value, &mut buf), H256::hash(value));
+        }
+    }
+
+    #[test]
+    fn hash_with_buffer_reuses_allocation() {
+        let values: Vec<Vec<u8>> = (0..1000).map(|len| vec![0xab; 1000 - len]).collect();
+        let mut buf = Vec::new();
+        H256::hash_with_buffer(&values[0], &mut buf);
+        let (ptr, capacity) = (buf.as_ptr(), buf.capacity());
+
+        for value in &values {
+            assert_eq!(H256::hash_with_buffer(value, &mut buf), H256::hash(value));
+        }
+        assert_eq!((buf.as_ptr(), buf.capacity()), (ptr, capacity));
+
+        let mut rlp = Vec::new();
+        alloy_rlp::Encodable::encode(&values[0], &mut rlp);
+        assert_eq!(H256::keccak(&rlp), H256::hash(&values[0]));
+    }
 }
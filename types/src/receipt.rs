@@ -1,16 +1,20 @@
 mod log;
+#[cfg(feature = "proofs")]
 mod receipt_merkle_proof;
 mod transaction_receipt;
+#[cfg(feature = "proofs")]
 mod trie;
 mod tx_type;
 
 pub use log::Log;
+#[cfg(feature = "proofs")]
 pub use receipt_merkle_proof::{MerkleProof, MerkleProofNode};
 pub use transaction_receipt::{Receipt, TransactionReceipt};
+#[cfg(feature = "proofs")]
 pub use trie::{
     branch::BranchNode,
     extension::ExtensionNode,
     leaf::{Leaf, LeafEncoder},
-    nibble::Nibbles,
+    nibble::{InvalidNibble, Nibbles},
 };
-pub use tx_type::TxType;
+pub use tx_type::{TxType, UnknownTxType};
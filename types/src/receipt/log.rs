@@ -49,3 +49,109 @@ impl Encodable for Log {
         alloy_rlp::length_of_length(rlp_head.payload_length) + rlp_head.payload_length
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use alloy_rlp::Encodable;
+    use hex_literal::hex;
+
+    use super::Log;
+    use crate::{H160, H256};
+
+    /// RLP of the log as encoded by ethers, whose `Log` keeps the data as `Bytes`.
+    fn reference_encoding(log: &Log) -> Vec<u8> {
+        let log = ethers::types::Log {
+            address: log.address.0.into(),
+            topics: log.topics.iter().map(|topic| topic.0.into()).collect(),
+            data: log.data.clone().into(),
+            ..Default::default()
+        };
+        ethers::utils::rlp::encode(&log).to_vec()
+    }
+
+    fn assert_encoding(log: &Log, expected: &[u8]) {
+        let encoded = alloy_rlp::encode(log);
+        assert_eq!(encoded, expected);
+        assert_eq!(log.length(), expected.len());
+        assert_eq!(encoded, reference_encoding(log));
+    }
+
+    #[test]
+    fn encode_log_without_topics() {
+        // LOG0: an empty topics list, and a data string short enough for a single byte header
+        let log = Log {
+            address: H160(hex!("0000000000000000000000000000000000000011")),
+            topics: vec![],
+            data: hex!("0100ff").to_vec(),
+        };
+        assert_encoding(
+            &log,
+            &hex!("da940000000000000000000000000000000000000011c0830100ff"),
+        );
+
+        // Neither topics nor data
+        let log = Log {
+            data: vec![],
+            ..log
+        };
+        assert_encoding(
+            &log,
+            &hex!("d7940000000000000000000000000000000000000011c080"),
+        );
+    }
+
+    #[test]
+    fn encode_log_with_four_topics() {
+        // LOG4: 4 topics of 33 bytes make a list with a long header
+        let log = Log {
+            address: H160([0x11; 20]),
+            topics: (1..=4).map(|i| H256([i; 32])).collect(),
+            data: vec![0x2a],
+        };
+        let mut expected = hex!("f89c941111111111111111111111111111111111111111f884").to_vec();
+        for i in 1..=4 {
+            expected.push(0xa0);
+            expected.extend([i; 32]);
+        }
+        expected.push(0x2a);
+        assert_encoding(&log, &expected);
+    }
+
+    #[test]
+    fn encode_log_with_large_data() {
+        // 4 KiB of data takes a two byte length in the string header, and the whole log a two
+        // byte length in the list header
+        let data: Vec<u8> = (0..4096).map(|i| i as u8).collect();
+        let log = Log {
+            address: H160([0x11; 20]),
+            topics: vec![H256([0x22; 32])],
+            data: data.clone(),
+        };
+        let mut expected = hex!("f9103a941111111111111111111111111111111111111111e1a0").to_vec();
+        expected.extend([0x22; 32]);
+        expected.extend(hex!("b91000"));
+        expected.extend(data);
+        assert_encoding(&log, &expected);
+    }
+
+    #[test]
+    // Transfer event of the first receipt of mainnet block 17819525
+    fn encode_mainnet_log() {
+        let log = Log {
+            address: H160(hex!("c8ad8ba92bd7ce4f02e89a543582aa3c27957311")),
+            topics: vec![
+                H256(hex!(
+                    "ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef"
+                )),
+                H256(hex!(
+                    "000000000000000000000000d322566e55b0e336cecb710772109e1af9eb6d4e"
+                )),
+                H256(hex!(
+                    "000000000000000000000000c8ad8ba92bd7ce4f02e89a543582aa3c27957311"
+                )),
+            ],
+            data: hex!("000000000000000000000000000000000000000000000000000142249d2df000").to_vec(),
+        };
+        assert_encoding(&log, &hex!("f89b94c8ad8ba92bd7ce4f02e89a543582aa3c27957311f863a0ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3efa0000000000000000000000000d322566e55b0e336cecb710772109e1af9eb6d4ea0000000000000000000000000c8ad8ba92bd7ce4f02e89a543582aa3c27957311a0000000000000000000000000000000000000000000000000000142249d2df000"));
+    }
+}
@@ -1,5 +1,7 @@
 use alloc::{boxed::Box, vec::Vec};
 
+#[cfg(feature = "merkle-proof")]
+use crate::ValidationError;
 use crate::H256;
 
 use super::{
@@ -12,7 +14,7 @@ use super::{
 /// transaction receipts.
 ///
 /// [1]: https://ethereum.org/se/developers/docs/data-structures-and-encoding/patricia-merkle-trie/
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MerkleProofNode {
     /// An extension node in the Patricia Merkle Trie.
@@ -59,8 +61,13 @@ pub enum MerkleProofNode {
 /// Requires a [`ReceiptWithBloom`] to generate a leaf node, and the rest of the proof proceeds
 /// from the leaf node.
 ///
+/// The default proof has no nodes and an empty key, so its [`MerkleProof::merkle_root`] is the
+/// hash of a leaf with an empty path, which is not the root of any receipt trie. In a block with
+/// a single receipt the leaf is the root, and the proof of that receipt is the default one with
+/// the key of transaction `0` set.
+///
 /// [1]: https://ethereum.org/se/developers/docs/data-structures-and-encoding/patricia-merkle-trie/
-#[derive(Debug, PartialEq, Default)]
+#[derive(Debug, PartialEq, Eq, Hash, Default)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MerkleProof {
     pub proof: Vec<MerkleProofNode>,
@@ -68,8 +75,126 @@ pub struct MerkleProof {
 }
 
 impl MerkleProof {
+    /// Builds the proof for the receipt at `index` from all receipts of a block, in transaction
+    /// order.
+    ///
+    /// Fails with [`ValidationError::TransactionIndexOutOfBounds`] if there is no receipt at
+    /// `index`, as the proof would be for a key that isn't in the trie.
+    #[cfg(feature = "merkle-proof")]
+    pub fn from_transactions(
+        receipts: &[TransactionReceipt],
+        index: usize,
+    ) -> Result<Self, ValidationError> {
+        use crate::merkle::{IterativeTrie, PatriciaTrie};
+
+        if index >= receipts.len() {
+            return Err(ValidationError::TransactionIndexOutOfBounds {
+                index,
+                len: receipts.len(),
+            });
+        }
+
+        let mut trie = PatriciaTrie::new();
+        for (i, receipt) in receipts.iter().enumerate() {
+            trie.insert(alloy_rlp::encode(i), receipt.encode_to_vec());
+        }
+        Ok(trie.merkle_proof(alloy_rlp::encode(index)))
+    }
+
+    /// Number of nodes in the proof, not counting the leaf.
+    pub fn len(&self) -> usize {
+        self.proof.len()
+    }
+
+    /// Whether the receipt's leaf is the root of the trie, i.e. the block has a single receipt.
+    pub fn is_empty(&self) -> bool {
+        self.proof.is_empty()
+    }
+
+    /// Number of nibbles of the key path the proof nodes cover before the leaf: one per branch
+    /// and the prefix length of every extension.
+    pub fn depth(&self) -> usize {
+        self.proof
+            .iter()
+            .map(|node| match node {
+                MerkleProofNode::ExtensionNode { prefix } => prefix.len(),
+                MerkleProofNode::BranchNode { .. } => 1,
+            })
+            .sum()
+    }
+
+    /// Bytes of RLP [`MerkleProof::merkle_root`] hashes for the proof nodes, with the child hash
+    /// slotted in, plus the key. Excludes the receipt, which is submitted along with the proof.
+    pub fn encoded_byte_len(&self) -> usize {
+        use alloy_rlp::Encodable;
+
+        let nodes: usize = self
+            .proof
+            .iter()
+            .map(|node| match node {
+                MerkleProofNode::ExtensionNode { prefix } => {
+                    ExtensionNode::new(prefix.clone(), H256([0; 32])).length()
+                }
+                MerkleProofNode::BranchNode {
+                    branches,
+                    value,
+                    index,
+                } => {
+                    let mut branches = *branches.as_ref();
+                    branches[(index & 0x0f) as usize] = Some(H256([0; 32]));
+                    BranchNode {
+                        branches,
+                        value: value.clone(),
+                    }
+                    .length()
+                }
+            })
+            .sum();
+        nodes + self.key.len()
+    }
+
+    /// Decodes the transaction index the proof was built for from the RLP-encoded `key`.
+    ///
+    /// Returns `None` if the key isn't a canonical RLP encoding of an index.
+    pub fn transaction_index(&self) -> Option<usize> {
+        let index = <usize as alloy_rlp::Decodable>::decode(&mut self.key.as_slice()).ok()?;
+        (alloy_rlp::encode(index) == self.key).then_some(index)
+    }
+
+    /// Checks that the proof nodes follow the nibble path of `key`: every extension prefix and
+    /// branch index must match the key, and at least the leaf terminator has to be left for the
+    /// leaf node.
+    ///
+    /// [`MerkleProof::merkle_root`] relies on this to slot hashes into the right branches.
+    pub fn follows_key_path(&self) -> bool {
+        let key = Nibbles::from_raw(self.key.clone(), true);
+        let mut offset = 0;
+
+        for node in self.proof.iter() {
+            match node {
+                MerkleProofNode::ExtensionNode { prefix } => {
+                    let end = offset + prefix.len();
+                    if end >= key.len() || key.slice(offset, end) != *prefix {
+                        return false;
+                    }
+                    offset = end;
+                }
+                MerkleProofNode::BranchNode { index, .. } => {
+                    if offset + 1 >= key.len() || key.at(offset) != *index as usize {
+                        return false;
+                    }
+                    offset += 1;
+                }
+            }
+        }
+        true
+    }
+
     /// Given a transaction receipt, compute the Merkle root of the Patricia Merkle Trie using the
     /// rest of the Merkle proof.
+    ///
+    /// Extension prefixes are encoded as they are, a deserialized proof has only valid nibbles as
+    /// they are checked with [`Nibbles::try_from_hex`].
     pub fn merkle_root(&self, leaf: &TransactionReceipt) -> H256 {
         // Recovering a Merkle root from a Merkle proof involves computing the hash of the leaf node
         // and the hashes of the rest of the nodes in the proof.
@@ -116,3 +241,312 @@ impl MerkleProof {
         hash
     }
 }
+
+/// One line per node: its type, and the branch index with the slots of its siblings or the
+/// extension prefix, nibbles in hex.
+impl core::fmt::Display for MerkleProofNode {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            MerkleProofNode::ExtensionNode { prefix } => {
+                write!(f, "extension, prefix ")?;
+                for nibble in prefix.get_data() {
+                    write!(f, "{nibble:x}")?;
+                }
+                write!(f, " ({} nibbles)", prefix.len())
+            }
+            MerkleProofNode::BranchNode {
+                branches,
+                value,
+                index,
+            } => {
+                write!(f, "branch, index {index:x}, siblings [")?;
+                let populated = branches
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, branch)| branch.is_some());
+                for (i, (slot, _)) in populated.enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{slot:x}")?;
+                }
+                write!(f, "]")?;
+                if let Some(value) = value {
+                    write!(f, ", value of {} bytes", value.len())?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The structure of the proof, for logging one that fails to reconstruct the receipts root: the
+/// key and transaction index, then the nodes from the root down to the leaf.
+impl core::fmt::Display for MerkleProof {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "merkle proof of key 0x")?;
+        for byte in &self.key {
+            write!(f, "{byte:02x}")?;
+        }
+        match self.transaction_index() {
+            Some(index) => write!(f, " (transaction {index})")?,
+            None => write!(f, " (not a transaction index)")?,
+        }
+        write!(f, ", {} nodes", self.len())?;
+        for (i, node) in self.proof.iter().enumerate() {
+            write!(f, "\n  {i}: {node}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::{MerkleProof, MerkleProofNode, Nibbles};
+    use crate::H256;
+    #[cfg(feature = "merkle-proof")]
+    use crate::{Bloom, Receipt, TransactionReceipt, TxType};
+
+    /// `n` receipts without logs, told apart by their cumulative gas used.
+    #[cfg(feature = "merkle-proof")]
+    fn receipts(n: u64) -> Vec<TransactionReceipt> {
+        (0..n)
+            .map(|i| TransactionReceipt {
+                bloom: Bloom::new([0; 256]),
+                receipt: Receipt {
+                    tx_type: TxType::EIP1559,
+                    success: true,
+                    cumulative_gas_used: 21000 * (i + 1),
+                    logs: vec![],
+                },
+            })
+            .collect()
+    }
+
+    fn branch(index: u8) -> MerkleProofNode {
+        MerkleProofNode::BranchNode {
+            branches: Default::default(),
+            value: None,
+            index,
+        }
+    }
+
+    #[test]
+    fn single_merkle_root_api() {
+        // The crate root exports this proof type and nothing else that verifies receipts; both
+        // lines stop compiling if a second proof type with its own `merkle_root` takes its place.
+        let _: MerkleProof = crate::MerkleProof::default();
+        let _: fn(&crate::MerkleProof, &crate::TransactionReceipt) -> crate::H256 =
+            MerkleProof::merkle_root;
+    }
+
+    #[cfg(feature = "merkle-proof")]
+    #[test]
+    fn from_transactions_index_out_of_bounds() {
+        use crate::ValidationError;
+
+        let receipts = receipts(3);
+
+        let proof = MerkleProof::from_transactions(&receipts, 2).unwrap();
+        assert_eq!(proof.transaction_index(), Some(2));
+        assert!(matches!(
+            MerkleProof::from_transactions(&receipts, 3),
+            Err(ValidationError::TransactionIndexOutOfBounds { index: 3, len: 3 })
+        ));
+        assert!(matches!(
+            MerkleProof::from_transactions(&[], 0),
+            Err(ValidationError::TransactionIndexOutOfBounds { index: 0, len: 0 })
+        ));
+    }
+
+    #[cfg(feature = "merkle-proof")]
+    #[test]
+    fn single_receipt_proof_is_default_with_key() {
+        use alloy_rlp::Encodable;
+
+        let receipt = receipts(1).remove(0);
+        // Hash of the leaf node with the hex-prefixed even `path`, then the receipt
+        let leaf_hash = |path: &[u8]| {
+            let value = receipt.encode_to_vec();
+            let mut leaf = Vec::new();
+            alloy_rlp::Header {
+                list: true,
+                payload_length: path.length() + value.as_slice().length(),
+            }
+            .encode(&mut leaf);
+            path.encode(&mut leaf);
+            value.as_slice().encode(&mut leaf);
+            H256::keccak(&leaf)
+        };
+
+        // The default proof hashes the leaf alone, with an empty path
+        assert_eq!(
+            MerkleProof::default().merkle_root(&receipt),
+            leaf_hash(&[0x20])
+        );
+
+        // The root of a single receipt trie is the leaf with the path of key `0x80`
+        let proof = MerkleProof {
+            key: alloy_rlp::encode(0usize),
+            ..Default::default()
+        };
+        assert_eq!(
+            MerkleProof::from_transactions(core::slice::from_ref(&receipt), 0).unwrap(),
+            proof
+        );
+        assert_eq!(proof.merkle_root(&receipt), leaf_hash(&[0x20, 0x80]));
+        assert_ne!(
+            proof.merkle_root(&receipt),
+            MerkleProof::default().merkle_root(&receipt)
+        );
+    }
+
+    #[cfg(feature = "merkle-proof")]
+    #[test]
+    fn proof_size() {
+        let receipts = receipts(3);
+
+        // Keys 0x80, 0x01 and 0x02: a root branch on the first nibble, then one on the second
+        // for the keys starting with 0
+        let proof = MerkleProof::from_transactions(&receipts, 2).unwrap();
+        assert_eq!((proof.len(), proof.depth()), (2, 2));
+        // 2 hashes of 33 bytes, 15 empty items and the list header, twice, plus the key
+        assert_eq!(proof.encoded_byte_len(), 2 * 83 + 1);
+        let proof = MerkleProof::from_transactions(&receipts, 0).unwrap();
+        assert_eq!((proof.len(), proof.depth()), (1, 1));
+        assert_eq!(proof.encoded_byte_len(), 83 + 1);
+
+        let proof = MerkleProof::from_transactions(&receipts[..1], 0).unwrap();
+        assert!(proof.is_empty());
+        assert_eq!((proof.len(), proof.depth()), (0, 0));
+        assert_eq!(proof.encoded_byte_len(), proof.key.len());
+    }
+
+    #[cfg(feature = "merkle-proof")]
+    #[test]
+    fn describe_proof() {
+        let receipts = receipts(3);
+        // The root branch has the key 0x80 next to the keys starting with 0, whose branch
+        // has the key 0x01 next to 0x02
+        let proof = MerkleProof::from_transactions(&receipts, 2).unwrap();
+        assert_eq!(
+            proof.to_string(),
+            "merkle proof of key 0x02 (transaction 2), 2 nodes\n  \
+             0: branch, index 0, siblings [8]\n  \
+             1: branch, index 2, siblings [1]"
+        );
+
+        let mut branches: [Option<H256>; 16] = Default::default();
+        branches[0x3] = Some(H256([1; 32]));
+        branches[0xa] = Some(H256([2; 32]));
+        let proof = MerkleProof {
+            proof: vec![
+                MerkleProofNode::ExtensionNode {
+                    prefix: Nibbles::from_hex(vec![0x0, 0xa, 0xf]),
+                },
+                MerkleProofNode::BranchNode {
+                    branches: Box::new(branches),
+                    value: Some(vec![0; 4]),
+                    index: 0xc,
+                },
+            ],
+            key: vec![0x81],
+        };
+        assert_eq!(
+            proof.to_string(),
+            "merkle proof of key 0x81 (not a transaction index), 2 nodes\n  \
+             0: extension, prefix 0af (3 nibbles)\n  \
+             1: branch, index c, siblings [3 a], value of 4 bytes"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn fixture_proof_size() {
+        let proof: crate::EventProof = serde_json::from_str(include_str!(
+            "../../../pallet/tests/data/goerli/event_proof_8652100.json"
+        ))
+        .unwrap();
+        let proof = proof.merkle_proof_of_receipt;
+
+        assert_eq!(proof.len(), proof.proof.len());
+        assert_eq!((proof.len(), proof.depth()), (1, 1));
+        // A branch with 6 siblings and the slotted hash
+        assert_eq!(proof.encoded_byte_len(), 2 + 7 * 33 + 10 + 1);
+    }
+
+    #[test]
+    fn proofs_in_hash_set() {
+        let proof = |index: u8, hash: u8| {
+            let mut branches: [Option<H256>; 16] = Default::default();
+            branches[usize::from(index) ^ 1] = Some(H256([hash; 32]));
+            MerkleProof {
+                proof: vec![
+                    MerkleProofNode::ExtensionNode {
+                        prefix: Nibbles::from_hex(vec![index]),
+                    },
+                    MerkleProofNode::BranchNode {
+                        branches: Box::new(branches),
+                        value: None,
+                        index,
+                    },
+                ],
+                key: alloy_rlp::encode(usize::from(index)),
+            }
+        };
+
+        let mut set = HashSet::new();
+        assert!(set.insert(proof(1, 1)));
+        assert!(!set.insert(proof(1, 1)));
+        // Proofs differing only in a sibling hash or in the index are distinct
+        assert!(set.insert(proof(1, 2)));
+        assert!(set.insert(proof(2, 1)));
+        assert_eq!(set.len(), 3);
+        assert!(set.contains(&proof(1, 2)));
+    }
+
+    #[test]
+    fn transaction_index_from_key() {
+        for index in [0usize, 1, 127, 128, 255, 1000] {
+            let proof = MerkleProof {
+                proof: vec![],
+                key: alloy_rlp::encode(index),
+            };
+            assert_eq!(proof.transaction_index(), Some(index));
+        }
+
+        // Non-canonical encoding of 1 and trailing garbage are rejected.
+        for key in [vec![0x81, 0x01], vec![0x01, 0x02], vec![]] {
+            let proof = MerkleProof { proof: vec![], key };
+            assert_eq!(proof.transaction_index(), None);
+        }
+    }
+
+    #[test]
+    fn key_path_must_match_proof_nodes() {
+        // rlp(3) = 0x03 -> nibbles [0, 3, 16]
+        let key = alloy_rlp::encode(3usize);
+
+        let proof = MerkleProof {
+            proof: vec![branch(0), branch(3)],
+            key: key.clone(),
+        };
+        assert!(proof.follows_key_path());
+
+        // Branch index doesn't match the key nibble.
+        let proof = MerkleProof {
+            proof: vec![branch(0), branch(4)],
+            key: key.clone(),
+        };
+        assert!(!proof.follows_key_path());
+
+        // Proof is deeper than the key.
+        let proof = MerkleProof {
+            proof: vec![branch(0), branch(3), branch(0)],
+            key,
+        };
+        assert!(!proof.follows_key_path());
+    }
+}
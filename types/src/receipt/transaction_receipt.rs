@@ -1,7 +1,7 @@
 use alloc::vec::Vec;
-use alloy_rlp::{BufMut, BytesMut, Encodable};
+use alloy_rlp::{BufMut, Encodable};
 
-use crate::{Bloom, Log};
+use crate::{Bloom, Log, H160};
 
 use super::tx_type::TxType;
 
@@ -29,7 +29,9 @@ pub struct Receipt {
     pub tx_type: TxType,
     /// If transaction is executed successfully.
     ///
-    /// This is the `statusCode`
+    /// This is the `statusCode` of EIP-658, which is either 1 or 0. Receipts without it, i.e.
+    /// pre-Byzantium receipts that carry a state root instead, or with other status codes can't be
+    /// represented.
     pub success: bool,
     /// Gas used
     pub cumulative_gas_used: u64,
@@ -37,7 +39,40 @@ pub struct Receipt {
     pub logs: Vec<Log>,
 }
 
+impl Receipt {
+    /// The EIP-658 status code encoded into the receipt, 1 on success and 0 on failure.
+    pub fn status_code(&self) -> u8 {
+        u8::from(self.success)
+    }
+}
+
 impl TransactionReceipt {
+    /// The RLP encoding of the receipt, which is its value in the receipts trie.
+    pub fn encode_to_vec(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.length());
+        self.encode(&mut out);
+        out
+    }
+
+    /// Whether `bloom` is the bloom of the receipt's logs. The bloom is hashed into the receipt as
+    /// provided, so this catches a bloom tampered to hide or claim logs before it is relied on.
+    pub fn verify_bloom(&self) -> bool {
+        Bloom::from_logs(&self.receipt.logs) == self.bloom
+    }
+
+    /// Logs emitted by the contract at `address`, in order.
+    pub fn logs_for_address(&self, address: H160) -> impl Iterator<Item = &Log> {
+        self.receipt
+            .logs
+            .iter()
+            .filter(move |log| log.address.ct_eq(&address))
+    }
+
+    /// Whether the contract at `address` emitted any log.
+    pub fn has_log_from(&self, address: H160) -> bool {
+        self.logs_for_address(address).next().is_some()
+    }
+
     fn encode_fields(&self, out: &mut dyn BufMut) {
         let list_encode: [&dyn Encodable; 4] = [
             &self.receipt.success,
@@ -64,27 +99,15 @@ impl Encodable for TransactionReceipt {
     }
 
     fn encode(&self, out: &mut dyn alloy_rlp::BufMut) {
-        if matches!(self.receipt.tx_type, TxType::Legacy) {
-            self.encode_fields(out);
-            return;
-        }
-
-        let mut payload = BytesMut::new();
-        self.encode_fields(&mut payload);
-
-        match self.receipt.tx_type {
-            TxType::EIP2930 => {
-                out.put_u8(0x01);
-            }
-            TxType::EIP1559 => {
-                out.put_u8(0x02);
-            }
-            TxType::EIP4844 => {
-                out.put_u8(0x03);
-            }
-            _ => unreachable!("legacy handled; qed."),
-        }
-        out.put_slice(payload.as_ref());
+        // Typed receipts are prefixed with their type byte, see EIP-2718
+        let type_byte = match self.receipt.tx_type {
+            TxType::Legacy => return self.encode_fields(out),
+            TxType::EIP2930 => 0x01,
+            TxType::EIP1559 => 0x02,
+            TxType::EIP4844 => 0x03,
+        };
+        out.put_u8(type_byte);
+        self.encode_fields(out);
     }
 }
 
@@ -123,9 +146,324 @@ mod tests {
         };
 
         receipt.encode(&mut data);
+        assert_eq!(receipt.encode_to_vec(), data);
+        // The status follows the list header
+        assert_eq!(receipt.receipt.status_code(), 0);
+        assert_eq!(data[3], alloy_rlp::EMPTY_STRING_CODE);
 
         // check that the rlp length equals the length of the expected rlp
         assert_eq!(receipt.length(), expected.len());
         assert_eq!(data, expected);
     }
+
+    /// Logs shared by the vectors below: several logs with up to three topics, one without
+    /// topics and data long enough to need the long string encoding. The expected bytes were
+    /// produced by a standalone RLP encoder as the EIP-2718 type byte (none for legacy) followed
+    /// by `rlp([status, cumulative_gas_used, bloom, logs])`.
+    fn logs() -> Vec<Log> {
+        vec![
+            Log {
+                address: H160(hex!("0000000000000000000000000000000000000011")),
+                topics: vec![
+                    H256(hex!(
+                        "000000000000000000000000000000000000000000000000000000000000dead"
+                    )),
+                    H256(hex!(
+                        "000000000000000000000000000000000000000000000000000000000000beef"
+                    )),
+                ],
+                data: hex!("0100ff").to_vec(),
+            },
+            Log {
+                address: H160(hex!("0000000000000000000000000000000000000022")),
+                topics: vec![H256([0x11; 32]), H256([0x22; 32]), H256([0x33; 32])],
+                data: vec![],
+            },
+            Log {
+                address: H160(hex!("0000000000000000000000000000000000000033")),
+                topics: vec![],
+                data: (0..60).collect(),
+            },
+        ]
+    }
+
+    fn assert_encoding(receipt: &TransactionReceipt, expected: &[u8]) {
+        assert_eq!(receipt.length(), expected.len());
+        assert_eq!(receipt.encode_to_vec(), expected);
+    }
+
+    #[test]
+    fn encode_legacy_receipt_multiple_logs() {
+        let receipt = TransactionReceipt {
+            receipt: Receipt {
+                tx_type: TxType::Legacy,
+                cumulative_gas_used: 0x5208,
+                logs: logs(),
+                success: true,
+            },
+            bloom: Bloom::new([0; 256]),
+        };
+
+        assert_encoding(
+            &receipt,
+            &hex!(
+                "f9023c01825208b90100000000000000000000000000000000000000000000000000000000000000"
+                "00000000000000000000000000000000000000000000000000000000000000000000000000000000"
+                "00000000000000000000000000000000000000000000000000000000000000000000000000000000"
+                "00000000000000000000000000000000000000000000000000000000000000000000000000000000"
+                "00000000000000000000000000000000000000000000000000000000000000000000000000000000"
+                "00000000000000000000000000000000000000000000000000000000000000000000000000000000"
+                "0000000000000000000000000000000000000000000000000000f90132f85d940000000000000000"
+                "000000000000000000000011f842a000000000000000000000000000000000000000000000000000"
+                "0000000000deada0000000000000000000000000000000000000000000000000000000000000beef"
+                "830100fff87b940000000000000000000000000000000000000022f863a011111111111111111111"
+                "11111111111111111111111111111111111111111111a02222222222222222222222222222222222"
+                "222222222222222222222222222222a0333333333333333333333333333333333333333333333333"
+                "333333333333333380f854940000000000000000000000000000000000000033c0b83c0001020304"
+                "05060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f202122232425262728292a2b2c"
+                "2d2e2f303132333435363738393a3b"
+            ),
+        );
+    }
+
+    #[test]
+    fn encode_eip2930_receipt_multiple_logs() {
+        let receipt = TransactionReceipt {
+            receipt: Receipt {
+                tx_type: TxType::EIP2930,
+                cumulative_gas_used: 0x10000,
+                logs: logs(),
+                success: false,
+            },
+            bloom: Bloom::new([0; 256]),
+        };
+
+        assert_encoding(
+            &receipt,
+            &hex!(
+                "01f9023d8083010000b9010000000000000000000000000000000000000000000000000000000000"
+                "00000000000000000000000000000000000000000000000000000000000000000000000000000000"
+                "00000000000000000000000000000000000000000000000000000000000000000000000000000000"
+                "00000000000000000000000000000000000000000000000000000000000000000000000000000000"
+                "00000000000000000000000000000000000000000000000000000000000000000000000000000000"
+                "00000000000000000000000000000000000000000000000000000000000000000000000000000000"
+                "00000000000000000000000000000000000000000000000000000000f90132f85d94000000000000"
+                "0000000000000000000000000011f842a00000000000000000000000000000000000000000000000"
+                "00000000000000deada0000000000000000000000000000000000000000000000000000000000000"
+                "beef830100fff87b940000000000000000000000000000000000000022f863a01111111111111111"
+                "111111111111111111111111111111111111111111111111a0222222222222222222222222222222"
+                "2222222222222222222222222222222222a033333333333333333333333333333333333333333333"
+                "3333333333333333333380f854940000000000000000000000000000000000000033c0b83c000102"
+                "030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f202122232425262728292a"
+                "2b2c2d2e2f303132333435363738393a3b"
+            ),
+        );
+    }
+
+    #[test]
+    fn encode_eip1559_receipt_multiple_logs() {
+        let receipt = TransactionReceipt {
+            receipt: Receipt {
+                tx_type: TxType::EIP1559,
+                cumulative_gas_used: 0xabcdef,
+                logs: logs(),
+                success: true,
+            },
+            bloom: Bloom::new([0; 256]),
+        };
+
+        assert_encoding(
+            &receipt,
+            &hex!(
+                "02f9023d0183abcdefb9010000000000000000000000000000000000000000000000000000000000"
+                "00000000000000000000000000000000000000000000000000000000000000000000000000000000"
+                "00000000000000000000000000000000000000000000000000000000000000000000000000000000"
+                "00000000000000000000000000000000000000000000000000000000000000000000000000000000"
+                "00000000000000000000000000000000000000000000000000000000000000000000000000000000"
+                "00000000000000000000000000000000000000000000000000000000000000000000000000000000"
+                "00000000000000000000000000000000000000000000000000000000f90132f85d94000000000000"
+                "0000000000000000000000000011f842a00000000000000000000000000000000000000000000000"
+                "00000000000000deada0000000000000000000000000000000000000000000000000000000000000"
+                "beef830100fff87b940000000000000000000000000000000000000022f863a01111111111111111"
+                "111111111111111111111111111111111111111111111111a0222222222222222222222222222222"
+                "2222222222222222222222222222222222a033333333333333333333333333333333333333333333"
+                "3333333333333333333380f854940000000000000000000000000000000000000033c0b83c000102"
+                "030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f202122232425262728292a"
+                "2b2c2d2e2f303132333435363738393a3b"
+            ),
+        );
+    }
+
+    #[test]
+    fn encode_eip4844_receipt_multiple_logs() {
+        let receipt = TransactionReceipt {
+            receipt: Receipt {
+                tx_type: TxType::EIP4844,
+                cumulative_gas_used: 0x123456789,
+                logs: logs(),
+                success: true,
+            },
+            bloom: Bloom::new([0; 256]),
+        };
+
+        assert_encoding(
+            &receipt,
+            &hex!(
+                "03f9023f01850123456789b901000000000000000000000000000000000000000000000000000000"
+                "00000000000000000000000000000000000000000000000000000000000000000000000000000000"
+                "00000000000000000000000000000000000000000000000000000000000000000000000000000000"
+                "00000000000000000000000000000000000000000000000000000000000000000000000000000000"
+                "00000000000000000000000000000000000000000000000000000000000000000000000000000000"
+                "00000000000000000000000000000000000000000000000000000000000000000000000000000000"
+                "000000000000000000000000000000000000000000000000000000000000f90132f85d9400000000"
+                "00000000000000000000000000000011f842a0000000000000000000000000000000000000000000"
+                "000000000000000000deada000000000000000000000000000000000000000000000000000000000"
+                "0000beef830100fff87b940000000000000000000000000000000000000022f863a0111111111111"
+                "1111111111111111111111111111111111111111111111111111a022222222222222222222222222"
+                "22222222222222222222222222222222222222a03333333333333333333333333333333333333333"
+                "33333333333333333333333380f854940000000000000000000000000000000000000033c0b83c00"
+                "0102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f202122232425262728"
+                "292a2b2c2d2e2f303132333435363738393a3b"
+            ),
+        );
+    }
+
+    // Receipt of mainnet transaction 0x1b8959ec14ce33b0b9f9f0308de0f26174895ca619183ef802ce52ce09742ae8
+    // as returned by go-ethereum. It is part of block 17819525, whose receipts root the merkle
+    // tests check.
+    fn mainnet_eip1559_receipt() -> TransactionReceipt {
+        TransactionReceipt {
+            receipt: Receipt {
+                tx_type: TxType::EIP1559,
+                cumulative_gas_used: 0xec361b,
+                logs: vec![
+                    Log {
+                        address: H160(hex!("836a808d4828586a69364065a1e064609f5078c7")),
+                        topics: vec![
+                            H256(hex!(
+                                "8c5be1e5ebec7d5bd14f71427d1e84f3dd0314c0f7b2291e5b200ac8c7c3b925"
+                            )),
+                            H256(hex!(
+                                "000000000000000000000000baa11401e61cc134ea2b2f3f28fe1344e64c51c3"
+                            )),
+                            H256(hex!(
+                                "0000000000000000000000004e5f305bfca77b17f804635a9ba669e187d51719"
+                            )),
+                        ],
+                        data: hex!(
+                            "0000000000000000000000000000000000000000000000193eee0f8a9265f431"
+                        )
+                        .to_vec(),
+                    },
+                    Log {
+                        address: H160(hex!("836a808d4828586a69364065a1e064609f5078c7")),
+                        topics: vec![
+                            H256(hex!(
+                                "8c5be1e5ebec7d5bd14f71427d1e84f3dd0314c0f7b2291e5b200ac8c7c3b925"
+                            )),
+                            H256(hex!(
+                                "000000000000000000000000baa11401e61cc134ea2b2f3f28fe1344e64c51c3"
+                            )),
+                            H256(hex!(
+                                "0000000000000000000000004e5f305bfca77b17f804635a9ba669e187d51719"
+                            )),
+                        ],
+                        data: hex!(
+                            "0000000000000000000000000000000000000000000000000000000000000000"
+                        )
+                        .to_vec(),
+                    },
+                ],
+                success: true,
+            },
+            bloom: Bloom::new(hex!(
+                "02000000000000000000000000000000000000000000000000000000000000000000000000000004"
+                "00000000000000000000000100000000001000000020000000000000000000000000000000000000"
+                "00000000000000000000000000000000000000000000000000000000000000000000000000000000"
+                "00000000000000000000000000000000000000000000000000100000000000000000000000000000"
+                "02000800000000000000000000000000000000000000000000000000000000000000001000000000"
+                "00000000000000000000000000000001000000000000000000100000000000000000000000000000"
+                "00000000000000000000000000000400"
+            )),
+        }
+    }
+
+    #[test]
+    fn encode_mainnet_eip1559_receipt() {
+        assert_encoding(
+            &mainnet_eip1559_receipt(),
+            &hex!(
+                "02f902450183ec361bb9010002000000000000000000000000000000000000000000000000000000"
+                "00000000000000000000000400000000000000000000000100000000001000000020000000000000"
+                "00000000000000000000000000000000000000000000000000000000000000000000000000000000"
+                "00000000000000000000000000000000000000000000000000000000000000000000000000100000"
+                "00000000000000000000000002000800000000000000000000000000000000000000000000000000"
+                "00000000000000100000000000000000000000000000000000000001000000000000000000100000"
+                "00000000000000000000000000000000000000000000000000000400f9013af89b94836a808d4828"
+                "586a69364065a1e064609f5078c7f863a08c5be1e5ebec7d5bd14f71427d1e84f3dd0314c0f7b229"
+                "1e5b200ac8c7c3b925a0000000000000000000000000baa11401e61cc134ea2b2f3f28fe1344e64c"
+                "51c3a00000000000000000000000004e5f305bfca77b17f804635a9ba669e187d51719a000000000"
+                "00000000000000000000000000000000000000193eee0f8a9265f431f89b94836a808d4828586a69"
+                "364065a1e064609f5078c7f863a08c5be1e5ebec7d5bd14f71427d1e84f3dd0314c0f7b2291e5b20"
+                "0ac8c7c3b925a0000000000000000000000000baa11401e61cc134ea2b2f3f28fe1344e64c51c3a0"
+                "0000000000000000000000004e5f305bfca77b17f804635a9ba669e187d51719a000000000000000"
+                "00000000000000000000000000000000000000000000000000"
+            ),
+        );
+    }
+
+    #[test]
+    fn verify_bloom() {
+        let receipt = mainnet_eip1559_receipt();
+        assert!(receipt.verify_bloom());
+
+        // A bloom hiding the logs
+        let mut tampered = receipt.clone();
+        tampered.bloom = Bloom::new([0; 256]);
+        assert!(!tampered.verify_bloom());
+
+        // A bloom claiming topics that aren't there
+        let mut tampered = receipt.clone();
+        tampered.receipt.logs.pop();
+        tampered.receipt.logs[0].topics.pop();
+        assert!(!tampered.verify_bloom());
+
+        let mut empty = receipt;
+        empty.receipt.logs.clear();
+        assert!(!empty.verify_bloom());
+        empty.bloom = Bloom::new([0; 256]);
+        assert!(empty.verify_bloom());
+    }
+
+    #[test]
+    fn logs_for_address() {
+        let receipt = TransactionReceipt {
+            receipt: Receipt {
+                tx_type: TxType::Legacy,
+                cumulative_gas_used: 0x5208,
+                logs: logs(),
+                success: true,
+            },
+            bloom: Bloom::new([0; 256]),
+        };
+
+        let none = H160(hex!("0000000000000000000000000000000000000044"));
+        assert_eq!(receipt.logs_for_address(none).count(), 0);
+        assert!(!receipt.has_log_from(none));
+
+        let one = H160(hex!("0000000000000000000000000000000000000022"));
+        assert_eq!(
+            receipt.logs_for_address(one).collect::<Vec<_>>(),
+            [&receipt.receipt.logs[1]]
+        );
+        assert!(receipt.has_log_from(one));
+
+        let multiple = mainnet_eip1559_receipt();
+        let address = H160(hex!("836a808d4828586a69364065a1e064609f5078c7"));
+        assert_eq!(
+            multiple.logs_for_address(address).collect::<Vec<_>>(),
+            multiple.receipt.logs.iter().collect::<Vec<_>>()
+        );
+        assert!(multiple.has_log_from(address));
+    }
 }
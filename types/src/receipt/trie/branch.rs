@@ -100,8 +100,7 @@ mod tests {
                     },
                 };
 
-                let mut receipt_encoded = vec![];
-                receipt.encode(&mut receipt_encoded);
+                let receipt_encoded = receipt.encode_to_vec();
 
                 let leaf =
                     Leaf::from_transaction_receipt(Nibbles::from_raw(vec![i], true), receipt);
@@ -18,7 +18,7 @@ impl Leaf {
     pub fn from_transaction_receipt(key: Nibbles, value: TransactionReceipt) -> Self {
         Self {
             key: key.encode_compact(),
-            value: alloy_rlp::encode(value),
+            value: value.encode_to_vec(),
         }
     }
 }
@@ -101,8 +101,7 @@ mod tests {
             },
         };
 
-        let mut receipt_encoded = vec![];
-        receipt.encode(&mut receipt_encoded);
+        let receipt_encoded = receipt.encode_to_vec();
 
         let our_leaf =
             Leaf::from_transaction_receipt(Nibbles::from_raw(key.clone(), true), receipt);
@@ -1,16 +1,61 @@
 use alloc::vec::Vec;
 
-#[derive(Debug, Clone, Eq, PartialEq, Default)]
+/// Nibble values below 16, optionally followed by the leaf terminator 16. Deserializing checks the
+/// values with [`Nibbles::try_from_hex`].
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Default)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "NibblesData"))]
 pub struct Nibbles {
     hex_data: Vec<u8>,
 }
 
+/// A value of [`Nibbles`] that is not a nibble, or a leaf terminator that is not the last value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidNibble {
+    pub position: usize,
+    pub value: u8,
+}
+
+impl core::fmt::Display for InvalidNibble {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "invalid nibble {} at {}", self.value, self.position)
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct NibblesData {
+    hex_data: Vec<u8>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<NibblesData> for Nibbles {
+    type Error = InvalidNibble;
+
+    fn try_from(data: NibblesData) -> Result<Self, Self::Error> {
+        Nibbles::try_from_hex(data.hex_data)
+    }
+}
+
 impl Nibbles {
     pub fn from_hex(hex: Vec<u8>) -> Self {
         Nibbles { hex_data: hex }
     }
 
+    /// Like [`Nibbles::from_hex`], but fails on values above 15, except for a leaf terminator
+    /// 16 as the last value.
+    pub fn try_from_hex(hex: Vec<u8>) -> Result<Self, InvalidNibble> {
+        let last = hex.len().saturating_sub(1);
+        match hex
+            .iter()
+            .enumerate()
+            .find(|&(position, &value)| value > 16 || (value == 16 && position != last))
+        {
+            Some((position, &value)) => Err(InvalidNibble { position, value }),
+            None => Ok(Nibbles { hex_data: hex }),
+        }
+    }
+
     pub fn from_raw(raw: Vec<u8>, is_leaf: bool) -> Self {
         let mut hex_data = vec![];
         for item in raw.into_iter() {
@@ -164,7 +209,7 @@ impl Nibbles {
 mod tests {
     use hex_literal::hex;
 
-    use crate::receipt::trie::nibble::Nibbles;
+    use crate::receipt::trie::nibble::{InvalidNibble, Nibbles};
 
     #[test]
     fn test_nibble() {
@@ -186,6 +231,41 @@ mod tests {
         assert_eq!(encoded, expected);
     }
 
+    #[test]
+    fn try_from_hex_checks_nibbles() {
+        for hex in [vec![], vec![0, 15], vec![16], vec![1, 2, 16]] {
+            assert_eq!(
+                Nibbles::try_from_hex(hex.clone()),
+                Ok(Nibbles::from_hex(hex))
+            );
+        }
+        for (hex, position, value) in [
+            (vec![0x10, 1], 0, 16),
+            (vec![1, 0x20], 1, 0x20),
+            (vec![1, 2, 0xff], 2, 0xff),
+            (vec![16, 16], 0, 16),
+        ] {
+            assert_eq!(
+                Nibbles::try_from_hex(hex),
+                Err(InvalidNibble { position, value })
+            );
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserializing_checks_nibbles() {
+        let nibbles: Nibbles = serde_json::from_str(r#"{"hex_data":[1,15,16]}"#).unwrap();
+        assert_eq!(nibbles, Nibbles::from_hex(vec![1, 15, 16]));
+        assert_eq!(
+            serde_json::to_string(&nibbles).unwrap(),
+            r#"{"hex_data":[1,15,16]}"#
+        );
+
+        let err = serde_json::from_str::<Nibbles>(r#"{"hex_data":[1,17]}"#).unwrap_err();
+        assert!(err.to_string().contains("invalid nibble 17 at 1"), "{err}");
+    }
+
     #[test]
     fn hashed_regression() {
         let mut nibbles = hex!("05010406040a040203030f010805020b050c04070003070e0909070f010b0a0805020301070c0a0902040b0f000f0006040a04050f020b090701000a0a040b").to_vec();
@@ -17,18 +17,31 @@ pub enum TxType {
     EIP4844 = 3_isize,
 }
 
-impl TxType {
-    pub fn from_u64(value: u64) -> Option<Self> {
+/// A transaction type byte that doesn't correspond to any [`TxType`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct UnknownTxType(pub u8);
+
+impl TryFrom<u8> for TxType {
+    type Error = UnknownTxType;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
         match value {
-            0 => Some(Self::Legacy),
-            1 => Some(Self::EIP2930),
-            2 => Some(Self::EIP1559),
-            3 => Some(Self::EIP4844),
-            _ => None,
+            0 => Ok(Self::Legacy),
+            1 => Ok(Self::EIP2930),
+            2 => Ok(Self::EIP1559),
+            3 => Ok(Self::EIP4844),
+            _ => Err(UnknownTxType(value)),
         }
     }
 }
 
+impl TxType {
+    /// Parses the `type` field of an RPC receipt, `None` if the type is unknown.
+    pub fn from_u64(value: u64) -> Option<Self> {
+        u8::try_from(value).ok()?.try_into().ok()
+    }
+}
+
 impl Encodable for TxType {
     /// TxType is encoded as [`u8`][1].
     ///
@@ -66,4 +79,26 @@ mod tests {
         Encodable::encode(&TxType::EIP4844, &mut buf);
         assert_eq!(buf[..], [3]);
     }
+
+    #[test]
+    fn try_from_u8() {
+        for tx_type in [
+            TxType::Legacy,
+            TxType::EIP2930,
+            TxType::EIP1559,
+            TxType::EIP4844,
+        ] {
+            assert_eq!(TxType::try_from(tx_type as u8), Ok(tx_type));
+            assert_eq!(TxType::from_u64(tx_type as u64), Some(tx_type));
+        }
+
+        assert_eq!(TxType::try_from(4), Err(UnknownTxType(4)));
+        assert_eq!(TxType::try_from(0x7e), Err(UnknownTxType(0x7e)));
+        assert_eq!(TxType::try_from(u8::MAX), Err(UnknownTxType(u8::MAX)));
+        assert_eq!(TxType::from_u64(4), None);
+        // Must not wrap around to a known type
+        assert_eq!(TxType::from_u64(0x100), None);
+        assert_eq!(TxType::from_u64(0x102), None);
+        assert_eq!(TxType::from_u64(u64::MAX), None);
+    }
 }
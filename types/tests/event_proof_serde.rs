@@ -0,0 +1,38 @@
+#![cfg(all(feature = "serde", feature = "merkle-proof"))]
+
+use types::EventProof;
+
+#[path = "../../merkle/tests/common.rs"]
+mod common;
+
+// The pallet deserializes exactly what the relayer serializes, a proof surviving the round trip
+// unchanged and still valid is what makes it accepted on-chain.
+#[test]
+fn event_proof_serde_round_trip_17819525() {
+    let (block_hash, block_header) =
+        common::load_block(include_str!("../../merkle/tests/suits/block_17819525.json"));
+    let receipts = common::load_receipts(include_str!(
+        "../../merkle/tests/suits/block_17819525_receipts.json"
+    ));
+
+    for index in [0, receipts.len() / 2, receipts.len() - 1] {
+        let proof = EventProof::build(block_header.clone(), block_hash, &receipts, index).unwrap();
+
+        let encoded = serde_json::to_vec(&proof).unwrap();
+        let decoded: EventProof =
+            serde_json::from_str(std::str::from_utf8(&encoded).unwrap()).unwrap();
+
+        assert_eq!(decoded.block_header, proof.block_header);
+        assert_eq!(decoded.block_hash, proof.block_hash);
+        assert_eq!(decoded.transaction_receipt, proof.transaction_receipt);
+        assert_eq!(
+            decoded.transaction_receipt_hash,
+            proof.transaction_receipt_hash
+        );
+        assert_eq!(
+            decoded.merkle_proof_of_receipt,
+            proof.merkle_proof_of_receipt
+        );
+        decoded.validate().unwrap();
+    }
+}